@@ -0,0 +1,117 @@
+// Periodic autosaving of the spline state, so a crash doesn't lose all unsaved work. Native
+// builds write to a file in the user's data directory, which unlike the temp directory survives a
+// reboot, while the web build uses the browser's localStorage. The autosave is removed whenever the splines get saved manually, so if one exists
+// on startup it is newer than the last manual save.
+use anyhow::*;
+use web_time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Seconds since the Unix epoch, which is how the time of the autosave is stored
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn exists() -> bool {
+    backend::exists()
+}
+
+pub fn read() -> Option<String> {
+    backend::read()
+}
+
+pub fn write(serialized_state: &str) -> Result<()> {
+    backend::write(serialized_state, now())
+}
+
+pub fn remove() {
+    backend::remove();
+}
+
+// How long ago the autosave was written, if there is one
+pub fn age() -> Option<Duration> {
+    let timestamp = backend::timestamp()?;
+    Some(Duration::from_secs(now().saturating_sub(timestamp)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::path::PathBuf;
+    use web_time::UNIX_EPOCH;
+
+    use anyhow::*;
+
+    fn path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("spline_generator").join("autosave.json"))
+    }
+
+    pub fn exists() -> bool {
+        path().is_some_and(|path| path.exists())
+    }
+
+    pub fn read() -> Option<String> {
+        std::fs::read_to_string(path()?).ok()
+    }
+
+    // The file's modification time doubles as the timestamp, so there's no need to store it
+    pub fn write(serialized_state: &str, _timestamp: u64) -> Result<()> {
+        // Write to a temporary file first so a crash mid-write can't corrupt the previous autosave
+        let path = path().context("no data directory to autosave to")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, serialized_state)?;
+        std::fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    pub fn timestamp() -> Option<u64> {
+        let modified = std::fs::metadata(path()?).ok()?.modified().ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+    }
+
+    pub fn remove() {
+        if let Some(path) = path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use anyhow::*;
+
+    const STATE_KEY: &str = "spline_generator_autosave";
+    const TIMESTAMP_KEY: &str = "spline_generator_autosave_time";
+
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    // localStorage has no way to check for a key without getting its value
+    pub fn exists() -> bool {
+        read().is_some()
+    }
+
+    pub fn read() -> Option<String> {
+        storage()?.get_item(STATE_KEY).ok()?
+    }
+
+    pub fn write(serialized_state: &str, timestamp: u64) -> Result<()> {
+        let storage = storage().ok_or_else(|| anyhow!("localStorage is unavailable"))?;
+        // Setting an item throws if it goes over the storage quota
+        storage.set_item(STATE_KEY, serialized_state).map_err(|e| anyhow!("failed to write to localStorage: {e:?}"))?;
+        storage.set_item(TIMESTAMP_KEY, &timestamp.to_string()).map_err(|e| anyhow!("failed to write to localStorage: {e:?}"))?;
+        Ok(())
+    }
+
+    pub fn timestamp() -> Option<u64> {
+        storage()?.get_item(TIMESTAMP_KEY).ok()??.parse().ok()
+    }
+
+    pub fn remove() {
+        if let Some(storage) = storage() {
+            let _ = storage.remove_item(STATE_KEY);
+            let _ = storage.remove_item(TIMESTAMP_KEY);
+        }
+    }
+}
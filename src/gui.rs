@@ -1,17 +1,36 @@
 use crate::RenderState;
-use crate::world::{map, spline, World};
+use crate::keybinds;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::texture;
+use crate::world::{camera, map, spline, World};
 use crate::world::spline::export;
 
+use cgmath::{InnerSpace, MetricSpace};
 use egui::{Context, DragValue};
 use egui_winit::{EventResponse, State};
 use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
 use noop_waker::noop_waker;
 use rfd::AsyncFileDialog;
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
 use winit::event::WindowEvent;
 use winit::window::Window;
 
+// Number of recent frames the frame time min/max are taken over
+const FRAME_TIME_WINDOW: usize = 120;
+
+// Linear luminance of the viewport background above which the text drawn over it is black
+// instead of white. The default background is just above it.
+const DARK_TEXT_LUMINANCE: f32 = 0.15;
+
+// A file dialog and the reading or writing done with the picked file, polled every frame until done
+type FileFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+// File name of a VMF along with what was parsed from it
+type LoadedVmf = (String, anyhow::Result<map::MapGeometry>);
+
 pub struct Gui {
     // egui variables
     state: State,
@@ -19,18 +38,46 @@ pub struct Gui {
 
     // gui state
     menu_selection: GuiMenu,
-    snap_value: f32,
     bundle_spline: u32,
     bundle_point: u32,
     bundle_slot: u32,
+    show_point_labels: bool,
+    export_selected_only: bool,
+    export_separately: bool,
+    export_origin: export::ExportOrigin,
+    export_custom_origin: [f32; 3],
+    export_obj_up_axis: export::ObjUpAxis,
+    #[cfg(not(target_arch = "wasm32"))]
+    overview_splines_on_top: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    overview_error: Option<String>,
+    tangent_fraction: f32,
+    smooth_strength: f32,
+    smooth_magnitudes: bool,
+    unify_handles: bool,
+    repeat_count: u32,
+    repeat_offset: [f32; 3],
+    selection_offset: [f32; 3],
+    dragged_point: Option<usize>, // Row being dragged in the point list
+    file_hovered: bool, // Whether a file is being dragged over the window
 
-    vmf_future: Option<Pin<Box<dyn Future<Output = Option<String>>>>>,
-    load_state_future: Option<Pin<Box<dyn Future<Output = Option<String>>>>>,
-    save_state_future: Option<Pin<Box<dyn Future<Output = ()>>>>,
-    export_spline_future: Option<Pin<Box<dyn Future<Output = ()>>>>,
+    vmf_future: Option<FileFuture<Option<LoadedVmf>>>,
+    vmf_append: bool, // Whether the VMF being picked gets added to the current map instead of replacing it
+    load_state_future: Option<FileFuture<Option<Vec<u8>>>>,
+    append_state_future: Option<FileFuture<Option<Vec<u8>>>>,
+    save_state_future: Option<FileFuture<()>>,
+    export_spline_future: Option<FileFuture<()>>,
+    #[cfg(not(target_arch = "wasm32"))] // Overviews are only available natively, see World::render_overview
+    overview_readback: Option<texture::RenderTargetReadback>,
+    #[cfg(not(target_arch = "wasm32"))]
+    export_overview_future: Option<FileFuture<()>>,
 
     avg_frame_time: f64,
+    recent_frame_times: VecDeque<f64>, // Last FRAME_TIME_WINDOW frame times in seconds
+    show_fps: bool,
+    show_frame_times: bool, // Whether to show frame time in ms alongside the FPS
     window_swapped: bool,
+    autosave_prompt: bool, // Whether to ask about restoring an autosave left over from last session
 }
 
 #[derive(Eq, PartialEq)]
@@ -72,18 +119,46 @@ impl Gui {
             renderer,
 
             menu_selection: GuiMenu::Controls,
-            snap_value: 64.0,
             bundle_spline: 1,
             bundle_point: 1,
             bundle_slot: 1,
+            show_point_labels: false,
+            export_selected_only: false,
+            export_separately: false,
+            export_origin: export::ExportOrigin::FirstPoint,
+            export_custom_origin: [0.0; 3],
+            export_obj_up_axis: export::ObjUpAxis::Y,
+            #[cfg(not(target_arch = "wasm32"))]
+            overview_splines_on_top: true,
+            #[cfg(not(target_arch = "wasm32"))]
+            overview_error: None,
+            tangent_fraction: 1.0,
+            smooth_strength: 1.0,
+            smooth_magnitudes: false,
+            unify_handles: true,
+            repeat_count: 1,
+            repeat_offset: [0.0; 3],
+            selection_offset: [0.0; 3],
+            dragged_point: None,
+            file_hovered: false,
 
             vmf_future: None,
+            vmf_append: false,
             load_state_future: None,
+            append_state_future: None,
             save_state_future: None,
             export_spline_future: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            overview_readback: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            export_overview_future: None,
 
             avg_frame_time: 1.0 / 60.0, // 60 FPS is a reasonable starting assumption
+            recent_frame_times: VecDeque::with_capacity(FRAME_TIME_WINDOW),
+            show_fps: true,
+            show_frame_times: false,
             window_swapped: false,
+            autosave_prompt: crate::autosave::exists(),
         }
     }
 
@@ -91,6 +166,46 @@ impl Gui {
         self.state.on_window_event(window, event)
     }
 
+    pub fn is_pointer_over_gui(&self) -> bool {
+        self.state.egui_ctx().is_pointer_over_area()
+    }
+
+    pub fn autosave_prompt_open(&self) -> bool {
+        self.autosave_prompt
+    }
+
+    pub fn set_file_hovered(&mut self, hovered: bool) {
+        self.file_hovered = hovered;
+    }
+
+    // Open a file dropped onto the window, going through the same futures as the file pickers so
+    // update() loads it like any other. VMFs replace the map, while saves replace the splines.
+    pub fn open_dropped_file(&mut self, path: PathBuf) {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "vmf" if self.vmf_future.is_none() => {
+                self.vmf_append = false;
+                self.vmf_future = Some(Box::pin(async move {
+                    let name = path.file_name()?.to_string_lossy().into_owned();
+                    Some((name, map::Map::geometry_from_file(&path)))
+                }));
+            },
+            "json" | "splb" if self.load_state_future.is_none() => {
+                self.load_state_future = Some(Box::pin(async move {
+                    match std::fs::read(&path) {
+                        Ok(contents) => Some(contents),
+                        Err(e) => {
+                            log::warn!("failed to read dropped file {}: {e}", path.display());
+                            None
+                        }
+                    }
+                }));
+            },
+            "vmf" | "json" | "splb" => log::warn!("still loading another file, ignoring {}", path.display()),
+            _ => log::warn!("don't know how to open dropped file {}", path.display()),
+        }
+    }
+
     pub fn update(&mut self, render_state: &RenderState, world: &mut World) {
         if let Some(vmf_future) = &mut self.vmf_future {
             // Poll our vmf_future until it is finished loading. This is probably a stupid way to
@@ -101,8 +216,16 @@ impl Gui {
             if let std::task::Poll::Ready(vmf) = poll_result {
                 // vmf_future is ready, so update map
                 // check if we managed to actually load a vmf file first
-                if let Some(vmf) = vmf {
-                    world.map = map::Map::from_string(&vmf, &render_state.device).unwrap();
+                if let Some((name, geometry)) = vmf {
+                    match geometry {
+                        Ok(geometry) => {
+                            if !self.vmf_append {
+                                world.map = map::Map::empty(&render_state.device);
+                            }
+                            world.map.add(name, geometry, &render_state.device);
+                        },
+                        Err(e) => log::warn!("failed to load VMF: {e}"),
+                    }
                 }
                 self.vmf_future = None;
             }
@@ -122,12 +245,24 @@ impl Gui {
             }
         }
 
+        if let Some(append_state_future) = &mut self.append_state_future {
+            // Same polling setup as above, but the splines are added to the current ones
+            let waker = noop_waker();
+            let mut ctx = std::task::Context::from_waker(&waker);
+            let poll_result = append_state_future.as_mut().poll(&mut ctx);
+            if let std::task::Poll::Ready(save) = poll_result {
+                if let Some(save) = save {
+                    world.append_state(&save, render_state);
+                }
+                self.append_state_future = None;
+            }
+        }
+
         if let Some(save_state_future) = &mut self.save_state_future {
             // Same polling setup as above, but we just set to none if finished
             let waker = noop_waker();
             let mut ctx = std::task::Context::from_waker(&waker);
-            let poll_result = save_state_future.as_mut().poll(&mut ctx);
-            if let std::task::Poll::Ready(_) = poll_result {
+            if save_state_future.as_mut().poll(&mut ctx).is_ready() {
                 self.save_state_future = None;
             }
         }
@@ -136,17 +271,57 @@ impl Gui {
             // Same polling setup as above, but we just set to none if finished
             let waker = noop_waker();
             let mut ctx = std::task::Context::from_waker(&waker);
-            let poll_result = export_spline_future.as_mut().poll(&mut ctx);
-            if let std::task::Poll::Ready(_) = poll_result {
+            if export_spline_future.as_mut().poll(&mut ctx).is_ready() {
                 self.export_spline_future = None;
             }
         }
+
+        // Once the overview is back from the GPU, encode it and ask where to save it
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(image) = self.overview_readback.as_ref().and_then(|readback| readback.try_finish(&render_state.device)) {
+            self.overview_readback = None;
+            let png = image.and_then(|image| {
+                let mut png = Vec::new();
+                image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+                Ok(png)
+            });
+            match png {
+                Ok(png_bytes) => {
+                    self.export_overview_future = Some(Box::pin(async {
+                        let png_bytes = png_bytes; // Need this to move png_bytes inside the closure
+                        let save_file = AsyncFileDialog::new()
+                            .add_filter("PNG image (.png)", &["png"])
+                            .set_file_name("overview.png")
+                            .save_file()
+                            .await;
+                        if let Some(save_handle) = save_file {
+                            let _ = save_handle.write(&png_bytes).await;
+                        };
+                    }));
+                },
+                Err(e) => self.overview_error = Some(e.to_string()),
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(export_overview_future) = &mut self.export_overview_future {
+            // Same polling setup as above, but we just set to none if finished
+            let waker = noop_waker();
+            let mut ctx = std::task::Context::from_waker(&waker);
+            if export_overview_future.as_mut().poll(&mut ctx).is_ready() {
+                self.export_overview_future = None;
+            }
+        }
     }
 
     pub fn render(&mut self, render_state: &RenderState, world: &mut World, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, dt: f64, total_time: f64) {
         // Update our FPS average. In order to get a rolling average without storing the frame
         // times for the past X frames, we compute it using a geometric series sum.
         self.avg_frame_time = 15.0 * self.avg_frame_time / 16.0 + dt / 16.0;
+        if self.recent_frame_times.len() == FRAME_TIME_WINDOW {
+            self.recent_frame_times.pop_front();
+        }
+        self.recent_frame_times.push_back(dt);
 
         // Accumulate input handled by egui
         let mut raw_input = self.state.take_egui_input(&render_state.window);
@@ -199,42 +374,273 @@ impl Gui {
 
                     match self.menu_selection {
                         GuiMenu::Controls => {
-                            ui.label("Controls:");
-                            ui.label("WASD: Move around");
+                            ui.label("Controls:").on_hover_text("Click a key to rebind it, then press the new key. Escape cancels");
+                            for action in keybinds::Action::ALL {
+                                ui.horizontal(|ui| {
+                                    let key_text;
+                                    if world.keybinds.capturing == Some(action) {
+                                        key_text = "Press a key...".to_string();
+                                    }
+                                    else {
+                                        key_text = keybinds::display_name(world.keybinds.key(action));
+                                    }
+                                    if ui.button(key_text).clicked() {
+                                        world.keybinds.capturing = Some(action);
+                                    }
+                                    ui.label(action.description());
+                                });
+                            }
                             ui.label("Shift: Speed up movement");
-                            ui.label("Z: Toggle mouse capture, allowing camera control");
                             ui.label("Mouse: Aim the camera");
-                            ui.label("Space: Insert a new point into the current spline");
-                            ui.label("Left & Right Arrow Keys: Change the selected point on the current spline");
-                        },
-                        GuiMenu::Map => {
-                            if ui.button("Load VMF").clicked() && self.vmf_future.is_none() {
-                                // Spawn a file picker. We'll get the result of the file picker
-                                // later in update()
-                                self.vmf_future = Some(Box::pin(async {
-                                    let map_vmf_file = AsyncFileDialog::new()
-                                        .add_filter("VMF", &["vmf"])
-                                        .pick_file()
-                                        .await;
-                                    if let Some(map_vmf_file) = map_vmf_file {
-                                        String::from_utf8(map_vmf_file.read().await).ok()
+                            ui.label("Left Click: Select the highlighted point under the cursor");
+                            if ui.button("Reset Keybinds").clicked() {
+                                world.keybinds = keybinds::Keybinds::default();
+                                world.keybinds.save();
+                            }
+                            ui.separator();
+
+                            ui.label("Camera:");
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Roll: {:.1}°", cgmath::Deg::from(world.camera.roll).0));
+                                if ui.button("Level").clicked() {
+                                    world.camera.roll = cgmath::Rad(0.0);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                let mut orthographic = matches!(world.camera.projection, camera::Projection::Orthographic { .. });
+                                ui.label("Orthographic:");
+                                if ui.checkbox(&mut orthographic, "").changed() {
+                                    if orthographic {
+                                        world.camera.projection = camera::Projection::Orthographic { height: camera::DEFAULT_ORTHO_HEIGHT };
                                     }
                                     else {
-                                        None
+                                        world.camera.projection = camera::Projection::Perspective;
+                                    }
+                                }
+                                if let camera::Projection::Orthographic { height } = &mut world.camera.projection {
+                                    ui.label("View Height:").on_hover_text("Scroll over the viewport to zoom");
+                                    ui.add(DragValue::new(height).clamp_range(1.0..=f32::MAX));
+                                }
+                                else {
+                                    ui.label("FOV:");
+                                    ui.add(egui::Slider::new(&mut world.camera.fovy, camera::MIN_FOVY..=camera::MAX_FOVY).suffix("°"));
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Ortho Views:");
+                                for preset in camera::ViewPreset::ALL {
+                                    if ui.button(format!("{preset}")).clicked() {
+                                        world.camera.apply_view_preset(preset);
+                                        world.camera_controller.stop();
+                                    }
+                                }
+                            });
+                            ui.separator();
+
+                            ui.label("Preferences:");
+                            ui.horizontal(|ui| {
+                                ui.label("Smooth Camera Movement:");
+                                ui.checkbox(&mut world.camera_controller.settings.smoothing, "");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Camera Speed:").on_hover_text("Scroll over the viewport to change the speed on the fly");
+                                ui.add(egui::Slider::new(&mut world.camera_controller.settings.speed, camera::MIN_SPEED..=camera::MAX_SPEED).logarithmic(true));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Shift Speed Multiplier:");
+                                ui.add(egui::Slider::new(&mut world.camera_controller.settings.speed_multiplier, 1.0..=16.0));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Mouse Sensitivity:").on_hover_text("In milliradians per pixel");
+                                let mut sensitivity = world.camera_controller.settings.sensitivity * 1000.0;
+                                if ui.add(egui::Slider::new(&mut sensitivity, 0.1..=20.0).logarithmic(true)).changed() {
+                                    world.camera_controller.settings.sensitivity = sensitivity / 1000.0;
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Spline LOD:");
+                                ui.checkbox(&mut world.lod_enabled, "");
+                                ui.label("Distance:");
+                                ui.add_enabled(world.lod_enabled, DragValue::new(&mut world.lod_distance).clamp_range(0.0..=f32::MAX));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Focus Distance:").on_hover_text("How far back from the selected point the camera moves when focusing on it");
+                                ui.add(DragValue::new(&mut world.focus_distance).clamp_range(0.0..=f32::MAX));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Show Grid:").on_hover_text("Draw a ground grid on the Z=0 plane, along with the X (red), Y (green), and Z (blue) axes");
+                                ui.checkbox(&mut world.show_grid, "");
+                                ui.label("Spacing:");
+                                ui.add_enabled(world.show_grid, DragValue::new(&mut world.grid_spacing).clamp_range(1.0..=f32::MAX));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Rebase Render Origin:").on_hover_text("Render relative to the center of the map, which reduces jitter far away from the world origin. Exports still use world coordinates");
+                                ui.checkbox(&mut world.rebase_origin, "");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Map View:");
+                                egui::ComboBox::from_id_source("Map View Selector")
+                                    .selected_text(format!("{}", world.map_view_mode))
+                                    .show_ui(ui, |ui| {
+                                        for mode in [map::MapViewMode::NormalColor, map::MapViewMode::FlatLit, map::MapViewMode::Textured] {
+                                            ui.selectable_value(&mut world.map_view_mode, mode, format!("{mode}"));
+                                        }
+                                    });
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Map Faces:").on_hover_text("Turn off culling or switch to wireframe to see splines through walls");
+                                let supports_wireframe = world.map_supports_wireframe();
+                                egui::ComboBox::from_id_source("Map Render Mode Selector")
+                                    .selected_text(format!("{}", world.map_render_mode))
+                                    .show_ui(ui, |ui| {
+                                        for mode in map::MapRenderMode::ALL {
+                                            // WebGL can't draw wireframes
+                                            if mode != map::MapRenderMode::Wireframe || supports_wireframe {
+                                                ui.selectable_value(&mut world.map_render_mode, mode, format!("{mode}"));
+                                            }
+                                        }
+                                    });
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Present Mode:").on_hover_text("Vsync modes limit the frame rate to the display's refresh rate");
+                                egui::ComboBox::from_id_source("Present Mode Selector")
+                                    .selected_text(format!("{:?}", world.present_mode))
+                                    .show_ui(ui, |ui| {
+                                        for mode in render_state.present_modes.iter() {
+                                            ui.selectable_value(&mut world.present_mode, *mode, format!("{mode:?}"));
+                                        }
+                                    });
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Anti-aliasing:");
+                                ui.add_enabled_ui(render_state.msaa_supported, |ui| {
+                                    ui.selectable_value(&mut world.msaa_enabled, false, "1x");
+                                    ui.selectable_value(&mut world.msaa_enabled, true, format!("{}x MSAA", crate::world::MSAA_SAMPLES));
+                                }).response.on_disabled_hover_text("MSAA isn't supported by this graphics backend");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Frame Rate Cap:");
+                                ui.checkbox(&mut world.frame_cap_enabled, "");
+                                ui.add_enabled(world.frame_cap_enabled, DragValue::new(&mut world.frame_cap).clamp_range(1.0..=1000.0).suffix(" FPS"));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Background Color:");
+                                ui.color_edit_button_rgb(&mut world.clear_color);
+                                if ui.button("Reset").clicked() {
+                                    world.clear_color = crate::world::DEFAULT_CLEAR_COLOR;
+                                }
+                                ui.checkbox(&mut world.skybox_background, "Use skybox").on_hover_text("Match the map's skybox faces instead, if it has any. Saved with the splines");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("FPS Counter:");
+                                ui.checkbox(&mut self.show_fps, "");
+                                ui.label("Frame Times:").on_hover_text(format!("Also show the frame time in ms, with the min and max over the last {FRAME_TIME_WINDOW} frames"));
+                                ui.add_enabled(self.show_fps, egui::Checkbox::without_text(&mut self.show_frame_times));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Autosave:");
+                                ui.checkbox(&mut world.autosave_enabled, "");
+                                ui.label("Every:");
+                                ui.add_enabled(world.autosave_enabled, DragValue::new(&mut world.autosave_interval).clamp_range(5.0..=3600.0).suffix("s"));
+                            });
+                        },
+                        GuiMenu::Map => {
+                            ui.horizontal(|ui| {
+                                let load_clicked = ui.button("Load VMF").clicked();
+                                let add_clicked = ui.button("Add VMF").on_hover_text("Add the VMF to the current map instead of replacing it, for maps split across several files").clicked();
+                                if (load_clicked || add_clicked) && self.vmf_future.is_none() {
+                                    // Spawn a file picker. We'll get the result of the file picker
+                                    // later in update()
+                                    self.vmf_append = add_clicked;
+                                    self.vmf_future = Some(Box::pin(async {
+                                        let map_vmf_file = AsyncFileDialog::new()
+                                            .add_filter("VMF", &["vmf"])
+                                            .pick_file()
+                                            .await;
+                                        if let Some(map_vmf_file) = map_vmf_file {
+                                            // Natively we can read straight from the file, while the
+                                            // web only hands us its contents
+                                            #[cfg(not(target_arch = "wasm32"))]
+                                            let geometry = map::Map::geometry_from_file(map_vmf_file.path());
+                                            #[cfg(target_arch = "wasm32")]
+                                            let geometry = map::Map::geometry_from_reader(&map_vmf_file.read().await[..]);
+                                            Some((map_vmf_file.file_name(), geometry))
+                                        }
+                                        else {
+                                            None
+                                        }
+                                    }));
+                                }
+                            });
+                            let mut removed_source = None;
+                            for (i, name) in world.map.source_names().enumerate() {
+                                ui.horizontal(|ui| {
+                                    if ui.button("x").on_hover_text("Remove this VMF from the map").clicked() {
+                                        removed_source = Some(i);
                                     }
-                                }));
+                                    ui.label(name);
+                                });
+                            }
+                            if let Some(i) = removed_source {
+                                world.map.remove(i, &render_state.device);
                             }
+                            ui.horizontal(|ui| {
+                                ui.label("Show Entities:").on_hover_text("Mark each of the map's entities, colored by classname");
+                                ui.checkbox(&mut world.show_entities, "");
+                                ui.checkbox(&mut world.show_entity_labels, "Labels");
+                            });
+                            egui::CollapsingHeader::new("Entity Classes").show(ui, |ui| {
+                                let mut class_counts: BTreeMap<&str, usize> = BTreeMap::new();
+                                for entity in world.map.entities() {
+                                    *class_counts.entry(&entity.classname).or_default() += 1;
+                                }
+                                ui.horizontal(|ui| {
+                                    if ui.button("Show all").clicked() {
+                                        world.hidden_entity_classes.clear();
+                                    }
+                                    if ui.button("Hide all").clicked() {
+                                        world.hidden_entity_classes = class_counts.keys().map(|classname| classname.to_string()).collect();
+                                    }
+                                });
+                                for (classname, count) in class_counts.iter() {
+                                    let mut shown = !world.hidden_entity_classes.contains(*classname);
+                                    if ui.checkbox(&mut shown, format!("{classname} ({count})")).changed() {
+                                        if shown {
+                                            world.hidden_entity_classes.remove(*classname);
+                                        }
+                                        else {
+                                            world.hidden_entity_classes.insert(classname.to_string());
+                                        }
+                                    }
+                                }
+                            });
                             ui.separator();
                             ui.horizontal(|ui| {
                                 if ui.button("Load splines").clicked() && self.load_state_future.is_none() {
                                     // Spawn a file picker for the spline save file
                                     self.load_state_future = Some(Box::pin(async {
                                         let save_file = AsyncFileDialog::new()
-                                            .add_filter("Spline state (.json)", &["json"])
+                                            .add_filter("Spline state (.json, .splb)", &["json", "splb"])
+                                            .add_filter("Exported model (.smd, .qc)", &["smd", "qc"])
+                                            .pick_file()
+                                            .await;
+                                        if let Some(save_file) = save_file {
+                                            Some(save_file.read().await)
+                                        }
+                                        else {
+                                            None
+                                        }
+                                    }));
+                                }
+                                if ui.button("Append from file").clicked() && self.append_state_future.is_none() {
+                                    self.append_state_future = Some(Box::pin(async {
+                                        let save_file = AsyncFileDialog::new()
+                                            .add_filter("Spline state (.json, .splb)", &["json", "splb"])
+                                            .add_filter("Exported model (.smd, .qc)", &["smd", "qc"])
                                             .pick_file()
                                             .await;
                                         if let Some(save_file) = save_file {
-                                            String::from_utf8(save_file.read().await).ok()
+                                            Some(save_file.read().await)
                                         }
                                         else {
                                             None
@@ -242,21 +648,72 @@ impl Gui {
                                     }));
                                 }
                                 if ui.button("Save splines").clicked() {
-                                    // Serialize our state, spawn a file picker, and write to the
-                                    // selected file
-                                    let serialized_state = world.save_state();
+                                    // Grab our state, spawn a file picker, and write to the selected
+                                    // file. The extension picked decides the format we save in.
+                                    let save_data = world.save_data();
                                     self.save_state_future = Some(Box::pin(async {
+                                        let save_data = save_data; // Need this to move save_data inside the closure
                                         let save_file = AsyncFileDialog::new()
                                             .add_filter("Spline state (.json)", &["json"])
+                                            .add_filter("Binary spline state (.splb)", &["splb"])
                                             .set_file_name("splines.json")
                                             .save_file()
                                             .await;
                                         if let Some(save_handle) = save_file {
-                                            let _ = save_handle.write(&serialized_state.into_bytes()).await;
+                                            let binary = save_handle.file_name().to_lowercase().ends_with(".splb");
+                                            let serialized_state = crate::world::encode_state(&save_data, binary);
+                                            if save_handle.write(&serialized_state).await.is_ok() {
+                                                // The manual save is now the newest, so the autosave isn't needed
+                                                crate::autosave::remove();
+                                            }
                                         };
                                     }));
                                 }
                             });
+                            if let Some(age) = crate::autosave::age() {
+                                ui.horizontal(|ui| {
+                                    if ui.button("Restore previous session").clicked() {
+                                        if let Some(save) = crate::autosave::read() {
+                                            world.restore_state(save.as_bytes(), render_state);
+                                        }
+                                    }
+                                    ui.label(format!("Autosaved {} ago", format_age(age)));
+                                });
+                            }
+                            // Overviews are only available natively, see World::render_overview
+                            #[cfg(not(target_arch = "wasm32"))]
+                            {
+                                ui.horizontal(|ui| {
+                                    let exporting = self.overview_readback.is_some() || self.export_overview_future.is_some();
+                                    if ui.button("Export overview image").clicked() && !exporting {
+                                        // Render a top-down view of the whole scene, which gets saved as a PNG once
+                                        // it's back from the GPU
+                                        match world.render_overview(render_state, self.overview_splines_on_top) {
+                                            Ok(readback) => {
+                                                self.overview_error = None;
+                                                self.overview_readback = Some(readback);
+                                            },
+                                            Err(e) => self.overview_error = Some(e.to_string()),
+                                        }
+                                    }
+                                    ui.checkbox(&mut self.overview_splines_on_top, "Splines on top")
+                                        .on_hover_text("Flatten the image to 2D by drawing the splines over the map, even where the map covers them");
+                                });
+                                if let Some(error) = &self.overview_error {
+                                    ui.colored_label(egui::Color32::YELLOW, format!("Couldn't export overview: {error}"));
+                                }
+                            }
+                            ui.separator();
+
+                            ui.horizontal(|ui| {
+                                ui.label("Measure:").on_hover_text("Left click two spots in the world to measure between them. Escape clears the measurement");
+                                ui.checkbox(&mut world.measuring, "");
+                            });
+                            if let (Some(start), Some(end)) = (world.measure_start, world.measure_end) {
+                                let delta = end - start;
+                                ui.label(format!("Distance: {:.2}", delta.magnitude()));
+                                ui.label(format!("X: {:.2} Y: {:.2} Z: {:.2}", delta.x, delta.y, delta.z));
+                            }
                         },
                         GuiMenu::Spline => {
                             ui.horizontal(|ui| {
@@ -282,27 +739,131 @@ impl Gui {
                                     .show_ui(ui, |ui| {
                                         ui.set_width(ui.available_width());
                                         for (i, spline) in world.splines.iter().enumerate() {
-                                            ui.selectable_value(&mut world.selected_spline, i as u32, format!("Spline {} - {}", i + 1, spline.borrow().data.name));
+                                            ui.horizontal(|ui| {
+                                                ui.checkbox(&mut spline.borrow_mut().visible, "").on_hover_text("Show this spline in the viewport");
+                                                ui.selectable_value(&mut world.selected_spline, i as u32, format!("Spline {} - {}", i + 1, spline.borrow().data.name));
+                                            });
                                         }
                                     });
+                                if ui.add_enabled(world.splines.len() > 0, egui::Button::new("Solo")).on_hover_text("Hide every spline but the selected one, or show them all again if they already are").clicked() {
+                                    world.toggle_solo();
+                                }
                             });
-                            if ui.button("Export").clicked() {
-                                // Write out a zip file containing the uncompiled spline model
-                                let zip_bytes = export::construct_zip(&world.splines).unwrap();
-                                self.export_spline_future = Some(Box::pin(async {
-                                    let zip_bytes = zip_bytes; // Need this to move zip_bytes inside the closure
-                                    let save_file = AsyncFileDialog::new()
-                                        .add_filter("Export archive (.zip)", &["zip"])
-                                        .set_file_name("model_export.zip")
-                                        .save_file()
-                                        .await;
-                                    if let Some(save_handle) = save_file {
-                                        let _ = save_handle.write(&zip_bytes).await;
-                                    };
-                                }));
+                            let export_splines;
+                            if self.export_selected_only && !world.splines.is_empty() {
+                                let selected_spline = world.selected_spline as usize;
+                                export_splines = &world.splines[selected_spline..=selected_spline];
+                            }
+                            else {
+                                export_splines = &world.splines[..];
+                            }
+                            // Splines with the same model path would overwrite each other in game,
+                            // so don't allow exporting until they are renamed
+                            let duplicate_names = export::duplicate_model_names(export_splines);
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(duplicate_names.is_empty(), egui::Button::new("Export")).clicked() {
+                                    // Write out a zip file containing the uncompiled spline model
+                                    let export_data: Vec<spline::SplineData> = export_splines.iter().map(|s| s.borrow().data.clone()).collect();
+                                    let zip_bytes = export::construct_zip(&export_data, self.export_separately, self.export_origin).unwrap();
+                                    self.export_spline_future = Some(Box::pin(async {
+                                        let zip_bytes = zip_bytes; // Need this to move zip_bytes inside the closure
+                                        let save_file = AsyncFileDialog::new()
+                                            .add_filter("Export archive (.zip)", &["zip"])
+                                            .set_file_name("model_export.zip")
+                                            .save_file()
+                                            .await;
+                                        if let Some(save_handle) = save_file {
+                                            let _ = save_handle.write(&zip_bytes).await;
+                                        };
+                                    }));
+                                }
+                                if ui.button("Export OBJ").on_hover_text("Export the meshes as an OBJ, for previewing in other 3D software").clicked() {
+                                    let export_data: Vec<spline::SplineData> = export_splines.iter().map(|s| s.borrow().data.clone()).collect();
+                                    let zip_bytes = export::construct_obj_zip(&export_data, self.export_obj_up_axis).unwrap();
+                                    self.export_spline_future = Some(Box::pin(async {
+                                        let zip_bytes = zip_bytes; // Need this to move zip_bytes inside the closure
+                                        let save_file = AsyncFileDialog::new()
+                                            .add_filter("Export archive (.zip)", &["zip"])
+                                            .set_file_name("obj_export.zip")
+                                            .save_file()
+                                            .await;
+                                        if let Some(save_handle) = save_file {
+                                            let _ = save_handle.write(&zip_bytes).await;
+                                        };
+                                    }));
+                                }
+                                if ui.button("Export glTF").on_hover_text("Export the meshes as a single GLB file, for previewing in a browser").clicked() {
+                                    let export_data: Vec<spline::SplineData> = export_splines.iter().map(|s| s.borrow().data.clone()).collect();
+                                    match export::gltf_from_splines(&export_data) {
+                                        Ok(glb_bytes) => {
+                                            self.export_spline_future = Some(Box::pin(async {
+                                                let glb_bytes = glb_bytes; // Need this to move glb_bytes inside the closure
+                                                let save_file = AsyncFileDialog::new()
+                                                    .add_filter("glTF binary (.glb)", &["glb"])
+                                                    .set_file_name("splines.glb")
+                                                    .save_file()
+                                                    .await;
+                                                if let Some(save_handle) = save_file {
+                                                    let _ = save_handle.write(&glb_bytes).await;
+                                                };
+                                            }));
+                                        },
+                                        Err(e) => log::warn!("failed to export glTF: {e}"),
+                                    }
+                                }
+                                ui.checkbox(&mut self.export_selected_only, "Selected spline only");
+                            });
+                            ui.checkbox(&mut self.export_separately, "Export each separately")
+                                .on_hover_text("Put each model in its own folder with its own materials, so they can be compiled independently");
+                            ui.horizontal(|ui| {
+                                ui.label("Model Origin:").on_hover_text("Where the origin of each exported model is placed in the world");
+                                let custom_origin = export::ExportOrigin::Custom(self.export_custom_origin.into());
+                                egui::ComboBox::from_id_source("Export Origin Selector")
+                                    .selected_text(format!("{}", self.export_origin))
+                                    .show_ui(ui, |ui| {
+                                        for origin in [export::ExportOrigin::FirstPoint, export::ExportOrigin::WorldOrigin, export::ExportOrigin::BoundingCenter, custom_origin] {
+                                            ui.selectable_value(&mut self.export_origin, origin, format!("{origin}"));
+                                        }
+                                    });
+                            });
+                            if let export::ExportOrigin::Custom(_) = self.export_origin {
+                                ui.horizontal(|ui| {
+                                    ui.label("X:");
+                                    ui.add(DragValue::new(&mut self.export_custom_origin[0]));
+                                    ui.label("Y:");
+                                    ui.add(DragValue::new(&mut self.export_custom_origin[1]));
+                                    ui.label("Z:");
+                                    ui.add(DragValue::new(&mut self.export_custom_origin[2]));
+                                });
+                                self.export_origin = export::ExportOrigin::Custom(self.export_custom_origin.into());
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("OBJ Up Axis:");
+                                egui::ComboBox::from_id_source("OBJ Up Axis Selector")
+                                    .selected_text(format!("{}", self.export_obj_up_axis))
+                                    .show_ui(ui, |ui| {
+                                        for up_axis in [export::ObjUpAxis::Z, export::ObjUpAxis::Y] {
+                                            ui.selectable_value(&mut self.export_obj_up_axis, up_axis, format!("{up_axis}"));
+                                        }
+                                    });
+                            });
+                            for name in duplicate_names.iter() {
+                                ui.colored_label(egui::Color32::YELLOW, format!("Multiple splines use the model path {name}"));
+                            }
+                            let export_counts = export::export_counts(export_splines);
+                            let total_vertices: usize = export_counts.iter().map(|c| c.1).sum();
+                            let total_triangles: usize = export_counts.iter().map(|c| c.2).sum();
+                            ui.label(format!("Export: {total_vertices} vertices, {total_triangles} triangles"))
+                                .on_hover_text("Counted at each spline's export subdivisions, not the render subdivisions");
+                            for (name, vertices, _) in export_counts.iter() {
+                                if *vertices > export::MAX_MODEL_VERTICES {
+                                    ui.colored_label(egui::Color32::YELLOW, format!("{name} has {vertices} vertices, over the limit of {}", export::MAX_MODEL_VERTICES));
+                                }
                             }
                             ui.separator();
 
+                            // Playback needs the spline, so it gets toggled once we're done editing it
+                            let mut toggle_playback = false;
                             if world.splines.len() > 0 {
                                 // Need to declare up here because borrow checker :)
                                 let num_splines = world.splines.len();
@@ -317,21 +878,120 @@ impl Gui {
                                     if ui.add(DragValue::new(&mut spline.data.radius)).changed() {
                                         rebuild_spline = true;
                                     }
+                                    if ui.button("Apply to all").on_hover_text("Give every spline this radius").clicked() {
+                                        let radius = spline.data.radius;
+                                        apply_to_other_splines(world, |other| other.data.radius = radius);
+                                    }
                                 });
                                 ui.horizontal(|ui| {
                                     ui.label("Sides:");
-                                    if ui.add(DragValue::new(&mut spline.data.sides).clamp_range(1..=std::u32::MAX)).changed() {
+                                    if ui.add(DragValue::new(&mut spline.data.sides).clamp_range(spline::MIN_SIDES..=u32::MAX)).changed() {
                                         rebuild_spline = true;
                                     }
+                                    if ui.button("Apply to all").on_hover_text("Give every spline this many sides").clicked() {
+                                        let sides = spline.data.sides;
+                                        apply_to_other_splines(world, |other| other.data.sides = sides);
+                                    }
                                 });
                                 ui.horizontal(|ui| {
-                                    ui.label("Subdivisions:");
-                                    if ui.add(DragValue::new(&mut spline.data.subdivisions).clamp_range(1..=std::u32::MAX)).changed() {
+                                    ui.label("Subdivisions:").on_hover_text("Subdivisions per segment of the exported model");
+                                    // Export builds its own mesh, but segments overriding their subdivisions render relative to this
+                                    if ui.add(DragValue::new(&mut spline.data.subdivisions).clamp_range(1..=u32::MAX)).changed() {
                                         rebuild_spline = true;
                                     }
+                                    if ui.button("Apply to all").on_hover_text("Give every spline these subdivisions").clicked() {
+                                        let subdivisions = spline.data.subdivisions;
+                                        apply_to_other_splines(world, |other| other.data.subdivisions = subdivisions);
+                                    }
                                 });
                                 ui.horizontal(|ui| {
-                                    ui.label("Bundle Spline:");
+                                    ui.label("Render Subdivisions:").on_hover_text("Subdivisions per segment shown in the viewport. Keep this low to keep editing responsive");
+                                    let mut adaptive = matches!(spline.data.render_subdivisions, spline::SubdivMode::Adaptive(_));
+                                    if ui.checkbox(&mut adaptive, "Adaptive").on_hover_text("Subdivide only where the spline bends, until it deviates by at most the tolerance").changed() {
+                                        if adaptive {
+                                            spline.data.render_subdivisions = spline::SubdivMode::Adaptive(1.0);
+                                        }
+                                        else {
+                                            spline.data.render_subdivisions = spline::SubdivMode::Fixed(8);
+                                        }
+                                        rebuild_spline = true;
+                                    }
+                                    let response = match &mut spline.data.render_subdivisions {
+                                        spline::SubdivMode::Fixed(subdivisions) => ui.add(DragValue::new(subdivisions).clamp_range(1..=u32::MAX)),
+                                        spline::SubdivMode::Adaptive(tolerance) => ui.add(DragValue::new(tolerance).clamp_range(0.01..=f32::MAX).speed(0.1).prefix("tolerance: ")),
+                                    };
+                                    if response.changed() {
+                                        rebuild_spline = true;
+                                    }
+                                    if ui.button("Apply to all").on_hover_text("Give every spline these render subdivisions").clicked() {
+                                        let render_subdivisions = spline.data.render_subdivisions;
+                                        apply_to_other_splines(world, |other| other.data.render_subdivisions = render_subdivisions);
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Interpolation:").on_hover_text("Catmull-Rom derives every tangent from the neighboring points, ignoring their pitch, yaw, and tangent magnitude");
+                                    let previous_interpolation = spline.data.interpolation;
+                                    egui::ComboBox::from_id_source("Interpolation Selector")
+                                        .selected_text(format!("{}", spline.data.interpolation))
+                                        .show_ui(ui, |ui| {
+                                            for mode in [spline::InterpolationMode::Hermite, spline::InterpolationMode::CatmullRom] {
+                                                ui.selectable_value(&mut spline.data.interpolation, mode, format!("{mode}"));
+                                            }
+                                        });
+                                    if spline.data.interpolation != previous_interpolation {
+                                        rebuild_spline = true;
+                                    }
+                                    let catmull_rom = spline.data.interpolation == spline::InterpolationMode::CatmullRom;
+                                    if ui.add_enabled(catmull_rom, DragValue::new(&mut spline.data.tension).speed(0.01).prefix("tension: ")).changed() {
+                                        rebuild_spline = true;
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Closed Loop:").on_hover_text("Connect the last point back to the first");
+                                    if ui.checkbox(&mut spline.data.closed, "").changed() {
+                                        rebuild_spline = true;
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("End Caps:").on_hover_text("How the ends of the tube are closed off. Loops have no ends");
+                                    let previous_end_caps = spline.data.end_caps;
+                                    ui.add_enabled_ui(!spline.data.closed, |ui| {
+                                        egui::ComboBox::from_id_source("End Cap Selector")
+                                            .selected_text(format!("{}", spline.data.end_caps))
+                                            .show_ui(ui, |ui| {
+                                                for style in [spline::CapStyle::None, spline::CapStyle::Flat, spline::CapStyle::Rounded] {
+                                                    ui.selectable_value(&mut spline.data.end_caps, style, format!("{style}"));
+                                                }
+                                            });
+                                    });
+                                    if spline.data.end_caps != previous_end_caps {
+                                        rebuild_spline = true;
+                                    }
+                                });
+                                let (mesh_vertices, mesh_triangles) = spline.mesh_counts();
+                                ui.label(format!("Length: {:.1} units - Mesh: {mesh_vertices} vertices, {mesh_triangles} triangles", spline.length()))
+                                    .on_hover_text("Measured along the render subdivisions");
+                                let playing = world.is_playing();
+                                ui.horizontal(|ui| {
+                                    if playing {
+                                        toggle_playback = ui.button("Stop").clicked();
+                                    }
+                                    else {
+                                        toggle_playback = ui.button("Play").on_hover_text("Move the camera along the spline. Escape or any movement key takes back control").clicked();
+                                    }
+                                    ui.label("Duration:");
+                                    ui.add(DragValue::new(&mut world.playback_duration).speed(0.1).clamp_range(0.1..=f32::MAX).suffix(" s"));
+                                });
+                                egui::CollapsingHeader::new("Segment Lengths").show(ui, |ui| {
+                                    let num_points = spline.data.points.len();
+                                    // The lengths are from the last rebuild, so points may have been removed since
+                                    for (i, (arc_length, start)) in spline.segment_lengths().iter().zip(spline.data.points.iter()).enumerate() {
+                                        let end = &spline.data.points[(i + 1) % num_points];
+                                        ui.label(format!("{} to {}: {arc_length:.1} along the curve, {:.1} straight", i + 1, (i + 1) % num_points + 1, start.position.distance(end.position)));
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Bundle Spline:").on_hover_text("Bundle splines guide the points bundled to them, and get exported as part of the model of the next non-bundle spline");
                                     if ui.checkbox(&mut spline.data.bundle, "").changed() {
                                         // Need to rebuild to build the bundle positions
                                         rebuild_spline = true;
@@ -341,18 +1001,224 @@ impl Gui {
                                     ui.label("Model Path:");
                                     ui.text_edit_singleline(&mut spline.data.name);
                                 });
+                                for problem in export::model_name_problems(&spline.data.name) {
+                                    ui.colored_label(egui::Color32::YELLOW, problem);
+                                }
+                                ui.horizontal(|ui| {
+                                    let sanitized_name = export::sanitize_model_name(&spline.data.name);
+                                    ui.label(format!("$modelname: {}", sanitized_name));
+                                    if sanitized_name != spline.data.name && ui.button("Sanitize").clicked() {
+                                        spline.data.name = sanitized_name;
+                                    }
+                                });
+                                if ui.button("Aim tangents at next point").clicked() {
+                                    spline.aim_tangents_at_next();
+                                }
+                                ui.horizontal(|ui| {
+                                    if ui.button("Scale tangents to segments").clicked() {
+                                        spline.scale_tangents_to_segments(self.tangent_fraction);
+                                    }
+                                    ui.label("Fraction:").on_hover_text("Fraction of the average adjacent segment length to use as the tangent magnitude. 1 gives a natural looking curve");
+                                    ui.add(DragValue::new(&mut self.tangent_fraction).speed(0.01).clamp_range(0.0..=f32::MAX));
+                                });
+                                ui.horizontal(|ui| {
+                                    if ui.button("Smooth tangents").on_hover_text("Turn each interior tangent towards the average direction of its adjacent segments, removing creases").clicked() {
+                                        spline.smooth_tangents(self.smooth_strength, self.smooth_magnitudes);
+                                    }
+                                    ui.label("Strength:").on_hover_text("0 leaves the tangents unchanged, 1 fully smooths them");
+                                    ui.add(egui::Slider::new(&mut self.smooth_strength, 0.0..=1.0));
+                                    ui.checkbox(&mut self.smooth_magnitudes, "Magnitudes").on_hover_text("Also scale the magnitudes towards the average adjacent segment length");
+                                });
+                                ui.horizontal(|ui| {
+                                    if ui.button("Randomize Colors").clicked() {
+                                        let seed = spline.data.color_seed;
+                                        spline.randomize_colors(seed);
+                                    }
+                                    ui.label("Seed:");
+                                    ui.add(DragValue::new(&mut spline.data.color_seed));
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Show Point Numbers:");
+                                    ui.checkbox(&mut self.show_point_labels, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Show Control Polygon:").on_hover_text("Draw straight lines between the control points, showing the path the curve is built from");
+                                    ui.checkbox(&mut world.show_control_polygon, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Show Point Gizmos:").on_hover_text("Draw a cube at each control point of the selected spline, with the selected point enlarged");
+                                    ui.checkbox(&mut world.show_point_gizmos, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("X-Ray Selected Spline:").on_hover_text("Faintly draw the selected spline through walls, so you can tell where it passes behind the map");
+                                    ui.checkbox(&mut world.xray_selected, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Snap New Points to Grid:").on_hover_text("Round newly placed points to the snap value instead of whole units");
+                                    ui.checkbox(&mut world.snap_placement, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Place Points on Surface:").on_hover_text("Place new points where the camera's view hits the map instead of at the camera. Hold alt to switch temporarily");
+                                    ui.checkbox(&mut world.place_on_surface, "");
+                                });
+                                ui.separator();
+
+                                egui::CollapsingHeader::new(format!("Point List ({})", spline.data.points.len())).show(ui, |ui| {
+                                    let mut clicked_point = None;
+                                    let mut removed_point = None;
+                                    let mut row_rects = Vec::with_capacity(spline.data.points.len());
+                                    // Forget a drag that was let go of while the list was hidden
+                                    if ui.input(|input| !input.pointer.any_down() && !input.pointer.any_released()) {
+                                        self.dragged_point = None;
+                                    }
+                                    egui::ScrollArea::vertical().id_source("Point List").max_height(200.0).show(ui, |ui| {
+                                        for (i, point) in spline.data.points.iter().enumerate() {
+                                            let row = ui.horizontal(|ui| {
+                                                let handle = ui.add(egui::Label::new("=").sense(egui::Sense::drag())).on_hover_text("Drag to reorder");
+                                                if handle.drag_started() {
+                                                    self.dragged_point = Some(i);
+                                                }
+                                                let selected = i == spline.selected_point as usize || spline.multi_selection.contains(&(i as u32));
+                                                let position = point.position;
+                                                if ui.selectable_label(selected, format!("{}: {:.1}, {:.1}, {:.1}", i + 1, position.x, position.y, position.z)).clicked() {
+                                                    clicked_point = Some(i);
+                                                }
+                                                if ui.small_button("x").on_hover_text("Remove this point").clicked() {
+                                                    removed_point = Some(i);
+                                                }
+                                            });
+                                            row_rects.push(row.response.rect);
+                                        }
+
+                                        // The dragged row gets dropped before the first row whose middle is below the pointer
+                                        if let Some(from) = self.dragged_point {
+                                            let pointer = ui.ctx().pointer_interact_pos();
+                                            let slot = pointer.map(|pointer| row_rects.iter().filter(|rect| rect.center().y < pointer.y).count());
+                                            if let Some(slot) = slot {
+                                                let y = row_rects.get(slot).map_or_else(|| row_rects.last().map_or(0.0, |rect| rect.bottom()), |rect| rect.top());
+                                                let x_range = ui.max_rect().x_range();
+                                                ui.painter().hline(x_range, y, ui.visuals().selection.stroke);
+                                            }
+                                            if ui.input(|input| input.pointer.any_released()) {
+                                                if let Some(slot) = slot {
+                                                    // Slots past the dragged row shift up once it's taken out
+                                                    let to = if slot > from { slot - 1 } else { slot };
+                                                    spline.move_point(from, to);
+                                                }
+                                                self.dragged_point = None;
+                                            }
+                                        }
+                                    });
+                                    if let Some(i) = clicked_point {
+                                        spline.selected_point = i as u32;
+                                        spline.multi_selection.clear();
+                                    }
+                                    if let Some(i) = removed_point {
+                                        spline.remove_point(i);
+                                    }
+                                });
                                 ui.separator();
 
+                                // Edit the properties shared by every selected point at once
+                                let selected_points = spline.selected_points();
+                                if selected_points.len() > 1 {
+                                    ui.label(format!("Shared properties - {} points selected", selected_points.len()))
+                                        .on_hover_text("Shift+click points or hold shift while stepping through points to change the selection. Mixed values get overwritten on edit");
+                                    let first_point = spline.data.points[selected_points[0]].clone();
+                                    let mixed = |differs: &dyn Fn(&spline::SplineControlPoint) -> bool| {
+                                        selected_points.iter().any(|i| differs(&spline.data.points[*i]))
+                                    };
+                                    let mixed_roll = mixed(&|p| p.roll != first_point.roll);
+                                    let mixed_tangent = mixed(&|p| p.tangent_magnitude != first_point.tangent_magnitude);
+                                    let mixed_color = mixed(&|p| p.color != first_point.color);
+                                    let mixed_radius = mixed(&|p| p.radius != first_point.radius);
+
+                                    ui.horizontal(|ui| {
+                                        ui.label("Roll:");
+                                        let mut roll = first_point.roll.0;
+                                        if ui.add(DragValue::new(&mut roll)).changed() {
+                                            for i in selected_points.iter() {
+                                                spline.data.points[*i].roll = cgmath::Deg(roll);
+                                            }
+                                            rebuild_spline = true;
+                                        }
+                                        if mixed_roll {
+                                            ui.label("(mixed)");
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Tangent Magnitude:");
+                                        let mut tangent_magnitude = first_point.tangent_magnitude;
+                                        if ui.add(DragValue::new(&mut tangent_magnitude)).changed() {
+                                            for i in selected_points.iter() {
+                                                spline.data.points[*i].tangent_magnitude = tangent_magnitude;
+                                            }
+                                            rebuild_spline = true;
+                                        }
+                                        if mixed_tangent {
+                                            ui.label("(mixed)");
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Radius Scale:");
+                                        let mut radius = first_point.radius;
+                                        if ui.add(DragValue::new(&mut radius).speed(0.01)).changed() {
+                                            for i in selected_points.iter() {
+                                                spline.data.points[*i].radius = radius;
+                                            }
+                                            rebuild_spline = true;
+                                        }
+                                        if mixed_radius {
+                                            ui.label("(mixed)");
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Color:");
+                                        let mut color = first_point.color;
+                                        if ui.color_edit_button_srgba(&mut color).changed() {
+                                            for i in selected_points.iter() {
+                                                spline.data.points[*i].color = color;
+                                            }
+                                        }
+                                        if mixed_color {
+                                            ui.label("(mixed)");
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Move by X:");
+                                        ui.add(DragValue::new(&mut self.selection_offset[0]));
+                                        ui.label("Y:");
+                                        ui.add(DragValue::new(&mut self.selection_offset[1]));
+                                        ui.label("Z:");
+                                        ui.add(DragValue::new(&mut self.selection_offset[2]));
+                                        if ui.button("Move").on_hover_text("Bundled points are left in place").clicked() {
+                                            spline.translate_selection(self.selection_offset.into());
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Snap all").on_hover_text(format!("Snap every selected point to {}", world.snap_value)).clicked() {
+                                            spline.snap_selection(world.snap_value);
+                                        }
+                                        if ui.button("Clear selection").clicked() {
+                                            spline.multi_selection.clear();
+                                        }
+                                    });
+                                    ui.separator();
+                                }
+
                                 let enabled = spline.selected_point < spline.data.points.len() as u32;
-                                // Default values to show in case we don't have a selected point and the UI is disabled
-                                let mut default_point = spline::SplineControlPoint::blank();
+                                // Without a selected point we are at the end of the spline, so the UI is
+                                // disabled and previews the point that would get added there instead.
+                                // Placement raycasts the map, so only do it when there's a preview.
+                                let mut default_point = (!enabled).then(|| spline.append_preview(&world.camera, world.placement_position()));
 
                                 if enabled {
                                     ui.label(format!("Point properties - Selected point: {}", spline.selected_point + 1));
                                 }
                                 else {
-                                    ui.label("Point properties");
+                                    ui.label("Point properties - Preview of the next added point");
                                 }
+                                let (previous_position, next_position) = spline.adjacent_positions(spline.selected_point as usize);
                                 ui.add_enabled_ui(enabled, |ui| {
                                     let selected_point = spline.selected_point as usize;
                                     ui.horizontal(|ui| {
@@ -360,12 +1226,15 @@ impl Gui {
                                             spline.add_before_selected();
                                         }
                                         if ui.button("-").clicked() {
-                                            spline.data.points.remove(selected_point);
-                                            rebuild_spline = true;
+                                            spline.remove_point(selected_point);
                                         }
                                     });
 
-                                    let point = spline.data.points.get_mut(selected_point).unwrap_or(&mut default_point);
+                                    let export_subdivisions = spline.data.subdivisions;
+                                    // Removing the last point leaves nothing to show until the next frame
+                                    let Some(point) = spline.data.points.get_mut(selected_point).or(default_point.as_mut()) else {
+                                        return;
+                                    };
                                     if point.bundle_ref.is_none() {
                                         ui.horizontal(|ui| {
                                             if ui.button("Bundle").clicked() && self.bundle_spline - 1 != world.selected_spline && world.splines[self.bundle_spline as usize - 1].borrow().data.bundle {
@@ -386,40 +1255,44 @@ impl Gui {
                                     }
 
                                     ui.add_enabled_ui(point.bundle_ref.is_none(), |ui| {
+                                        let pasted = Cell::new(None);
                                         ui.horizontal(|ui| {
-                                            ui.label("X:");
-                                            if ui.add(DragValue::new(&mut point.position.x)).changed() {
-                                                rebuild_spline = true;
-                                            }
-                                            ui.label("Y:");
-                                            if ui.add(DragValue::new(&mut point.position.y)).changed() {
-                                                rebuild_spline = true;
-                                            }
-                                            ui.label("Z:");
-                                            if ui.add(DragValue::new(&mut point.position.z)).changed() {
-                                                rebuild_spline = true;
+                                            for (axis, label) in ["X:", "Y:", "Z:"].into_iter().enumerate() {
+                                                ui.label(label);
+                                                if ui.add(coordinate_drag_value(&mut point.position[axis], axis, &pasted)).changed() {
+                                                    rebuild_spline = true;
+                                                }
                                             }
                                         });
+                                        if let Some((axis, values)) = pasted.take() {
+                                            for (i, value) in values.into_iter().enumerate().skip(1) {
+                                                point.position[axis + i] = value;
+                                            }
+                                            rebuild_spline = true;
+                                        }
 
                                         ui.horizontal(|ui| {
                                             ui.label("Snap position to");
-                                            ui.add(DragValue::new(&mut self.snap_value));
+                                            ui.add(DragValue::new(&mut world.snap_value))
+                                                .on_hover_text("Use the grid hotkeys to step through Hammer's grid sizes");
                                             ui.label("-");
                                             if ui.button("Snap").clicked() {
-                                                point.position.x = (point.position.x / self.snap_value).round() * self.snap_value;
-                                                point.position.y = (point.position.y / self.snap_value).round() * self.snap_value;
-                                                point.position.z = (point.position.z / self.snap_value).round() * self.snap_value;
+                                                point.position.x = (point.position.x / world.snap_value).round() * world.snap_value;
+                                                point.position.y = (point.position.y / world.snap_value).round() * world.snap_value;
+                                                point.position.z = (point.position.z / world.snap_value).round() * world.snap_value;
                                                 rebuild_spline = true;
                                             }
                                         });
 
+                                        // Handles replace the pitch, yaw, and magnitude when enabled
+                                        let angle_tangent = point.handles.is_none();
                                         ui.horizontal(|ui| {
                                             ui.label("Pitch:");
-                                            if ui.add(DragValue::new(&mut point.pitch.0)).changed() {
+                                            if ui.add_enabled(angle_tangent, DragValue::new(&mut point.pitch.0)).changed() {
                                                 rebuild_spline = true;
                                             }
                                             ui.label("Yaw:");
-                                            if ui.add(DragValue::new(&mut point.yaw.0)).changed() {
+                                            if ui.add_enabled(angle_tangent, DragValue::new(&mut point.yaw.0)).changed() {
                                                 rebuild_spline = true;
                                             }
                                             ui.label("Roll:");
@@ -427,29 +1300,250 @@ impl Gui {
                                                 rebuild_spline = true;
                                             }
                                         });
+                                        ui.horizontal(|ui| {
+                                            if ui.add_enabled(next_position.is_some(), egui::Button::new("Aim at next point")).on_hover_text("Point the tangent straight at the next point, keeping its magnitude").clicked() {
+                                                if let Some(next_position) = next_position {
+                                                    point.aim_tangent(next_position - point.position);
+                                                    rebuild_spline = true;
+                                                }
+                                            }
+                                            if ui.add_enabled(previous_position.is_some(), egui::Button::new("Aim from previous point")).on_hover_text("Point the tangent straight away from the previous point, keeping its magnitude").clicked() {
+                                                if let Some(previous_position) = previous_position {
+                                                    point.aim_tangent(point.position - previous_position);
+                                                    rebuild_spline = true;
+                                                }
+                                            }
+                                        });
 
                                         ui.horizontal(|ui| {
                                             ui.label("Tangent Magnitude:");
-                                            if ui.add(DragValue::new(&mut point.tangent_magnitude)).changed() {
+                                            if ui.add_enabled(angle_tangent, DragValue::new(&mut point.tangent_magnitude)).changed() {
                                                 rebuild_spline = true;
                                             }
                                         });
+
+                                        ui.horizontal(|ui| {
+                                            ui.label("Bezier Handles:").on_hover_text("Define the tangent by the offsets of its handles instead of pitch, yaw, and magnitude");
+                                            let mut use_handles = point.handles.is_some();
+                                            if ui.checkbox(&mut use_handles, "").changed() {
+                                                point.set_handles_enabled(use_handles);
+                                                rebuild_spline = true;
+                                            }
+                                            if use_handles {
+                                                ui.checkbox(&mut self.unify_handles, "Unify").on_hover_text("Mirror edits to one handle onto the other, keeping the curve smooth through the point");
+                                            }
+                                        });
+                                        if let Some((in_handle, out_handle)) = &mut point.handles {
+                                            let mut in_changed = false;
+                                            let mut out_changed = false;
+                                            ui.horizontal(|ui| {
+                                                ui.label("In X:");
+                                                in_changed |= ui.add(DragValue::new(&mut in_handle.x)).changed();
+                                                ui.label("Y:");
+                                                in_changed |= ui.add(DragValue::new(&mut in_handle.y)).changed();
+                                                ui.label("Z:");
+                                                in_changed |= ui.add(DragValue::new(&mut in_handle.z)).changed();
+                                            });
+                                            ui.horizontal(|ui| {
+                                                ui.label("Out X:");
+                                                out_changed |= ui.add(DragValue::new(&mut out_handle.x)).changed();
+                                                ui.label("Y:");
+                                                out_changed |= ui.add(DragValue::new(&mut out_handle.y)).changed();
+                                                ui.label("Z:");
+                                                out_changed |= ui.add(DragValue::new(&mut out_handle.z)).changed();
+                                            });
+                                            if self.unify_handles {
+                                                if in_changed {
+                                                    *out_handle = -*in_handle;
+                                                }
+                                                else if out_changed {
+                                                    *in_handle = -*out_handle;
+                                                }
+                                            }
+                                            if in_changed || out_changed {
+                                                rebuild_spline = true;
+                                            }
+                                        }
                                     });
 
+                                    ui.horizontal(|ui| {
+                                        ui.label("Radius Scale:").on_hover_text("Multiplies the spline's radius at this point. The tube tapers smoothly between points");
+                                        if ui.add(DragValue::new(&mut point.radius).speed(0.01)).changed() {
+                                            rebuild_spline = true;
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Segment Subdivisions:").on_hover_text("Export the segment after this point with this many subdivisions instead of the spline's. The viewport scales it like the render subdivisions");
+                                        let mut override_subdivisions = point.subdivisions.is_some();
+                                        if ui.checkbox(&mut override_subdivisions, "").changed() {
+                                            if override_subdivisions {
+                                                point.subdivisions = Some(export_subdivisions);
+                                            }
+                                            else {
+                                                point.subdivisions = None;
+                                            }
+                                            rebuild_spline = true;
+                                        }
+                                        if let Some(subdivisions) = &mut point.subdivisions {
+                                            if ui.add(DragValue::new(subdivisions).clamp_range(1..=u32::MAX)).changed() {
+                                                rebuild_spline = true;
+                                            }
+                                        }
+                                    });
                                     ui.horizontal(|ui| {
                                         ui.label("Color:");
                                         ui.color_edit_button_srgba(&mut point.color);
                                     });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Material:").on_hover_text("Export the segment after this point with this material instead of its color, given as a path under materials/");
+                                        let mut use_material = point.material_override.is_some();
+                                        if ui.checkbox(&mut use_material, "").changed() {
+                                            if use_material {
+                                                point.material_override = Some(String::new());
+                                            }
+                                            else {
+                                                point.material_override = None;
+                                            }
+                                        }
+                                        if let Some(material) = &mut point.material_override {
+                                            ui.text_edit_singleline(material);
+                                        }
+                                    });
                                 });
+                                ui.separator();
+
+                                // The selection from its first to its last point is the run that gets repeated
+                                let selected = spline.selected_points();
+                                let run = match (selected.first(), selected.last()) {
+                                    (Some(&first), Some(&last)) if first < last => Some((first, last)),
+                                    _ => None,
+                                };
+                                let max_copies = run.map_or(0, |(first, last)| spline.max_run_copies(first, last));
+                                match run {
+                                    Some((first, last)) => ui.label(format!("Repeat points {} to {}", first + 1, last + 1)),
+                                    None => ui.label("Repeat points - select at least two points"),
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.label("Copies:");
+                                    ui.add(DragValue::new(&mut self.repeat_count).clamp_range(1..=max_copies.max(1)));
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Extra offset X:");
+                                    ui.add(DragValue::new(&mut self.repeat_offset[0]));
+                                    ui.label("Y:");
+                                    ui.add(DragValue::new(&mut self.repeat_offset[1]));
+                                    ui.label("Z:");
+                                    ui.add(DragValue::new(&mut self.repeat_offset[2]));
+                                });
+                                if let Some((first, last)) = run {
+                                    if ui.add_enabled(max_copies > 0, egui::Button::new("Repeat")).clicked() {
+                                        spline.duplicate_run(first, last, self.repeat_count, self.repeat_offset.into());
+                                    }
+                                }
 
                                 if rebuild_spline {
                                     spline.request_rebuild();
                                 }
                             }
+                            if toggle_playback {
+                                if world.is_playing() {
+                                    world.stop_playback();
+                                }
+                                else {
+                                    world.start_playback();
+                                }
+                            }
                         }
                     }
                 });
 
+            // Label each point of the selected spline with its number. We draw on the background
+            // layer so that the labels don't cover up our windows.
+            if self.show_point_labels && !world.splines.is_empty() {
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                let spline = world.splines[world.selected_spline as usize].borrow();
+                for (i, point) in spline.data.points.iter().enumerate() {
+                    if let Some((x, y)) = world.project_to_screen(point.position, render_state) {
+                        let label_pos = egui::pos2(x, y) / ctx.pixels_per_point() - egui::vec2(0.0, 8.0);
+                        painter.text(label_pos, egui::Align2::CENTER_BOTTOM, format!("{}", i + 1), egui::FontId::proportional(14.0), egui::Color32::WHITE);
+                    }
+                }
+            }
+
+            // Label the entity markers with their classnames
+            if world.show_entity_labels {
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                for entity in world.visible_entities() {
+                    if let Some((x, y)) = world.project_to_screen(entity.origin, render_state) {
+                        let label_pos = egui::pos2(x, y) / ctx.pixels_per_point() - egui::vec2(0.0, 8.0);
+                        painter.text(label_pos, egui::Align2::CENTER_BOTTOM, &entity.classname, egui::FontId::proportional(12.0), entity.color());
+                    }
+                }
+            }
+
+            // Draw our measurement line and distance
+            if let Some(start) = world.measure_start {
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                let stroke = egui::Stroke::new(2.0, egui::Color32::YELLOW);
+                let start_screen = world.project_to_screen(start, render_state).map(|(x, y)| egui::pos2(x, y) / ctx.pixels_per_point());
+                if let Some(start_screen) = start_screen {
+                    painter.circle_filled(start_screen, 4.0, stroke.color);
+                }
+
+                if let Some(end) = world.measure_end {
+                    let end_screen = world.project_to_screen(end, render_state).map(|(x, y)| egui::pos2(x, y) / ctx.pixels_per_point());
+                    if let Some(end_screen) = end_screen {
+                        painter.circle_filled(end_screen, 4.0, stroke.color);
+                    }
+                    if let (Some(start_screen), Some(end_screen)) = (start_screen, end_screen) {
+                        let delta = end - start;
+                        painter.line_segment([start_screen, end_screen], stroke);
+                        painter.text(
+                            start_screen + (end_screen - start_screen) / 2.0,
+                            egui::Align2::CENTER_BOTTOM,
+                            format!("{:.2} ({:.2}, {:.2}, {:.2})", delta.magnitude(), delta.x, delta.y, delta.z),
+                            egui::FontId::proportional(14.0),
+                            stroke.color,
+                        );
+                    }
+                }
+            }
+
+            // Outline the window while a file is dragged over it, to show it can be dropped
+            if self.file_hovered {
+                let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("file_hover")));
+                let rect = ctx.screen_rect().shrink(4.0);
+                painter.rect_stroke(rect, 4.0, egui::Stroke::new(4.0, egui::Color32::LIGHT_BLUE));
+                painter.text(rect.center(), egui::Align2::CENTER_CENTER, "Drop a .vmf or spline save to open it", egui::FontId::proportional(20.0), egui::Color32::LIGHT_BLUE);
+            }
+
+            if self.autosave_prompt {
+                egui::Window::new("Restore Autosave")
+                    .anchor(egui::Align2::CENTER_CENTER, (0.0, 0.0))
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(&ctx, |ui| {
+                        ui.label("An autosave newer than your last manual save was found.");
+                        ui.horizontal(|ui| {
+                            if ui.button("Restore").clicked() {
+                                if let Some(save) = crate::autosave::read() {
+                                    world.restore_state(save.as_bytes(), render_state);
+                                }
+                                self.autosave_prompt = false;
+                            }
+                            if ui.button("Discard").clicked() {
+                                crate::autosave::remove();
+                                self.autosave_prompt = false;
+                            }
+                        });
+                    });
+            }
+
+            // The FPS counter sits right on the viewport, so its text has to stand out from the
+            // background rather than follow the theme
+            let [r, g, b] = world.background_color();
+            let dark_background = 0.2126 * r + 0.7152 * g + 0.0722 * b <= DARK_TEXT_LUMINANCE;
+            let overlay_text_color = if dark_background { egui::Color32::WHITE } else { egui::Color32::BLACK };
             egui::Window::new("FPS Counter")
                 .anchor(fps_anchor, fps_offset)
                 .resizable(false)
@@ -458,17 +1552,26 @@ impl Gui {
                     fill: egui::Color32::TRANSPARENT,
                     ..Default::default()
                 }).show(&ctx, |ui| {
-                    ui.colored_label(egui::Color32::BLACK, format!("{:.1} FPS", 1.0 / self.avg_frame_time));
+                    if self.show_fps {
+                        ui.colored_label(overlay_text_color, format!("{:.1} FPS", 1.0 / self.avg_frame_time));
+                        if self.show_frame_times {
+                            let min_frame_time = self.recent_frame_times.iter().copied().fold(f64::INFINITY, f64::min);
+                            let max_frame_time = self.recent_frame_times.iter().copied().fold(0.0, f64::max);
+                            ui.colored_label(overlay_text_color, format!("{:.2} ms (min {:.2}, max {:.2})", self.avg_frame_time * 1000.0, min_frame_time * 1000.0, max_frame_time * 1000.0));
+                        }
+                    }
+                    ui.colored_label(overlay_text_color, format!("Grid: {}", world.snap_value));
                 });
         });
 
         // Handle platform functions such as clipboard
         self.state.handle_platform_output(&render_state.window, full_ouptut.platform_output);
 
-        // Prepare egui output for rendering to wgpu
+        // Prepare egui output for rendering to wgpu. Use the scale egui laid the frame out with,
+        // which follows the window's current scale factor (and any egui zoom), so the two match.
         let screen_desc = ScreenDescriptor {
             size_in_pixels: render_state.size.into(),
-            pixels_per_point: render_state.window.scale_factor() as f32,
+            pixels_per_point: full_ouptut.pixels_per_point,
         };
         let tris = self.state.egui_ctx().tessellate(full_ouptut.shapes, full_ouptut.pixels_per_point);
         for (id, image_delta) in &full_ouptut.textures_delta.set {
@@ -501,3 +1604,82 @@ impl Gui {
         }
     }
 }
+
+// Set a property on every spline other than the selected one, which is still borrowed by the
+// spline panel, and rebuild them
+fn apply_to_other_splines(world: &World, apply: impl Fn(&mut spline::Spline)) {
+    for (i, other) in world.splines.iter().enumerate() {
+        if i != world.selected_spline as usize {
+            let mut other = other.borrow_mut();
+            apply(&mut other);
+            other.request_rebuild();
+        }
+    }
+}
+
+// DragValue for one coordinate of a position. Typing several values, like pasting "x y z" into
+// X, fills the following coordinates too, which get left in pasted for the caller to apply.
+// Invalid text leaves the coordinate as it was.
+fn coordinate_drag_value<'a>(value: &'a mut f32, axis: usize, pasted: &'a Cell<Option<(usize, Vec<f32>)>>) -> DragValue<'a> {
+    DragValue::new(value)
+        .update_while_editing(false) // Otherwise a half typed "x y z" would get applied
+        .custom_parser(move |text| {
+            let values = parse_coordinates(text)?;
+            if values.len() > 3 - axis {
+                return None;
+            }
+            let first = values[0];
+            if values.len() > 1 {
+                pasted.set(Some((axis, values)));
+            }
+            Some(first as f64)
+        })
+}
+
+// Parse whitespace or comma separated numbers, each optionally followed by a unit suffix like
+// "512u" or "512 units". Returns None if anything isn't a number.
+fn parse_coordinates(text: &str) -> Option<Vec<f32>> {
+    let mut values = Vec::new();
+    for token in text.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+        let token = token.to_ascii_lowercase();
+        let number = token.strip_suffix("units")
+            .or_else(|| token.strip_suffix("unit"))
+            .or_else(|| token.strip_suffix('u'))
+            .unwrap_or(&token);
+        if number.is_empty() {
+            // A suffix separated from its value by a space
+            if values.is_empty() {
+                return None;
+            }
+            continue;
+        }
+
+        let value: f32 = number.parse().ok()?;
+        if !value.is_finite() {
+            return None;
+        }
+        values.push(value);
+    }
+
+    if values.is_empty() {
+        return None;
+    }
+    Some(values)
+}
+
+// Rough human readable age, like "5 min"
+fn format_age(age: web_time::Duration) -> String {
+    let seconds = age.as_secs();
+    if seconds < 60 {
+        format!("{seconds} s")
+    }
+    else if seconds < 60 * 60 {
+        format!("{} min", seconds / 60)
+    }
+    else if seconds < 24 * 60 * 60 {
+        format!("{} h", seconds / (60 * 60))
+    }
+    else {
+        format!("{} days", seconds / (24 * 60 * 60))
+    }
+}
@@ -0,0 +1,188 @@
+// Rendering and exporting without a window, so that images and models of splines can be generated
+// outside of the app, such as thumbnails, golden images to catch regressions in the mesh
+// generation, or models rebuilt by a build script.
+use crate::texture;
+use crate::world::{self, camera, spline, DEFAULT_CLEAR_COLOR};
+use crate::world::spline::export;
+
+use anyhow::*;
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Point3, Rad, Vector3};
+use std::cell::RefCell;
+use wgpu::util::DeviceExt;
+
+const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const FOVY: f32 = 60.0;
+
+// Error from render_spline_image when there isn't even a software adapter to render with, so that
+// callers can tell it apart from rendering going wrong
+#[derive(Debug)]
+pub struct NoAdapterError;
+
+impl std::fmt::Display for NoAdapterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "no graphics adapter available")
+    }
+}
+
+impl std::error::Error for NoAdapterError {}
+
+// Render a spline's mesh to a width x height RGBA image. The camera looks down at the spline from
+// a fixed angle, backed off far enough to fit the whole mesh in view. Bundle references can't be
+// resolved without the other splines, so they are dropped.
+pub async fn render_spline_image(data: &spline::SplineData, width: u32, height: u32) -> Result<image::RgbaImage> {
+    ensure!(width > 0 && height > 0, "image size must be non-zero");
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    // Prefer a real adapter, but fall back to a software one so this works on machines without a GPU
+    let mut adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: None,
+        force_fallback_adapter: false,
+    }).await;
+    if adapter.is_none() {
+        adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: true,
+        }).await;
+    }
+    let adapter = adapter.ok_or(NoAdapterError)?;
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            label: None,
+        },
+        None
+    ).await?;
+
+    let camera_bind_group_layout = camera::create_bind_group_layout(&device);
+    let spline_renderer = spline::SplineRenderer::new(&device, IMAGE_FORMAT, &camera_bind_group_layout, 1);
+
+    // Build the spline's mesh with no point selected, so every point shows its own color
+    let mut spline = spline::Spline::new(&device, &spline_renderer);
+    spline.data = data.clone();
+    for point in spline.data.points.iter_mut() {
+        point.bundle_ref = None;
+    }
+    spline.selected_point = spline.data.points.len() as u32;
+    spline.request_rebuild();
+    spline.update(&[], &device, &queue);
+
+    // Frame the spline's bounding sphere from above and to the side
+    let (min, max) = spline.bounds().unwrap_or((Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0)));
+    let center = Point3::from_vec((min.to_vec() + max.to_vec()) / 2.0);
+    let radius = ((max - min).magnitude() / 2.0).max(1.0);
+    let aspect = width as f32 / height as f32;
+    let half_fov = Rad::from(Deg(FOVY.min(FOVY * aspect))) / 2.0;
+    let distance = radius / half_fov.0.sin();
+    let pitch = Rad(-0.5f32);
+    let yaw = Rad(std::f32::consts::FRAC_PI_4);
+    let view_dir = Vector3::new(pitch.0.cos() * yaw.0.cos(), pitch.0.cos() * yaw.0.sin(), pitch.0.sin());
+    let camera = camera::Camera {
+        position: center - view_dir * distance,
+        pitch,
+        yaw,
+        roll: Rad(0.0),
+        aspect,
+        fovy: FOVY,
+        znear: (distance - radius).max(1.0) / 2.0,
+        zfar: distance + radius * 2.0,
+        projection: camera::Projection::Perspective,
+    };
+    let mut camera_uniform = camera::CameraUniform::new();
+    camera_uniform.update_view_proj(&camera, Vector3::new(0.0, 0.0, 0.0));
+    let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Headless Camera Buffer"),
+        contents: bytemuck::cast_slice(&[camera_uniform]),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &camera_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }
+        ],
+        label: Some("headless_camera_bind_group"),
+    });
+
+    let target = texture::create_render_target(&device, width, height, IMAGE_FORMAT, "Headless Render Target");
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth_texture = texture::Texture::create_depth_texture(&device, width, height, 1, "headless_depth_texture");
+
+    let spline = RefCell::new(spline);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Headless Encoder"),
+    });
+    {
+        let spline = spline.borrow();
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Headless Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: DEFAULT_CLEAR_COLOR[0] as f64,
+                        g: DEFAULT_CLEAR_COLOR[1] as f64,
+                        b: DEFAULT_CLEAR_COLOR[2] as f64,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        spline_renderer.draw(&mut render_pass, &camera_bind_group, &spline, false, false);
+    }
+
+    texture::read_render_target(&device, &queue, encoder, &target)
+}
+
+// Convert a save, in any format the app can load, into the model zip that the Export button gives.
+// Only the spline data is needed to build the meshes, so this doesn't touch the GPU at all.
+pub fn export_save(save: &[u8], separately: bool) -> Result<Vec<u8>> {
+    let mut spline_data = world::decode_state(save)?.splines;
+    for data in spline_data.iter_mut() {
+        data.sanitize();
+    }
+    export::construct_zip(&spline_data, separately, export::ExportOrigin::FirstPoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::spline::tests::straight_spline;
+
+    #[test]
+    fn renders_a_spline() {
+        let image = match pollster::block_on(render_spline_image(&straight_spline(3, 8), 64, 48)) {
+            Result::Ok(image) => image,
+            // Not even a software adapter, so there's nothing to render with
+            Err(e) if e.is::<NoAdapterError>() => {
+                eprintln!("skipping, {e}");
+                return;
+            },
+            Err(e) => panic!("{e:#}"),
+        };
+        assert_eq!(image.dimensions(), (64, 48));
+        // The spline is framed in the middle, so it covers some of the background
+        let background = *image.get_pixel(0, 0);
+        assert!(image.pixels().any(|pixel| *pixel != background));
+    }
+}
@@ -0,0 +1,175 @@
+// Configurable keybindings. Input handling matches keys against actions instead of literal keys,
+// so that every hotkey can be rebound from the GUI. Bindings use physical key codes, so that they
+// stay in the same place on the keyboard regardless of layout (WASD on QWERTY is ZQSD on AZERTY).
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use winit::keyboard::PhysicalKey;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    RollLeft,
+    RollRight,
+    ToggleCameraLock,
+    PlacePoint,
+    SelectPrevious,
+    SelectNext,
+    IncreaseTangent,
+    DecreaseTangent,
+    GridSmaller,
+    GridLarger,
+    FocusPoint,
+}
+
+impl Action {
+    // In the order they get listed in the GUI
+    pub const ALL: [Action; 15] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::ToggleCameraLock,
+        Action::PlacePoint,
+        Action::SelectPrevious,
+        Action::SelectNext,
+        Action::IncreaseTangent,
+        Action::DecreaseTangent,
+        Action::GridSmaller,
+        Action::GridLarger,
+        Action::FocusPoint,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBackward => "Move backward",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::RollLeft => "Roll the camera left",
+            Action::RollRight => "Roll the camera right",
+            Action::ToggleCameraLock => "Toggle mouse capture, allowing camera control",
+            Action::PlacePoint => "Insert a new point into the current spline. Hold alt to switch between placing at the camera and on the surface in view",
+            Action::SelectPrevious => "Select the previous point on the current spline. Hold shift to extend the selection",
+            Action::SelectNext => "Select the next point on the current spline. Hold shift to extend the selection",
+            Action::IncreaseTangent => "Increase the tangent magnitude of the selected point by the snap value",
+            Action::DecreaseTangent => "Decrease the tangent magnitude of the selected point by the snap value",
+            Action::GridSmaller => "Halve the grid size used for snapping",
+            Action::GridLarger => "Double the grid size used for snapping",
+            Action::FocusPoint => "Move the camera to look at the selected point",
+        }
+    }
+
+    fn default_key(&self) -> &'static str {
+        match *self {
+            Action::MoveForward => "KeyW",
+            Action::MoveBackward => "KeyS",
+            Action::MoveLeft => "KeyA",
+            Action::MoveRight => "KeyD",
+            Action::RollLeft => "Comma",
+            Action::RollRight => "Period",
+            Action::ToggleCameraLock => "KeyZ",
+            Action::PlacePoint => "Space",
+            Action::SelectPrevious => "ArrowLeft",
+            Action::SelectNext => "ArrowRight",
+            Action::IncreaseTangent => "Equal",
+            Action::DecreaseTangent => "Minus",
+            Action::GridSmaller => "BracketLeft",
+            Action::GridLarger => "BracketRight",
+            Action::FocusPoint => "KeyF",
+        }
+    }
+}
+
+// Name a key so that it can be stored in a binding
+pub fn key_name(key: &PhysicalKey) -> Option<String> {
+    match key {
+        PhysicalKey::Code(code) => Some(format!("{code:?}")),
+        PhysicalKey::Unidentified(_) => None,
+    }
+}
+
+// Human readable version of a key name from key_name(). Key codes are named after the US QWERTY
+// layout, so letter and digit keys are shown by their QWERTY character.
+pub fn display_name(name: &str) -> String {
+    if name.is_empty() {
+        return "Unbound".to_string();
+    }
+
+    let short_name = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit"));
+    match short_name {
+        Some(short_name) if short_name.chars().count() == 1 => short_name.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Keybinds {
+    bindings: HashMap<Action, String>,
+    #[serde(skip)]
+    pub capturing: Option<Action>, // Action waiting for the next key press to bind to
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL.iter().map(|a| (*a, a.default_key().to_string())).collect(),
+            capturing: None,
+        }
+    }
+}
+
+impl Keybinds {
+    pub fn action(&self, key: &PhysicalKey) -> Option<Action> {
+        let name = key_name(key)?;
+        Action::ALL.iter().copied().find(|a| self.key(*a) == name)
+    }
+
+    pub fn key(&self, action: Action) -> &str {
+        self.bindings.get(&action).map(|k| k.as_str()).unwrap_or(action.default_key())
+    }
+
+    // Bind key to action. Any other action using the key loses its binding, so that a key never
+    // triggers two actions.
+    pub fn bind(&mut self, action: Action, key: String) {
+        for (other, other_key) in self.bindings.iter_mut() {
+            if *other != action && *other_key == key {
+                other_key.clear();
+            }
+        }
+        self.bindings.insert(action, key);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path() -> Option<std::path::PathBuf> {
+        Some(dirs::config_dir()?.join("spline_generator").join("keybinds.json"))
+    }
+
+    // Load the saved keybindings, falling back to the defaults
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(keybinds) = Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok()) {
+            return keybinds;
+        }
+
+        Self::default()
+    }
+
+    // Persist the keybindings. On web they only last for the session.
+    pub fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = Self::path() {
+            let result = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&path, serde_json::to_string(self).unwrap()));
+            if let Err(e) = result {
+                log::warn!("failed to save keybinds: {e}");
+            }
+        }
+    }
+}
@@ -1,17 +1,43 @@
+mod autosave;
 mod gui;
+mod headless;
+mod keybinds;
 mod texture;
+#[cfg(target_arch = "wasm32")]
+mod web_api;
 mod world;
 
 use web_time::{Duration, Instant};
 use winit::{
     event::*,
-    event_loop::EventLoop,
-    keyboard::Key,
+    event_loop::{ControlFlow, EventLoopBuilder},
+    keyboard::{Key, NamedKey},
     window::{CursorGrabMode, WindowBuilder, Window},
 };
 #[cfg(target_arch="wasm32")]
 use wasm_bindgen::prelude::*;
 
+// Library entry points for working with splines outside of the app
+pub use headless::{export_save, render_spline_image, NoAdapterError};
+pub use world::spline::{build_spline_mesh, CapStyle, InterpolationMode, SplineControlPoint, SplineData, SplineVertex, SubdivMode};
+
+// Events sent to the event loop from outside of winit. Only the web build has any, from the host page.
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        type UserEvent = web_api::HostEvent;
+    } else {
+        type UserEvent = ();
+    }
+}
+
+// Time between frames while the window is unfocused
+const BACKGROUND_FRAME_TIME: Duration = Duration::from_millis(100);
+
+// Frames in a row the surface can be lost or outdated before we recreate it instead of just
+// reconfiguring it, and how many recreations we try before giving up
+const SURFACE_RECREATE_THRESHOLD: u32 = 3;
+const MAX_SURFACE_RECREATE_ATTEMPTS: u32 = 5;
+
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
 }
@@ -21,15 +47,21 @@ struct State {
     world: world::World,
     gui: gui::Gui,
     camera_lock: bool,
+    surface_failures: u32, // Frames in a row that the surface was lost or outdated
+    last_autosave: Duration,
+    last_autosave_splines: String, // Splines of the last autosave, so we can skip saving if none of them changed
 }
 
 struct RenderState {
     window: Window,
+    instance: wgpu::Instance,
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
+    present_modes: Vec<wgpu::PresentMode>, // Present modes the surface supports that we offer
+    msaa_supported: bool, // Whether we can render with world::MSAA_SAMPLES samples
 }
 
 impl State {
@@ -62,7 +94,8 @@ impl State {
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                // Line polygon mode is only used for the map wireframe, which we can do without
+                features: adapter.features() & wgpu::Features::POLYGON_MODE_LINE,
                 // WebGL doesn't support all features of WGPU, so downlevel on web
                 limits: if cfg!(target_arch = "wasm32") {
                     wgpu::Limits::downlevel_webgl2_defaults()
@@ -95,13 +128,28 @@ impl State {
         // Wait to configure surface
         surface.configure(&device, &config);
 
+        // The automatic modes are always supported, but only offer the others if the surface has them
+        let mut present_modes = vec![wgpu::PresentMode::AutoVsync, wgpu::PresentMode::AutoNoVsync];
+        for mode in [wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox] {
+            if surface_caps.present_modes.contains(&mode) {
+                present_modes.push(mode);
+            }
+        }
+
+        // WebGL and some adapters can't multisample our formats, in which case we don't offer MSAA
+        let msaa_supported = [surface_format, texture::Texture::DEPTH_FORMAT].iter()
+            .all(|format| adapter.get_texture_format_features(*format).flags.sample_count_supported(world::MSAA_SAMPLES));
+
         let render_state = RenderState {
             window,
+            instance,
             surface,
             device,
             queue,
             config,
             size,
+            present_modes,
+            msaa_supported,
         };
 
         let world = world::World::new(&render_state);
@@ -112,6 +160,9 @@ impl State {
             world,
             gui,
             camera_lock: false,
+            surface_failures: 0,
+            last_autosave: Duration::ZERO,
+            last_autosave_splines: "[]".to_string(), // No splines yet, so nothing to autosave
         }
     }
 
@@ -130,19 +181,73 @@ impl State {
         }
     }
 
+    // Try to get a working surface again after it was lost or outdated. A reconfigure is usually
+    // enough, but after a suspend or GPU reset the surface itself can be dead, so we recreate it if
+    // the problem persists. Returns false once we've run out of attempts.
+    fn recover_surface(&mut self) -> bool {
+        self.surface_failures += 1;
+        if self.surface_failures < SURFACE_RECREATE_THRESHOLD {
+            self.resize(self.render_state.size);
+            return true;
+        }
+        if self.surface_failures >= SURFACE_RECREATE_THRESHOLD + MAX_SURFACE_RECREATE_ATTEMPTS {
+            return false;
+        }
+
+        log::warn!("surface still unusable after {} frames, recreating it", self.surface_failures);
+        if let Err(e) = self.render_state.reconfigure_surface() {
+            log::warn!("failed to recreate surface: {e}");
+        }
+        true
+    }
+
     fn input(&mut self, event: &WindowEvent) -> bool {
+        // While rebinding, the next key press goes to the binding instead of anywhere else.
+        // Escape cancels the rebind.
+        if let Some(action) = self.world.keybinds.capturing {
+            if let WindowEvent::KeyboardInput { event: KeyEvent { state: ElementState::Pressed, physical_key, logical_key, .. }, .. } = event {
+                if *logical_key != Key::Named(NamedKey::Escape) {
+                    if let Some(name) = keybinds::key_name(physical_key) {
+                        self.world.keybinds.bind(action, name);
+                        self.world.keybinds.save();
+                    }
+                }
+                self.world.keybinds.capturing = None;
+                return true;
+            }
+        }
+
+        // Dropped files get opened regardless of camera lock, since they don't come from the mouse
+        // or keyboard. winit doesn't report file drops on the web, so this is native only in practice.
+        if let WindowEvent::HoveredFile(_) | WindowEvent::HoveredFileCancelled | WindowEvent::DroppedFile(_) = event {
+            self.gui.set_file_hovered(matches!(event, WindowEvent::HoveredFile(_)));
+            if let WindowEvent::DroppedFile(path) = event {
+                self.gui.open_dropped_file(path.clone());
+            }
+            self.render_state.window.request_redraw();
+            return true;
+        }
+
         // Start by letting egui handle any inputs first. Then if the input wasn't consumed, we can
         // handle it ourselves. The one edge case is when camera lock is enabled. We don't want our
         // mouse/keyboard to affect egui, so don't forward if camera lock is enabled.
         // This might cause issues if some non-input events happens (such as window resize), but
-        // those events shouldn't happen during camera lock.
+        // those events shouldn't happen during camera lock. The exception is moving the window to
+        // a monitor with a different DPI, which egui always needs to hear about.
         let mut response = egui_winit::EventResponse { consumed: false, repaint: false };
-        if !self.camera_lock {
+        if !self.camera_lock || matches!(event, WindowEvent::ScaleFactorChanged { .. }) {
             response = self.gui.input(&self.render_state.window, event);
         }
         if response.repaint {
             self.render_state.window.request_redraw();
         }
+        // The cursor shouldn't pick anything in the world while it is over the GUI
+        if let WindowEvent::CursorMoved { .. } = event {
+            if response.consumed || self.gui.is_pointer_over_gui() {
+                self.world.clear_cursor();
+                return response.consumed;
+            }
+        }
         if response.consumed {
             return true;
         }
@@ -157,15 +262,16 @@ impl State {
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
                     state,
-                    logical_key,
+                    physical_key,
                     ..
                 },
                 ..
             } => {
                 if *state == ElementState::Pressed {
-                    match logical_key.as_ref() {
-                        Key::Character("z") | Key::Character("Z") => {
+                    match self.world.keybinds.action(physical_key) {
+                        Some(keybinds::Action::ToggleCameraLock) => {
                             self.camera_lock = !self.camera_lock;
+                            self.world.set_picking_enabled(!self.camera_lock);
                             if self.camera_lock {
                                 // Lock and hide the mouse. Since winit (at least currently)
                                 // doesn't support locked mode on all relevant platforms, use
@@ -191,6 +297,7 @@ impl State {
             },
             WindowEvent::Focused(focused) if *focused == false => {
                 self.camera_lock = false;
+                self.world.set_picking_enabled(true);
                 if self.render_state.window.set_cursor_grab(CursorGrabMode::None).is_err() {
                     eprintln!("failed to properly unset the cursor grab mode!");
                 }
@@ -209,16 +316,62 @@ impl State {
         }
     }
 
-    fn update(&mut self, dt: Duration) {
+    #[cfg(target_arch = "wasm32")]
+    fn handle_host_event(&mut self, event: web_api::HostEvent) {
+        match event {
+            web_api::HostEvent::LoadVmf(text) => {
+                match world::map::Map::from_string(&text, &self.render_state.device) {
+                    Ok(map) => self.world.map = map,
+                    Err(e) => log::warn!("failed to load VMF from host page: {e}"),
+                }
+            },
+            web_api::HostEvent::LoadState(json) => self.world.restore_state(json.as_bytes(), &self.render_state),
+        }
+    }
+
+    fn update(&mut self, dt: Duration, total_time: Duration) {
         // Process updates from the world
         self.world.update(&self.render_state, dt);
 
         // Process updates from the GUI
         self.gui.update(&self.render_state, &mut self.world);
+
+        // Apply any change of present mode from the GUI
+        if self.world.present_mode != self.render_state.config.present_mode {
+            self.render_state.config.present_mode = self.world.present_mode;
+            self.render_state.surface.configure(&self.render_state.device, &self.render_state.config);
+        }
+
+        // Periodically autosave. Hold off while the user hasn't decided whether to restore the
+        // previous autosave, otherwise we'd overwrite it.
+        if self.world.autosave_enabled && !self.gui.autosave_prompt_open()
+            && total_time - self.last_autosave >= Duration::from_secs_f32(self.world.autosave_interval) {
+            self.last_autosave = total_time;
+            // Only the splines are work worth restoring, so just moving the camera around doesn't
+            // get autosaved
+            let state = self.world.save_data();
+            let splines = serde_json::to_string(&state.splines).unwrap();
+            if splines != self.last_autosave_splines {
+                if state.splines.is_empty() {
+                    // Every spline got deleted, so restoring the autosave would only bring back
+                    // splines the user got rid of
+                    autosave::remove();
+                    self.last_autosave_splines = splines;
+                }
+                else {
+                    let serialized_state = String::from_utf8(world::encode_state(&state, false)).unwrap();
+                    match autosave::write(&serialized_state) {
+                        Ok(()) => self.last_autosave_splines = splines,
+                        Err(e) => log::warn!("failed to autosave: {e}"),
+                    }
+                }
+            }
+        }
     }
 
     fn render(&mut self, dt: Duration, total_time: Duration) -> Result<(), wgpu::SurfaceError> {
         let output = self.render_state.surface.get_current_texture()?;
+        self.surface_failures = 0;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder = self.render_state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
@@ -238,6 +391,15 @@ impl State {
     }
 }
 
+impl RenderState {
+    // Replace the surface with a fresh one for the window, configured the same as before
+    fn reconfigure_surface(&mut self) -> Result<(), wgpu::CreateSurfaceError> {
+        self.surface = unsafe { self.instance.create_surface(&self.window) }?;
+        self.surface.configure(&self.device, &self.config);
+        Ok(())
+    }
+}
+
 #[cfg_attr(target_arch="wasm32", wasm_bindgen(start))]
 pub async fn run() {
     cfg_if::cfg_if! {
@@ -249,8 +411,28 @@ pub async fn run() {
         }
     }
 
-    let event_loop = EventLoop::new().unwrap();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build().unwrap();
+    #[cfg(target_arch = "wasm32")]
+    web_api::set_proxy(event_loop.create_proxy());
+    let window_builder = WindowBuilder::new().with_title("Spline Generator");
+    // Start at a usable size, centered on the primary monitor if we can find it. The WASM canvas
+    // gets sized by the page instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    let window_builder = {
+        let inner_size = winit::dpi::LogicalSize::new(1280.0, 720.0);
+        let mut window_builder = window_builder.with_inner_size(inner_size);
+        if let Some(monitor) = event_loop.primary_monitor() {
+            let window_size = inner_size.to_physical::<i32>(monitor.scale_factor());
+            let monitor_size = monitor.size();
+            let monitor_position = monitor.position();
+            window_builder = window_builder.with_position(winit::dpi::PhysicalPosition::new(
+                monitor_position.x + (monitor_size.width as i32 - window_size.width) / 2,
+                monitor_position.y + (monitor_size.height as i32 - window_size.height) / 2,
+            ));
+        }
+        window_builder
+    };
+    let window = window_builder.build(&event_loop).unwrap();
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -269,6 +451,9 @@ pub async fn run() {
     let mut state = State::new(window).await;
     let mut total_time = Duration::ZERO;
     let mut last_render_time = Instant::now();
+    // Whether the window is in the background, or can't be seen at all
+    let mut focused = true;
+    let mut hidden = false;
 
     event_loop.run(move |event, elwt| {
         match event {
@@ -277,24 +462,46 @@ pub async fn run() {
                 window_id,
             } if window_id == state.window().id() => if !state.input(event) {
                 match event {
-                    WindowEvent::CloseRequested => elwt.exit(),
+                    WindowEvent::CloseRequested => {
+                        state.world.camera_controller.settings.save();
+                        elwt.exit();
+                    },
                     WindowEvent::Resized(physical_size) => {
+                        // Minimizing shrinks the window down to nothing on some platforms
+                        hidden = physical_size.width == 0 || physical_size.height == 0;
                         state.resize(*physical_size);
                     },
+                    WindowEvent::ScaleFactorChanged { .. } => {
+                        // The physical size usually changes along with the scale factor, and not
+                        // every platform follows up with a Resized event
+                        state.resize(state.window().inner_size());
+                        state.window().request_redraw();
+                    },
+                    WindowEvent::Focused(is_focused) => {
+                        focused = *is_focused;
+                    },
+                    WindowEvent::Occluded(is_occluded) => {
+                        hidden = *is_occluded;
+                    },
                     WindowEvent::RedrawRequested => {
                         let now = Instant::now();
                         let dt = now - last_render_time;
                         total_time += dt;
                         last_render_time = now;
-                        state.update(dt);
+                        state.update(dt, total_time);
 
                         match state.render(dt, total_time) {
                             Ok(_) => {}
-                            // Reconfigure the surface if lost
-                            Err(wgpu::SurfaceError::Lost) => state.resize(state.render_state.size),
+                            // Reconfigure or recreate the surface if lost or outdated
+                            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                                if !state.recover_surface() {
+                                    log::error!("couldn't recover the surface, giving up");
+                                    elwt.exit();
+                                }
+                            },
                             // The system is out of memory, we should probably quit
                             Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
-                            // All other errors (Outdated, Timeout) should be resolved by the next frame
+                            // Timeouts should be resolved by the next frame
                             Err(e) => eprintln!("{:?}", e),
                         }
 
@@ -307,11 +514,41 @@ pub async fn run() {
                 ..
             } => {
                 state.input_mouse_delta(delta);
+                // Device events arrive even while we're in the background
+                if focused {
+                    state.window().request_redraw();
+                }
+            },
+            #[cfg(target_arch = "wasm32")]
+            Event::UserEvent(host_event) => {
+                state.handle_host_event(host_event);
                 state.window().request_redraw();
             },
             Event::AboutToWait => {
-                // RedrawRequested will only trigger once unless we manually request it.
-                state.window().request_redraw();
+                // RedrawRequested will only trigger once unless we manually request it. Save the
+                // GPU from redrawing while the window can't be seen, and only redraw occasionally
+                // while the window is in the background.
+                if hidden {
+                    elwt.set_control_flow(ControlFlow::Wait);
+                }
+                else {
+                    let mut frame_time = Duration::ZERO;
+                    if state.world.frame_cap_enabled {
+                        frame_time = Duration::from_secs_f32(1.0 / state.world.frame_cap);
+                    }
+                    if !focused {
+                        frame_time = frame_time.max(BACKGROUND_FRAME_TIME);
+                    }
+
+                    // Wait out the rest of the frame if we are limiting our frame rate
+                    if last_render_time.elapsed() >= frame_time {
+                        elwt.set_control_flow(ControlFlow::Wait);
+                        state.window().request_redraw();
+                    }
+                    else {
+                        elwt.set_control_flow(ControlFlow::WaitUntil(last_render_time + frame_time));
+                    }
+                }
             }
             _ => {}
         }
@@ -1,5 +1,42 @@
-use spline_generator::run;
+use spline_generator::{export_save, run};
+
+use anyhow::*;
+
+const USAGE: &str = "usage: spline_generator [--input <save> --output <model.zip> [--separately]]";
 
 fn main() {
-    pollster::block_on(run());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        pollster::block_on(run());
+        return;
+    }
+
+    // Given arguments, convert a save to a model zip without opening the window
+    if let Err(e) = convert(&args) {
+        eprintln!("error: {e:#}");
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn convert(args: &[String]) -> Result<()> {
+    let mut input = None;
+    let mut output = None;
+    let mut separately = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input = Some(args.next().context("--input needs a path")?),
+            "--output" => output = Some(args.next().context("--output needs a path")?),
+            "--separately" => separately = true,
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    let input = input.context("missing --input")?;
+    let output = output.context("missing --output")?;
+
+    let save = std::fs::read(input).with_context(|| format!("failed to read {input}"))?;
+    let zip = export_save(&save, separately).with_context(|| format!("failed to export {input}"))?;
+    std::fs::write(output, zip).with_context(|| format!("failed to write {output}"))?;
+    Ok(())
 }
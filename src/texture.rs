@@ -79,17 +79,17 @@ impl Texture {
 
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32, sample_count: u32, label: &str) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT
@@ -117,3 +117,140 @@ impl Texture {
         Self { texture, view, sampler }
     }
 }
+
+// Texture we can render to offscreen and then read back with read_render_target() or
+// RenderTargetReadback
+pub fn create_render_target(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+// Multisampled color target to render into with MSAA. It gets resolved into a regular texture at
+// the end of the render pass, so only the view is needed.
+pub fn create_msaa_target(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, sample_count: u32, label: &str) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+// Copy of a render target on its way back from the GPU, see RenderTargetReadback::start()
+pub struct RenderTargetReadback {
+    output_buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+    receiver: std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+impl RenderTargetReadback {
+    // Submit encoder along with a copy of the render target, without waiting for the GPU. The
+    // target must use an 8-bit RGBA or BGRA format.
+    pub fn start(device: &wgpu::Device, queue: &wgpu::Queue, mut encoder: wgpu::CommandEncoder, texture: &wgpu::Texture) -> Self {
+        let width = texture.width();
+        let height = texture.height();
+
+        // Rows of a texture copy need to be aligned, so pad them out and strip the padding after
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (width * 4).div_ceil(align) * align;
+        let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Render Target Output Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        output_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        // Surfaces might prefer BGRA, but images are RGBA
+        let bgra = matches!(texture.format(), wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+        Self { output_buffer, width, height, padded_bytes_per_row, bgra, receiver }
+    }
+
+    // The target's pixels as RGBA if the GPU is done copying them, without blocking
+    pub fn try_finish(&self, device: &wgpu::Device) -> Option<Result<image::RgbaImage>> {
+        device.poll(wgpu::Maintain::Poll);
+        match self.receiver.try_recv() {
+            Result::Ok(result) => Some(result.map_err(Error::from).and_then(|()| self.pixels())),
+            Err(std::sync::mpsc::TryRecvError::Empty) => None,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Some(Err(anyhow!("render target read back was dropped"))),
+        }
+    }
+
+    // Wait for the GPU to finish copying, then return the target's pixels as RGBA
+    pub fn finish(self, device: &wgpu::Device) -> Result<image::RgbaImage> {
+        device.poll(wgpu::Maintain::Wait);
+        self.receiver.recv()??;
+        self.pixels()
+    }
+
+    fn pixels(&self) -> Result<image::RgbaImage> {
+        let unpadded_bytes_per_row = self.width * 4;
+        let buffer_slice = self.output_buffer.slice(..);
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * self.height) as usize);
+        for row in buffer_slice.get_mapped_range().chunks(self.padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        self.output_buffer.unmap();
+
+        if self.bgra {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels).context("render target read back with the wrong size")
+    }
+}
+
+// Submit encoder along with a copy of the render target, then wait for the GPU and return the
+// target's pixels as RGBA. The target must use an 8-bit RGBA or BGRA format.
+pub fn read_render_target(device: &wgpu::Device, queue: &wgpu::Queue, encoder: wgpu::CommandEncoder, texture: &wgpu::Texture) -> Result<image::RgbaImage> {
+    RenderTargetReadback::start(device, queue, encoder, texture).finish(device)
+}
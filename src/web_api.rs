@@ -0,0 +1,38 @@
+// Functions the host page can call to feed data into the running app, so that it can be embedded
+// with a map and splines preloaded. The calls get forwarded to the event loop as user events.
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use winit::event_loop::EventLoopProxy;
+
+pub enum HostEvent {
+    LoadVmf(String),
+    LoadState(String),
+}
+
+thread_local! {
+    static PROXY: RefCell<Option<EventLoopProxy<HostEvent>>> = const { RefCell::new(None) };
+}
+
+pub fn set_proxy(proxy: EventLoopProxy<HostEvent>) {
+    PROXY.with(|p| *p.borrow_mut() = Some(proxy));
+}
+
+fn send(event: HostEvent) -> Result<(), JsError> {
+    PROXY.with(|p| {
+        let proxy = p.borrow();
+        let proxy = proxy.as_ref().ok_or_else(|| JsError::new("spline generator hasn't started yet"))?;
+        proxy.send_event(event).map_err(|_| JsError::new("spline generator has exited"))
+    })
+}
+
+// Replace the current map with the given VMF text
+#[wasm_bindgen]
+pub fn load_vmf(text: String) -> Result<(), JsError> {
+    send(HostEvent::LoadVmf(text))
+}
+
+// Replace the current splines with the given spline save JSON
+#[wasm_bindgen]
+pub fn load_state(json: String) -> Result<(), JsError> {
+    send(HostEvent::LoadState(json))
+}
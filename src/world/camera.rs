@@ -1,11 +1,40 @@
+use crate::keybinds::{Action, Keybinds};
+
 use cgmath::prelude::*;
-use cgmath::{Deg, Point3, Matrix4, Rad, Vector3};
-use std::f32::consts::FRAC_PI_2;
+use cgmath::{Deg, Point3, Matrix4, Rad, Vector3, Vector4};
+use serde::{Serialize, Deserialize};
+use std::f32::consts::{FRAC_PI_2, PI};
 use web_time::Duration;
 use winit::event::*;
-use winit::keyboard::Key;
 
-const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+pub const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+
+// Speed the camera rolls at, in rad per second
+const ROLL_SPEED: f32 = 1.0;
+
+// Range of camera speeds in units per second, so that scrolling can't send the camera flying off
+// the map or leave it crawling
+pub const MIN_SPEED: f32 = 10.0;
+pub const MAX_SPEED: f32 = 20000.0;
+// Factor the camera speed changes by per scroll wheel notch
+const SPEED_SCROLL_STEP: f32 = 1.2;
+
+// Time in seconds for the smoothed camera velocity to get ~63% of the way to its target
+const SMOOTHING_TIME_CONSTANT: f32 = 0.1;
+
+// Range of vertical FOVs in degrees that give a usable perspective matrix. Near 0 or 180 the
+// matrix degenerates and the view goes black or inverts.
+pub const MIN_FOVY: f32 = 10.0;
+pub const MAX_FOVY: f32 = 170.0;
+
+// View height in orthographic mode at which movement runs at the normal speed
+const ORTHO_REFERENCE_HEIGHT: f32 = 1024.0;
+// View height used when switching to orthographic mode
+pub const DEFAULT_ORTHO_HEIGHT: f32 = 2048.0;
+// Factor the view height changes by per scroll wheel notch in orthographic mode
+const ORTHO_ZOOM_STEP: f32 = 1.25;
+// Scrolled pixels that count as one wheel notch, for touchpads and other pixel based scrolling
+const PIXELS_PER_SCROLL_LINE: f32 = 50.0;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -20,12 +49,31 @@ impl CameraUniform {
         }
     }
 
-    pub fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix().into();
+    // Positions on the GPU are relative to render_origin, so build the matrix relative to it too
+    pub fn update_view_proj(&mut self, camera: &Camera, render_origin: Vector3<f32>) {
+        self.view_proj = camera.build_view_projection_matrix_relative(render_origin).into();
     }
 
 }
 
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }
+        ],
+        label: Some("camera_bind_group_layout"),
+    })
+}
+
 #[rustfmt::skip]
 const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -34,114 +82,288 @@ const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // Height is the number of world units visible vertically
+    Orthographic { height: f32 },
+}
+
+// Orthographic views along the world axes, matching Hammer's 2D views
+#[derive(Clone, Copy, PartialEq)]
+pub enum ViewPreset {
+    Top, // Looking down, X to the right
+    Front, // Looking along -X, Y to the right
+    Side, // Looking along +Y, X to the right
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 3] = [ViewPreset::Top, ViewPreset::Front, ViewPreset::Side];
+}
+
+impl std::fmt::Display for ViewPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ViewPreset::Top => write!(f, "Top"),
+            ViewPreset::Front => write!(f, "Front"),
+            ViewPreset::Side => write!(f, "Side"),
+        }
+    }
+}
+
+// Convert a scroll event into a number of wheel notches, positive when scrolling up
+pub fn scroll_steps(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+    }
+}
+
 pub struct Camera {
     pub position: Point3<f32>,
     pub pitch: Rad<f32>,
     pub yaw: Rad<f32>,
+    pub roll: Rad<f32>,
     pub aspect: f32,
     pub fovy: f32,
     pub znear: f32,
     pub zfar: f32,
+    pub projection: Projection,
 }
 
 impl Camera {
     pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+        self.build_view_projection_matrix_relative(Vector3::zero())
+    }
+
+    // Build the matrix for positions that have had origin subtracted from them. Subtracting from
+    // the camera position here keeps the translation small, so far away positions don't lose
+    // precision in the matrix.
+    pub fn build_view_projection_matrix_relative(&self, origin: Vector3<f32>) -> Matrix4<f32> {
+        let view_dir = self.view_direction();
+
+        // Roll the up vector around the view direction. Pitch is clamped short of straight up or
+        // down, so the view direction is never parallel to Z.
+        let view_right = view_dir.cross(Vector3::unit_z()).normalize();
+        let view_up = view_right.cross(view_dir);
+        let (sin_roll, cos_roll) = self.roll.sin_cos();
+        let rolled_up = view_up * cos_roll + view_right * sin_roll;
+
+        let view = Matrix4::look_to_rh(self.position - origin, view_dir, rolled_up);
+        let proj = match self.projection {
+            Projection::Perspective => cgmath::perspective(Deg(self.fovy.clamp(MIN_FOVY, MAX_FOVY)), self.aspect, self.znear, self.zfar),
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                cgmath::ortho(-half_width, half_width, -half_height, half_height, self.znear, self.zfar)
+            }
+        };
+
+        return OPENGL_TO_WGPU_MATRIX * proj * view;
+    }
+
+    // Normalized direction the camera is looking in
+    pub fn view_direction(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
-        let view_dir = Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch);
+        Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch)
+    }
+
+    // Turn to look along direction. Roll is left alone, as is the yaw when looking straight up or
+    // down since it's undefined there.
+    pub fn look_along(&mut self, direction: Vector3<f32>) {
+        self.pitch = Rad(direction.z.clamp(-1.0, 1.0).asin().clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+        if direction.x != 0.0 || direction.y != 0.0 {
+            self.yaw = Rad(direction.y.atan2(direction.x));
+        }
+    }
 
-        let view = Matrix4::look_to_rh(self.position, view_dir, Vector3::unit_z());
-        let proj = cgmath::perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar);
+    // Switch to an orthographic view looking along one of the world axes. The view height is
+    // kept if we are already orthographic.
+    pub fn apply_view_preset(&mut self, preset: ViewPreset) {
+        (self.pitch, self.yaw) = match preset {
+            ViewPreset::Top => (-Rad(SAFE_FRAC_PI_2), Rad(FRAC_PI_2)),
+            ViewPreset::Front => (Rad(0.0), Rad(PI)),
+            ViewPreset::Side => (Rad(0.0), Rad(FRAC_PI_2)),
+        };
+        self.roll = Rad(0.0);
+        if self.projection == Projection::Perspective {
+            self.projection = Projection::Orthographic { height: DEFAULT_ORTHO_HEIGHT };
+        }
+    }
 
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+    // Zoom an orthographic view in by the given number of scroll wheel notches, or out if
+    // negative. Perspective views are left alone.
+    pub fn zoom(&mut self, steps: f32) {
+        if let Projection::Orthographic { height } = &mut self.projection {
+            *height = (*height / ORTHO_ZOOM_STEP.powf(steps)).max(1.0);
+        }
+    }
+
+    // Calculate the ray going from the camera through the given pixel of the window, returning
+    // the ray's origin and normalized direction.
+    pub fn cursor_ray(&self, cursor: (f32, f32), window_size: (f32, f32)) -> Option<(Point3<f32>, Vector3<f32>)> {
+        let inverse_view_proj = self.build_view_projection_matrix().invert()?;
+        let ndc_x = 2.0 * cursor.0 / window_size.0 - 1.0;
+        let ndc_y = 1.0 - 2.0 * cursor.1 / window_size.1;
+
+        // Unproject a point on the near plane and far plane to get our ray
+        let near = inverse_view_proj * Vector4::new(ndc_x, ndc_y, 0.0, 1.0);
+        let far = inverse_view_proj * Vector4::new(ndc_x, ndc_y, 1.0, 1.0);
+        let near = Point3::from_homogeneous(near);
+        let far = Point3::from_homogeneous(far);
+
+        Some((near, (far - near).normalize()))
+    }
+
+    // Project a world position to the pixel it lands on in the window. Returns None if the
+    // position is behind the camera.
+    pub fn project_to_screen(&self, position: Point3<f32>, window_size: (f32, f32)) -> Option<(f32, f32)> {
+        let clip = self.build_view_projection_matrix() * position.to_homogeneous();
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc_x = clip.x / clip.w;
+        let ndc_y = clip.y / clip.w;
+        Some(((ndc_x + 1.0) / 2.0 * window_size.0, (1.0 - ndc_y) / 2.0 * window_size.1))
+    }
+}
+
+// Camera controls the user can tune, persisted between sessions like the keybinds
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub speed: f32, // In units per second
+    pub speed_multiplier: f32, // Applied to the speed while shift is held
+    pub sensitivity: f32, // In rad per pixel
+    pub smoothing: bool, // Whether to accelerate and decelerate instead of instantly changing speed
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            speed: 500.0,
+            speed_multiplier: 4.0,
+            sensitivity: PI / 1000.0,
+            smoothing: false, // Off by default, so the camera moves like it always has
+        }
+    }
+}
+
+impl CameraSettings {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path() -> Option<std::path::PathBuf> {
+        Some(dirs::config_dir()?.join("spline_generator").join("camera.json"))
+    }
+
+    // Load the saved camera settings, falling back to the defaults
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(settings) = Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok()) {
+            return settings;
+        }
+
+        Self::default()
+    }
+
+    // Persist the camera settings. On web they only last for the session.
+    pub fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = Self::path() {
+            let result = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&path, serde_json::to_string(self).unwrap()));
+            if let Err(e) = result {
+                log::warn!("failed to save camera settings: {e}");
+            }
+        }
     }
 }
 
 pub struct CameraController {
-    speed: f32,
-    speed_multiplier: f32,
-    sensitivity: f32,
+    pub settings: CameraSettings,
     is_forward_pressed: bool,
     is_backward_pressed: bool,
     is_left_pressed: bool,
     is_right_pressed: bool,
     is_speed_multiplied: bool,
+    is_roll_left_pressed: bool,
+    is_roll_right_pressed: bool,
     delta_pitch: f32,
     delta_yaw: f32,
+    velocity: Vector3<f32>,
 }
 
 impl CameraController {
-    // Speed is given in units per second, sensitivity in rad per pixel
-    pub fn new(speed: f32, speed_multiplier:f32, sensitivity: f32) -> Self {
+    pub fn new(settings: CameraSettings) -> Self {
         Self {
-            speed,
-            speed_multiplier,
-            sensitivity,
+            settings,
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
             is_speed_multiplied: false,
+            is_roll_left_pressed: false,
+            is_roll_right_pressed: false,
             delta_pitch: 0.0,
             delta_yaw: 0.0,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
         }
     }
 
-    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+    pub fn process_events(&mut self, event: &WindowEvent, keybinds: &Keybinds) -> bool {
         match event {
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
                     state,
-                    logical_key,
+                    physical_key,
                     ..
                 },
                 ..
             } => {
                 let is_pressed = *state == ElementState::Pressed;
-                match logical_key.as_ref() {
-                    Key::Character("w") => {
+                match keybinds.action(physical_key) {
+                    Some(Action::MoveForward) => {
                         self.is_forward_pressed = is_pressed;
-                        self.is_speed_multiplied = false;
                         true
                     }
-                    Key::Character("W") => {
-                        self.is_forward_pressed = is_pressed;
-                        self.is_speed_multiplied = true;
-                        true
-                    }
-                    Key::Character("a") => {
-                        self.is_left_pressed = is_pressed;
-                        self.is_speed_multiplied = false;
-                        true
-                    }
-                    Key::Character("A") => {
+                    Some(Action::MoveLeft) => {
                         self.is_left_pressed = is_pressed;
-                        self.is_speed_multiplied = true;
                         true
                     }
-                    Key::Character("s") => {
+                    Some(Action::MoveBackward) => {
                         self.is_backward_pressed = is_pressed;
-                        self.is_speed_multiplied = false;
                         true
                     }
-                    Key::Character("S") => {
-                        self.is_backward_pressed = is_pressed;
-                        self.is_speed_multiplied = true;
+                    Some(Action::MoveRight) => {
+                        self.is_right_pressed = is_pressed;
                         true
                     }
-                    Key::Character("d") => {
-                        self.is_right_pressed = is_pressed;
-                        self.is_speed_multiplied = false;
+                    Some(Action::RollLeft) => {
+                        self.is_roll_left_pressed = is_pressed;
                         true
                     }
-                    Key::Character("D") => {
-                        self.is_right_pressed = is_pressed;
-                        self.is_speed_multiplied = true;
+                    Some(Action::RollRight) => {
+                        self.is_roll_right_pressed = is_pressed;
                         true
                     }
                     _ => false,
                 }
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                // Track shift directly rather than through the character case, which caps lock
+                // and some layouts would break. Let the event through in case others need it.
+                self.is_speed_multiplied = modifiers.state().shift_key();
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // Scrolling up speeds the camera up, and scrolling down slows it down
+                let speed = self.settings.speed * SPEED_SCROLL_STEP.powf(scroll_steps(delta));
+                self.settings.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+                true
+            }
             _ => false,
         }
     }
@@ -155,37 +377,72 @@ impl CameraController {
         }
     }
 
+    // Drop any momentum and pending mouse movement, so that a camera moved from elsewhere stays put
+    pub fn stop(&mut self) {
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        self.delta_pitch = 0.0;
+        self.delta_yaw = 0.0;
+    }
+
     pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
-        let mut speed = self.speed;
+        let mut speed = self.settings.speed;
         if self.is_speed_multiplied {
-            speed *= self.speed_multiplier;
+            speed *= self.settings.speed_multiplier;
         }
 
         let (sin_pitch, cos_pitch) = camera.pitch.sin_cos();
         let (sin_yaw, cos_yaw) = camera.yaw.sin_cos();
-        let view_dir = Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch);
+        let mut forward_dir = Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch);
+
+        // Moving towards the view doesn't change an orthographic view, so instead pan along the
+        // screen's up direction. We also scale our speed with the visible extent so that panning
+        // feels the same no matter how zoomed in we are.
+        if let Projection::Orthographic { height } = camera.projection {
+            forward_dir = Vector3::new(-sin_pitch * cos_yaw, -sin_pitch * sin_yaw, cos_pitch);
+            speed *= height / ORTHO_REFERENCE_HEIGHT;
+        }
 
         // For forwards/backwards, we translate in the direction of the camera
+        let mut target_velocity = Vector3::new(0.0, 0.0, 0.0);
         if self.is_forward_pressed {
-            camera.position += view_dir * speed * dt;
+            target_velocity += forward_dir * speed;
         }
         if self.is_backward_pressed {
-            camera.position -= view_dir * speed * dt;
+            target_velocity -= forward_dir * speed;
         }
 
-        // Since we don't have any roll, left/right will always be in the z = 0 plane.
+        // Roll only affects the view, so left/right will always be in the z = 0 plane.
         let view_right = Vector3::new(sin_yaw, -cos_yaw, 0.0);
 
         if self.is_right_pressed {
-            camera.position += view_right * speed * dt;
+            target_velocity += view_right * speed;
         }
         if self.is_left_pressed {
-            camera.position -= view_right * speed * dt;
+            target_velocity -= view_right * speed;
+        }
+
+        if self.settings.smoothing {
+            // Exponentially approach our target velocity, which eases in and out of movement
+            // independently of the frame rate
+            let blend = 1.0 - (-dt / SMOOTHING_TIME_CONSTANT).exp();
+            self.velocity += (target_velocity - self.velocity) * blend;
+        }
+        else {
+            self.velocity = target_velocity;
+        }
+        camera.position += self.velocity * dt;
+
+        if self.is_roll_right_pressed {
+            camera.roll += Rad(ROLL_SPEED * dt);
+        }
+        if self.is_roll_left_pressed {
+            camera.roll -= Rad(ROLL_SPEED * dt);
         }
+        camera.roll = camera.roll.normalize_signed();
 
-        camera.pitch += Rad(self.delta_pitch * self.sensitivity);
-        camera.yaw += Rad(self.delta_yaw * self.sensitivity);
+        camera.pitch += Rad(self.delta_pitch * self.settings.sensitivity);
+        camera.yaw += Rad(self.delta_yaw * self.settings.sensitivity);
 
         self.delta_pitch = 0.0;
         self.delta_yaw = 0.0;
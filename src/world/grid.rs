@@ -0,0 +1,221 @@
+// Ground grid on the Z=0 plane and colored axis lines through the origin, giving a frame of
+// reference when there's no map loaded
+use crate::texture;
+use crate::RenderState;
+use crate::Vertex;
+
+use cgmath::{Point3, Vector3};
+use wgpu::util::DeviceExt;
+
+// Number of grid lines on each side of the center, in each direction
+const GRID_HALF_LINES: i32 = 64;
+// Grid lines fully fade out at this many grid spacings from the camera, which is where they end
+const GRID_FADE_SPACINGS: f32 = GRID_HALF_LINES as f32;
+// Length of each axis line on either side of the origin, well past the edge of a Source map
+const AXIS_LENGTH: f32 = 65536.0;
+
+const GRID_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.5];
+const X_AXIS_COLOR: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+const Y_AXIS_COLOR: [f32; 4] = [0.1, 0.9, 0.1, 1.0];
+const Z_AXIS_COLOR: [f32; 4] = [0.1, 0.1, 0.9, 1.0];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+    grid_line: f32, // 1 for grid lines, which follow the camera and fade out, and 0 for the axes
+}
+
+impl GridVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4, 2 => Float32];
+}
+
+impl crate::Vertex for GridVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GridVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &GridVertex::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    offset: [f32; 3], // Added to the grid lines, relative to the render origin
+    fade_distance: f32,
+    camera_position: [f32; 3], // Relative to the render origin
+    _padding: f32,
+}
+
+pub struct GridRenderer {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    spacing: f32, // Spacing and render origin the vertex buffer was built with
+    render_origin: Vector3<f32>,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl GridRenderer {
+    pub fn new(render_state: &RenderState, camera_layout: &wgpu::BindGroupLayout, sample_count: u32) -> Self {
+        let uniform_buffer = render_state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[GridUniform {
+                offset: [0.0; 3],
+                fade_distance: 0.0,
+                camera_position: [0.0; 3],
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_bind_group_layout = render_state.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("grid_uniform_bind_group_layout"),
+        });
+        let uniform_bind_group = render_state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("grid_uniform_bind_group"),
+        });
+
+        let shader = render_state.device.create_shader_module(wgpu::include_wgsl!("grid_shader.wgsl"));
+
+        let render_pipeline_layout = render_state.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Render Pipeline Layout"),
+            bind_group_layouts: &[
+                camera_layout,
+                &uniform_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = render_state.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grid Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    GridVertex::desc(),
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_state.config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // The grid is see-through, so it doesn't write depth and hide whatever is drawn later
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let (vertex_buffer, vertex_count) = build_lines(&render_state.device, 1.0, Vector3::new(0.0, 0.0, 0.0));
+
+        GridRenderer {
+            vertex_buffer,
+            vertex_count,
+            spacing: 1.0,
+            render_origin: Vector3::new(0.0, 0.0, 0.0),
+            uniform_buffer,
+            uniform_bind_group,
+            render_pipeline,
+        }
+    }
+
+    // Center the grid under the camera, snapped to the grid so that the lines don't slide around,
+    // and rebuild the lines if the spacing or render origin changed
+    pub fn update(&mut self, render_state: &RenderState, spacing: f32, camera_position: Point3<f32>, render_origin: Vector3<f32>) {
+        if spacing != self.spacing || render_origin != self.render_origin {
+            (self.vertex_buffer, self.vertex_count) = build_lines(&render_state.device, spacing, render_origin);
+            self.spacing = spacing;
+            self.render_origin = render_origin;
+        }
+
+        let center = Vector3::new((camera_position.x / spacing).round() * spacing, (camera_position.y / spacing).round() * spacing, 0.0);
+        let uniform = GridUniform {
+            offset: (center - render_origin).into(),
+            fade_distance: spacing * GRID_FADE_SPACINGS,
+            camera_position: (Vector3::new(camera_position.x, camera_position.y, camera_position.z) - render_origin).into(),
+            _padding: 0.0,
+        };
+        render_state.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn draw<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+// Build the grid lines around (0, 0) and the axis lines through the world origin. The grid gets
+// moved into place in the shader, but the axes need to be relative to the render origin already.
+fn build_lines(device: &wgpu::Device, spacing: f32, render_origin: Vector3<f32>) -> (wgpu::Buffer, u32) {
+    let mut vertices = Vec::new();
+    let extent = GRID_HALF_LINES as f32 * spacing;
+    for i in -GRID_HALF_LINES..=GRID_HALF_LINES {
+        let offset = i as f32 * spacing;
+        for (start, end) in [([offset, -extent, 0.0], [offset, extent, 0.0]), ([-extent, offset, 0.0], [extent, offset, 0.0])] {
+            vertices.push(GridVertex { position: start, color: GRID_COLOR, grid_line: 1.0 });
+            vertices.push(GridVertex { position: end, color: GRID_COLOR, grid_line: 1.0 });
+        }
+    }
+
+    for (axis, color) in [(Vector3::unit_x(), X_AXIS_COLOR), (Vector3::unit_y(), Y_AXIS_COLOR), (Vector3::unit_z(), Z_AXIS_COLOR)] {
+        vertices.push(GridVertex { position: (-axis * AXIS_LENGTH - render_origin).into(), color, grid_line: 0.0 });
+        vertices.push(GridVertex { position: (axis * AXIS_LENGTH - render_origin).into(), color, grid_line: 0.0 });
+    }
+
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Grid Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    (buffer, vertices.len() as u32)
+}
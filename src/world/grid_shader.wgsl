@@ -0,0 +1,54 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+struct GridUniform {
+    offset: vec3<f32>,
+    fade_distance: f32,
+    camera_position: vec3<f32>,
+    _padding: f32,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) grid_line: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) position: vec3<f32>,
+    @location(2) grid_line: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<uniform> grid: GridUniform;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Grid lines follow the camera around, while the axes stay put at the origin
+    out.position = model.position + grid.offset * model.grid_line;
+    out.clip_position = camera.view_proj * vec4<f32>(out.position, 1.0);
+    out.color = model.color;
+    out.grid_line = model.grid_line;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Fade grid lines out with distance, before they get dense enough to cause moire
+    let distance = length(in.position - grid.camera_position);
+    let fade = 1.0 - smoothstep(grid.fade_distance * 0.25, grid.fade_distance, distance);
+    return vec4<f32>(in.color.rgb, in.color.a * mix(1.0, fade, in.grid_line));
+}
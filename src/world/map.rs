@@ -3,10 +3,11 @@ use crate::RenderState;
 use crate::Vertex;
 
 use anyhow::*;
-use cgmath::{Vector2, Vector3};
+use cgmath::{Point3, Vector2, Vector3};
 use cgmath::prelude::*;
 use regex::Regex;
 use std::collections::HashMap;
+use std::io::BufRead;
 use wgpu::util::DeviceExt;
 
 #[repr(C)]
@@ -15,10 +16,11 @@ pub struct MapVertex {
     position: [f32; 3],
     tex_coords: [f32; 2],
     color: [f32; 3],
+    normal: [f32; 3],
 }
 
 impl MapVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3];
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x3];
 }
 
 impl crate::Vertex for MapVertex {
@@ -31,18 +33,96 @@ impl crate::Vertex for MapVertex {
     }
 }
 
+fn upload_vertices(device: &wgpu::Device, vertices: &[MapVertex], origin: Vector3<f32>) -> wgpu::Buffer {
+    let relative_vertices: Vec<MapVertex> = vertices.iter().map(|v| MapVertex {
+        position: (Vector3::from(v.position) - origin).into(),
+        ..*v
+    }).collect();
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Map Vertex Buffer"),
+        contents: bytemuck::cast_slice(&relative_vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    })
+}
+
+// Everything we load from a VMF: the vertices and triangle indices of its visible faces, and the
+// entities placed in it
+pub struct MapGeometry {
+    vertices: Vec<MapVertex>,
+    indices: Vec<u32>,
+    entities: Vec<MapEntity>,
+    skybox_color: Option<[f32; 3]>, // Average color of the skybox faces, if there are any
+}
+
+// An entity with an origin, such as a spawn point or objective
+#[derive(Clone)]
+pub struct MapEntity {
+    pub classname: String,
+    pub origin: Point3<f32>,
+}
+
+impl MapEntity {
+    // Markers are colored by classname, so that entities of the same kind match
+    pub fn color(&self) -> egui::Color32 {
+        // FNV-1a, so the colors stay the same between runs
+        let hash = self.classname.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+        let hue = (hash % 360) as f32 / 360.0;
+        egui::Color32::from(egui::ecolor::Hsva::new(hue, 0.75, 1.0, 1.0))
+    }
+}
+
+// Geometry of one loaded VMF. We keep each file's geometry separate so that they can be removed
+// again after getting merged together.
+struct MapSource {
+    name: String,
+    geometry: MapGeometry,
+}
+
 pub struct Map {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     index_count: u32,
+    vertices: Vec<MapVertex>, // Kept so that we can reupload relative to a new render origin
+    render_origin: Vector3<f32>, // Subtracted from every vertex position on the GPU
+    sources: Vec<MapSource>,
+    entities: Vec<MapEntity>, // Entities of every source
+
+    indices: Vec<u32>, // Kept along with vertices so that we can raycast against the map
+    bounds: Option<(Point3<f32>, Point3<f32>)>, // Found when merging, since the render origin needs it every frame
 }
 
 impl Map {
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))] // Only the web build's host page API uses this
     pub fn from_string(vmf_string: &str, device: &wgpu::Device) -> Result<Self> {
-        let vmf = VMF::from_string(vmf_string)?;
+        let mut map = Self::empty(device);
+        map.add("VMF".to_string(), Self::geometry_from_string(vmf_string)?, device);
+        Ok(map)
+    }
 
-        // Grab all the solids to render
-        let world_solids = vmf.root.get_one("world")?.get_all("solid")?;
+    // Parse a VMF without uploading anything, so that it can be added to an existing map
+    pub fn geometry_from_string(vmf_string: &str) -> Result<MapGeometry> {
+        Self::geometry_from_vmf(VMF::from_string(vmf_string)?)
+    }
+
+    // Like geometry_from_string, but parsing the VMF as it gets read
+    pub fn geometry_from_reader<R: BufRead>(reader: R) -> Result<MapGeometry> {
+        Self::geometry_from_vmf(VMF::from_reader(reader)?)
+    }
+
+    // Parse a VMF straight from a file, so that we never need the whole file in memory at once
+    pub fn geometry_from_file(path: &std::path::Path) -> Result<MapGeometry> {
+        let file = std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Self::geometry_from_reader(std::io::BufReader::new(file))
+    }
+
+    fn geometry_from_vmf(vmf: VMF) -> Result<MapGeometry> {
+        // Grab all the solids to render. Prefabs and instances can lack a world block, in which case
+        // we only have the entity solids.
+        let worlds = vmf.root.get_all("world")?;
+        if worlds.is_empty() {
+            log::warn!("VMF has no world block, so no world geometry was loaded");
+        }
+        let world_solids = worlds.iter().map(|w| w.get_all("solid")).collect::<Result<Vec<_>>>()?.into_iter().flatten().collect::<Vec<_>>();
         let entity_solids = vmf.root.get_all("entity")?.iter().filter(|e| {
             let classname = e.get_one("classname");
             if !classname.is_ok() {
@@ -51,50 +131,87 @@ impl Map {
             classname.unwrap().to_str().unwrap_or("") == "func_detail"
         }).map (|e| e.get_all("solid")).collect::<Result<Vec<_>>>()?.into_iter().flatten().collect::<Vec<_>>();
 
-        // Convert the solids into its constituant sides, filtering out any nodraw or clip brushes
+        // Brush entities usually lack an origin, and entities with a broken one are skipped
+        let entities = vmf.root.get_all("entity")?.iter().filter_map(|e| {
+            let classname = e.get_one("classname").ok()?.to_str().ok()?.to_string();
+            let origin = e.get_one("origin").ok()?.to_vertex().ok()?;
+            Some(MapEntity { classname, origin: Point3::from_vec(origin) })
+        }).collect();
+
+        // Convert the solids into its constituant sides, filtering out any nodraw or clip brushes.
+        // Every side is still needed to find the vertices of the visible ones, so we keep the
+        // whole solid alongside each side.
         let mut sides = Vec::new();
-        for solid in world_solids {
-            let mut solid_sides = solid.get_all("side")?.iter().filter(is_side_visible).collect();
-            sides.append(&mut solid_sides);
-        };
-        for solid in entity_solids {
-            let mut solid_sides = solid.get_all("side")?.iter().filter(is_side_visible).collect();
-            sides.append(&mut solid_sides);
+        for solid in world_solids.into_iter().chain(entity_solids) {
+            let solid_sides = solid.get_all("side")?;
+            for (i, side) in solid_sides.iter().enumerate() {
+                if is_side_visible(&side) {
+                    sides.push((side, i, solid_sides));
+                }
+            }
         };
 
         // Construct our vertex and index bufferes from each side
         let mut vertices = Vec::new();
         let mut indices: Vec<u32> = Vec::new();
+        let mut skybox_color_sum = Vector3::zero();
+        let mut skybox_faces = 0;
 
-        for side in sides {
+        for (side, side_i, solid_sides) in sides {
             let initial_index = vertices.len() as u32;
 
-            // NOTE: We grab the vertices from vertices_plus, which is a Hammer++ exlcusive field.
-            // I'll figure out calculating vertices from the planes given by normal Hammer later
-            let side_vertices = side.get_one("vertices_plus")?.get_all("v")?.iter().map(|v| v.to_vertex()).collect::<Result<Vec<_>>>()?;
-            ensure!(side_vertices.len() >= 3, "VMF contains face with less than 3 vertices");
-
-            // Calculate the normal for the face
-            let cb = side_vertices[2] - side_vertices[1];
-            let ab = side_vertices[0] - side_vertices[1];
-            let normal = cb.cross(ab).normalize();
+            // Hammer++ stores the exact vertices of each face in vertices_plus, so use them when
+            // present. Otherwise, rebuild the face from the planes of the solid like Hammer does.
+            let side_vertices;
+            let normal;
+            if let Result::Ok(vertices_plus) = side.get_one("vertices_plus") {
+                side_vertices = vertices_plus.get_all("v")?.iter().map(|v| v.to_vertex()).collect::<Result<Vec<_>>>()?;
+                ensure!(side_vertices.len() >= 3, "VMF contains face with less than 3 vertices");
+                normal = face_normal(&side_vertices[0], &side_vertices[1], &side_vertices[2]);
+            }
+            else {
+                let planes = solid_sides.iter().map(|s| s.get_one("plane")?.to_plane_points()).collect::<Result<Vec<_>>>()?;
+                side_vertices = polygon_from_planes(side_i, &planes);
+                if side_vertices.len() < 3 {
+                    // The side lies outside the rest of the solid, so it has no area
+                    continue;
+                }
+                let [p1, p2, p3] = planes[side_i];
+                normal = face_normal(&p1, &p2, &p3);
+            }
 
             let material = side.get_one("material")?.to_str()?.to_uppercase();
+            let skybox = material == "TOOLS/TOOLSSKYBOX" || material == "TOOLS/TOOLSSKYBOX2D";
             let color;
-            if material == "TOOLS/TOOLSSKYBOX" || material == "TOOLS/TOOLSSKYBOX2D" {
+            if skybox {
                 color = Vector3::new(0.0, 1.0, 1.0);
+                skybox_color_sum += color;
+                skybox_faces += 1;
             }
             else {
                 // For non-skybox faces, calculate the color of the side based on the surface normal
                 color = normal / 2.0 + Vector3::new(0.5, 0.5, 0.5);
             }
 
+            // Displacements replace the face with a grid of vertices offset from it. If the
+            // displacement is broken, we still show the flat face.
+            if let Result::Ok(dispinfo) = side.get_one("dispinfo") {
+                match displacement_grid(dispinfo, &side_vertices, normal) {
+                    Result::Ok(grid) => {
+                        push_displacement(&grid, skybox, &mut vertices, &mut indices);
+                        continue;
+                    },
+                    Err(e) => log::warn!("failed to load displacement, using the flat face instead: {e}"),
+                }
+            }
+
             for vertex in &side_vertices {
                 let uv = calculate_uvs(vertex, &normal);
                 vertices.push(MapVertex {
                     position: [vertex.x, vertex.y, vertex.z],
                     tex_coords: [uv.x, uv.y],
                     color: [color.x, color.y, color.z],
+                    normal: [normal.x, normal.y, normal.z],
                 });
             }
 
@@ -107,22 +224,67 @@ impl Map {
             }
         }
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Map Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let skybox_color = (skybox_faces > 0).then(|| (skybox_color_sum / skybox_faces as f32).into());
+        Ok(MapGeometry { vertices, indices, entities, skybox_color })
+    }
+
+    // Add the geometry of another VMF to the map, merging it into the existing geometry
+    pub fn add(&mut self, name: String, geometry: MapGeometry, device: &wgpu::Device) {
+        self.sources.push(MapSource { name, geometry });
+        self.merge(device);
+    }
+
+    pub fn remove(&mut self, index: usize, device: &wgpu::Device) {
+        if index < self.sources.len() {
+            self.sources.remove(index);
+            self.merge(device);
+        }
+    }
+
+    // Entities of every loaded VMF
+    pub fn entities(&self) -> &[MapEntity] {
+        &self.entities
+    }
+
+    // Average color of the skybox faces across every loaded VMF, or None if none have a skybox
+    pub fn skybox_color(&self) -> Option<[f32; 3]> {
+        let colors: Vec<Vector3<f32>> = self.sources.iter().filter_map(|s| s.geometry.skybox_color).map(Vector3::from).collect();
+        if colors.is_empty() {
+            return None;
+        }
+        Some((colors.iter().sum::<Vector3<f32>>() / colors.len() as f32).into())
+    }
+
+    // Names of the loaded VMFs, in the order they were added
+    pub fn source_names(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|s| s.name.as_str())
+    }
+
+    // Combine the geometry of every source into one set of buffers, offsetting each source's
+    // indices past the vertices before it
+    fn merge(&mut self, device: &wgpu::Device) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        self.entities = Vec::new();
+        for source in self.sources.iter() {
+            let geometry = &source.geometry;
+            let initial_index = vertices.len() as u32;
+            vertices.extend_from_slice(&geometry.vertices);
+            indices.extend(geometry.indices.iter().map(|i| i + initial_index));
+            self.entities.extend_from_slice(&geometry.entities);
+        }
+
+        self.vertex_buffer = upload_vertices(device, &vertices, self.render_origin);
+        self.index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Map Index Buffer"),
             contents: bytemuck::cast_slice(&indices),
             usage: wgpu::BufferUsages::INDEX,
         });
+        self.index_count = indices.len() as u32;
 
-        Ok(Self {
-            vertex_buffer,
-            index_buffer,
-            index_count: indices.len() as u32,
-        })
+        self.vertices = vertices;
+        self.indices = indices;
+        self.bounds = self.triangle_bounds();
     }
 
     // Empty map that gets used at startup. Have this so we don't have to have special rendering
@@ -145,8 +307,257 @@ impl Map {
             vertex_buffer,
             index_buffer,
             index_count: 0,
+            vertices: Vec::new(),
+            render_origin: Vector3::zero(),
+            sources: Vec::new(),
+            entities: Vec::new(),
+            indices: Vec::new(),
+            bounds: None,
+        }
+    }
+
+    // Reupload our vertices relative to origin if it has changed
+    pub fn set_render_origin(&mut self, device: &wgpu::Device, origin: Vector3<f32>) {
+        if self.render_origin != origin && !self.vertices.is_empty() {
+            self.vertex_buffer = upload_vertices(device, &self.vertices, origin);
         }
+        self.render_origin = origin;
+    }
+
+    // Bounding box of the map's triangles, or None if the map is empty
+    pub fn bounds(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        self.bounds
     }
+
+    fn triangle_bounds(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        let first = self.triangles().next()?[0];
+        let mut min = first;
+        let mut max = first;
+        for vertex in self.triangles().flatten() {
+            min = Vector3::new(min.x.min(vertex.x), min.y.min(vertex.y), min.z.min(vertex.z));
+            max = Vector3::new(max.x.max(vertex.x), max.y.max(vertex.y), max.z.max(vertex.z));
+        }
+        Some((Point3::from_vec(min), Point3::from_vec(max)))
+    }
+
+    // Positions of the corners of each of the map's triangles
+    fn triangles(&self) -> impl Iterator<Item = [Vector3<f32>; 3]> + '_ {
+        self.indices.chunks_exact(3).map(|triangle| [triangle[0], triangle[1], triangle[2]].map(|i| Vector3::from(self.vertices[i as usize].position)))
+    }
+
+    // Cast a ray against the map's triangles, returning the distance along the ray to the closest
+    // hit. The direction must be normalized. We use Möller–Trumbore against every triangle, which
+    // is fast enough for one-off ray casts.
+    pub fn raycast(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let origin = origin.to_vec();
+        let mut closest: Option<f32> = None;
+        for [v0, v1, v2] in self.triangles() {
+            let edge1 = v1 - v0;
+            let edge2 = v2 - v0;
+            let p = direction.cross(edge2);
+            let determinant = edge1.dot(p);
+            if determinant.abs() < f32::EPSILON {
+                // Ray is parallel to the triangle
+                continue;
+            }
+
+            let inv_determinant = 1.0 / determinant;
+            let s = origin - v0;
+            let u = s.dot(p) * inv_determinant;
+            if !(0.0..=1.0).contains(&u) {
+                continue;
+            }
+            let q = s.cross(edge1);
+            let v = direction.dot(q) * inv_determinant;
+            if v < 0.0 || u + v > 1.0 {
+                continue;
+            }
+
+            let distance = edge2.dot(q) * inv_determinant;
+            if distance > 0.0 && closest.is_none_or(|c| distance < c) {
+                closest = Some(distance);
+            }
+        }
+
+        closest
+    }
+}
+
+// Half the size of the quad each side starts as before getting clipped by the rest of the solid.
+// Comfortably larger than the biggest map Hammer allows.
+const PLANE_QUAD_EXTENT: f64 = 131072.0;
+// How far outside a plane a point can be while still counting as on it
+const PLANE_EPSILON: f64 = 0.01;
+
+// Normal of a face from three of its points, given clockwise as seen from the front like Hammer
+// stores them
+fn face_normal(a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) -> Vector3<f32> {
+    let cb = c - b;
+    let ab = a - b;
+    cb.cross(ab).normalize()
+}
+
+// Positions of a displacement's vertices, as a square grid of rows from its start position. Each
+// vertex is the flat face position offset along its normal by its distance, plus its offset and
+// the displacement's elevation.
+struct DisplacementGrid {
+    size: usize, // Vertices along each side, 2^power + 1
+    positions: Vec<Vector3<f32>>,
+}
+
+fn displacement_grid(dispinfo: &VMFEntry, corners: &[Vector3<f32>], face_normal: Vector3<f32>) -> Result<DisplacementGrid> {
+    ensure!(corners.len() == 4, "displacement face has {} vertices instead of 4", corners.len());
+    let power: u32 = dispinfo.get_one("power")?.to_str()?.trim().parse()?;
+    ensure!((2..=4).contains(&power), "displacement power {power} is out of range");
+    let size = 2usize.pow(power) + 1;
+    let elevation: f32 = dispinfo.get_one("elevation").and_then(|e| Ok(e.to_str()?.trim().parse()?)).unwrap_or(0.0);
+
+    // The grid starts at the corner closest to startposition, then goes around the face in order
+    let start_position = dispinfo.get_one("startposition")?.to_floats()?;
+    ensure!(start_position.len() == 3, "displacement start position doesn't contain 3 entries");
+    let start_position = Vector3::new(start_position[0], start_position[1], start_position[2]);
+    let start_i = (0..4).min_by(|a, b| {
+        (corners[*a] - start_position).magnitude2().total_cmp(&(corners[*b] - start_position).magnitude2())
+    }).unwrap();
+    let [p0, p1, p2, p3] = [0, 1, 2, 3].map(|i| corners[(start_i + i) % 4]);
+
+    let normals = dispinfo.get_one("normals")?.to_rows(size, 3)?;
+    let distances = dispinfo.get_one("distances")?.to_rows(size, 1)?;
+    // Offsets are rarely used, so Hammer doesn't always write them
+    let offsets = dispinfo.get_one("offsets").ok().map(|o| o.to_rows(size, 3)).transpose()?;
+
+    // Rows go from p0 towards p1, and each row goes across towards the p3 to p2 edge
+    let mut positions = Vec::with_capacity(size * size);
+    let last = (size - 1) as f32;
+    for row in 0..size {
+        let row_start = p0.lerp(p1, row as f32 / last);
+        let row_end = p3.lerp(p2, row as f32 / last);
+        for column in 0..size {
+            let i = row * size + column;
+            let normal = Vector3::new(normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2]);
+            let mut position = row_start.lerp(row_end, column as f32 / last) + normal * distances[i] + face_normal * elevation;
+            if let Some(offsets) = &offsets {
+                position += Vector3::new(offsets[i * 3], offsets[i * 3 + 1], offsets[i * 3 + 2]);
+            }
+            positions.push(position);
+        }
+    }
+
+    Ok(DisplacementGrid { size, positions })
+}
+
+// Triangulate a displacement grid, with smooth normals so that terrain gets shaded
+fn push_displacement(grid: &DisplacementGrid, skybox: bool, vertices: &mut Vec<MapVertex>, indices: &mut Vec<u32>) {
+    let initial_index = vertices.len() as u32;
+    let size = grid.size;
+    let position = |row: usize, column: usize| grid.positions[row * size + column];
+
+    for row in 0..size {
+        for column in 0..size {
+            // Central differences, falling back to one-sided ones at the edges. Rows run the same
+            // way as the face's first edge, so this matches face_normal's orientation.
+            let row_delta = position((row + 1).min(size - 1), column) - position(row.saturating_sub(1), column);
+            let column_delta = position(row, (column + 1).min(size - 1)) - position(row, column.saturating_sub(1));
+            let normal = row_delta.cross(column_delta).normalize();
+            let color;
+            if skybox {
+                color = Vector3::new(0.0, 1.0, 1.0);
+            }
+            else {
+                color = normal / 2.0 + Vector3::new(0.5, 0.5, 0.5);
+            }
+
+            let vertex = position(row, column);
+            let uv = calculate_uvs(&vertex, &normal);
+            vertices.push(MapVertex {
+                position: [vertex.x, vertex.y, vertex.z],
+                tex_coords: [uv.x, uv.y],
+                color: [color.x, color.y, color.z],
+                normal: [normal.x, normal.y, normal.z],
+            });
+        }
+    }
+
+    for row in 0..(size - 1) {
+        for column in 0..(size - 1) {
+            // Same winding as the flat faces, which go p0, p2, p1 and p0, p3, p2
+            let a = initial_index + (row * size + column) as u32;
+            let b = a + 1;
+            let c = a + size as u32;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, d, c, a, b, d]);
+        }
+    }
+}
+
+// Recover the polygon of a side from the planes of every side in its solid. Each plane is given by
+// three points going clockwise as seen from outside the solid, and the solid is the space behind
+// all of them. We start with a huge quad on the side's plane and clip away everything in front of
+// every other plane, leaving the vertices in the same clockwise order as vertices_plus.
+fn polygon_from_planes(side_i: usize, planes: &[[Vector3<f32>; 3]]) -> Vec<Vector3<f32>> {
+    // Work in f64, since the quad starts out far larger than the map
+    let to_f64 = |v: &Vector3<f32>| v.cast::<f64>().unwrap();
+    let outward_planes: Vec<(Vector3<f64>, f64)> = planes.iter().map(|[p1, p2, p3]| {
+        let (p1, p2, p3) = (to_f64(p1), to_f64(p2), to_f64(p3));
+        let normal = (p1 - p2).cross(p3 - p2).normalize();
+        (normal, normal.dot(p1))
+    }).collect();
+
+    // Build the quad from two axes along the plane, with u x v pointing out of the solid
+    let (normal, distance) = outward_planes[side_i];
+    if !normal.x.is_finite() {
+        // Degenerate plane with collinear points
+        return Vec::new();
+    }
+    let helper_axis;
+    if normal.z.abs() < 0.9 {
+        helper_axis = Vector3::unit_z();
+    }
+    else {
+        helper_axis = Vector3::unit_x();
+    }
+    let u = helper_axis.cross(normal).normalize() * PLANE_QUAD_EXTENT;
+    let v = normal.cross(u);
+    let center = normal * distance;
+    let mut polygon = vec![center - u + v, center + u + v, center + u - v, center - u - v];
+
+    // Sutherland-Hodgman clipping against every other plane, which keeps the vertex order
+    for (i, (clip_normal, clip_distance)) in outward_planes.iter().enumerate() {
+        if i == side_i || !clip_normal.x.is_finite() {
+            continue;
+        }
+
+        let mut clipped = Vec::new();
+        for (j, current) in polygon.iter().enumerate() {
+            let next = polygon[(j + 1) % polygon.len()];
+            let current_distance = clip_normal.dot(*current) - clip_distance;
+            let next_distance = clip_normal.dot(next) - clip_distance;
+            if current_distance <= PLANE_EPSILON {
+                clipped.push(*current);
+            }
+            if (current_distance < -PLANE_EPSILON && next_distance > PLANE_EPSILON) || (current_distance > PLANE_EPSILON && next_distance < -PLANE_EPSILON) {
+                let t = current_distance / (current_distance - next_distance);
+                clipped.push(current + (next - current) * t);
+            }
+        }
+        polygon = clipped;
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    // Clipping through an existing vertex can leave duplicates behind
+    let mut vertices: Vec<Vector3<f32>> = Vec::new();
+    for vertex in polygon {
+        let vertex = vertex.cast::<f32>().unwrap();
+        if vertices.last().is_none_or(|last| (vertex - last).magnitude() > PLANE_EPSILON as f32) {
+            vertices.push(vertex);
+        }
+    }
+    while vertices.len() > 1 && (vertices[0] - vertices[vertices.len() - 1]).magnitude() <= PLANE_EPSILON as f32 {
+        vertices.pop();
+    }
+    vertices
 }
 
 const TEXTURE_SCALE: f32 = 256.0;
@@ -190,13 +601,66 @@ fn is_side_visible(side: &&VMFEntry) -> bool {
 
 // Struct that handles the rendering of map instances. Separate from Map so that we can freely swap
 // out our Map instance without rebuilding / migrating our rendering state
+// How the map gets shaded. The values match the mode in shader.wgsl
+#[derive(Clone, Copy, PartialEq)]
+pub enum MapViewMode {
+    NormalColor, // Colored by face normal, which helps with orienting
+    FlatLit, // Plain grey with simple directional lighting, for checking geometry
+    Textured, // Wall texture with simple directional lighting
+}
+
+impl std::fmt::Display for MapViewMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            MapViewMode::NormalColor => write!(f, "Normal Color"),
+            MapViewMode::FlatLit => write!(f, "Flat Grey Lit"),
+            MapViewMode::Textured => write!(f, "Textured"),
+        }
+    }
+}
+
+// Which of the map's faces get drawn and how, so splines can be seen through walls
+#[derive(Clone, Copy, PartialEq)]
+pub enum MapRenderMode {
+    Solid,
+    NoCull, // Also draws back faces
+    Wireframe,
+}
+
+impl MapRenderMode {
+    pub const ALL: [MapRenderMode; 3] = [MapRenderMode::Solid, MapRenderMode::NoCull, MapRenderMode::Wireframe];
+}
+
+impl std::fmt::Display for MapRenderMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            MapRenderMode::Solid => write!(f, "Solid"),
+            MapRenderMode::NoCull => write!(f, "Solid, No Culling"),
+            MapRenderMode::Wireframe => write!(f, "Wireframe"),
+        }
+    }
+}
+
+// Padded out to 16 bytes, since WebGL requires uniform buffers to be at least that large
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MapViewUniform {
+    mode: u32,
+    _padding: [u32; 3],
+}
+
 pub struct MapRenderer {
     wall_texture_bind_group: wgpu::BindGroup,
+    view_mode_buffer: wgpu::Buffer,
+    view_mode_bind_group: wgpu::BindGroup,
     render_pipeline: wgpu::RenderPipeline,
+    no_cull_render_pipeline: wgpu::RenderPipeline,
+    wireframe_render_pipeline: Option<wgpu::RenderPipeline>, // None if the device can't draw lines
+    render_mode: MapRenderMode,
 }
 
 impl MapRenderer {
-    pub fn new(render_state: &RenderState, camera_layout: &wgpu::BindGroupLayout) -> Self {
+    pub fn new(render_state: &RenderState, camera_layout: &wgpu::BindGroupLayout, sample_count: u32) -> Self {
         let wall_texture_bytes = include_bytes!("wall_texture.png");
         let wall_texture = texture::Texture::from_bytes(&render_state.device, &render_state.queue, wall_texture_bytes, "wall_texture").unwrap();
 
@@ -236,6 +700,37 @@ impl MapRenderer {
             label: Some("wall_texture_bind_group"),
         });
 
+        let view_mode_buffer = render_state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Map View Mode Buffer"),
+            contents: bytemuck::cast_slice(&[MapViewUniform { mode: MapViewMode::NormalColor as u32, _padding: [0; 3] }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let view_mode_bind_group_layout = render_state.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("view_mode_bind_group_layout"),
+        });
+        let view_mode_bind_group = render_state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &view_mode_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: view_mode_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("view_mode_bind_group"),
+        });
+
         let shader = render_state.device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
 
         let render_pipeline_layout = render_state.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -243,64 +738,68 @@ impl MapRenderer {
             bind_group_layouts: &[
                 &texture_bind_group_layout,
                 camera_layout,
+                &view_mode_bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = render_state.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    MapVertex::desc(),
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: render_state.config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
+        let device = &render_state.device;
+        let format = render_state.config.format;
+        let render_pipeline = create_map_pipeline(device, format, sample_count, &render_pipeline_layout, &shader, &MapPipelineStyle {
+            label: "Render Pipeline",
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+        });
+        // Without culling, the backs of faces show through from inside solids and behind walls
+        let no_cull_render_pipeline = create_map_pipeline(device, format, sample_count, &render_pipeline_layout, &shader, &MapPipelineStyle {
+            label: "No Cull Render Pipeline",
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+        });
+        // Line polygon mode is a native only feature, so WebGL doesn't get a wireframe
+        let wireframe_render_pipeline = device.features().contains(wgpu::Features::POLYGON_MODE_LINE).then(|| {
+            create_map_pipeline(device, format, sample_count, &render_pipeline_layout, &shader, &MapPipelineStyle {
+                label: "Wireframe Render Pipeline",
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Line,
+            })
         });
 
         MapRenderer {
             wall_texture_bind_group,
+            view_mode_buffer,
+            view_mode_bind_group,
             render_pipeline,
+            no_cull_render_pipeline,
+            wireframe_render_pipeline,
+            render_mode: MapRenderMode::Solid,
         }
     }
 
+    pub fn supports_wireframe(&self) -> bool {
+        self.wireframe_render_pipeline.is_some()
+    }
+
+    // Wireframe falls back to solid if the device can't draw it
+    pub fn set_render_mode(&mut self, mode: MapRenderMode) {
+        self.render_mode = mode;
+    }
+
+    pub fn set_view_mode(&self, queue: &wgpu::Queue, mode: MapViewMode) {
+        queue.write_buffer(&self.view_mode_buffer, 0, bytemuck::cast_slice(&[MapViewUniform { mode: mode as u32, _padding: [0; 3] }]));
+    }
+
     pub fn draw<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup, map: &'s Map) {
-        render_pass.set_pipeline(&self.render_pipeline);
+        let pipeline = match (self.render_mode, &self.wireframe_render_pipeline) {
+            (MapRenderMode::NoCull, _) => &self.no_cull_render_pipeline,
+            (MapRenderMode::Wireframe, Some(wireframe_pipeline)) => wireframe_pipeline,
+            _ => &self.render_pipeline,
+        };
+        render_pass.set_pipeline(pipeline);
 
         render_pass.set_bind_group(0, &self.wall_texture_bind_group, &[]);
         render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.view_mode_bind_group, &[]);
 
         render_pass.set_vertex_buffer(0, map.vertex_buffer.slice(..));
         render_pass.set_index_buffer(map.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
@@ -309,9 +808,62 @@ impl MapRenderer {
     }
 }
 
+// The map pipelines only differ in which faces get culled and how they get filled
+struct MapPipelineStyle {
+    label: &'static str,
+    cull_mode: Option<wgpu::Face>,
+    polygon_mode: wgpu::PolygonMode,
+}
+
+fn create_map_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, style: &MapPipelineStyle) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(style.label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                MapVertex::desc(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: style.cull_mode,
+            polygon_mode: style.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
 // VMF types to parse the VMF file into a traversible structure
 type VMFBranch = HashMap<String, Vec<VMFEntry>>;
 
+#[cfg_attr(test, derive(Debug, PartialEq))]
 enum VMFEntry {
     Branch(VMFBranch),
     Leaf(String),
@@ -336,6 +888,39 @@ impl VMFEntry {
         }
     }
 
+    // Parse a leaf of whitespace separated numbers, ignoring any brackets like startposition has
+    fn to_floats(&self) -> Result<Vec<f32>> {
+        let value = self.to_str()?;
+        Ok(value.replace(['[', ']'], " ").split_whitespace().map(|c| c.parse()).collect::<Result<Vec<f32>, _>>()?)
+    }
+
+    // Read the row0, row1, ... leaves of a displacement branch into one row-major list, checking
+    // that every row has size entries of the given number of components
+    fn to_rows(&self, size: usize, components: usize) -> Result<Vec<f32>> {
+        let mut values = Vec::with_capacity(size * size * components);
+        for row in 0..size {
+            let row_values = self.get_one(&format!("row{row}"))?.to_floats()?;
+            ensure!(row_values.len() == size * components, "displacement row{row} has {} values instead of {}", row_values.len(), size * components);
+            values.extend(row_values);
+        }
+        Ok(values)
+    }
+
+    // Parse a side's plane, which is stored as three points in the form "(x y z) (x y z) (x y z)"
+    fn to_plane_points(&self) -> Result<[Vector3<f32>; 3]> {
+        let value = self.to_str()?;
+        let coords = value.replace(['(', ')'], " ").split_whitespace().map(|c| c.parse()).collect::<Result<Vec<f32>, _>>()?;
+        if coords.len() != 9 {
+            bail!("VMF plane doesn't contain 3 points");
+        }
+
+        Ok([
+            Vector3::new(coords[0], coords[1], coords[2]),
+            Vector3::new(coords[3], coords[4], coords[5]),
+            Vector3::new(coords[6], coords[7], coords[8]),
+        ])
+    }
+
     fn to_str(&self) -> Result<&str> {
         if let VMFEntry::Leaf(value) = self {
             return Ok(value);
@@ -377,6 +962,7 @@ impl VMFEntry {
     }
 }
 
+#[cfg_attr(test, derive(Debug, PartialEq))]
 struct VMF {
     root: VMFEntry,
 }
@@ -384,13 +970,19 @@ struct VMF {
 impl VMF {
     // Parse a VMF file into a VMF struct
     fn from_string(vmf_string: &str) -> Result<Self> {
+        Self::from_reader(vmf_string.as_bytes())
+    }
+
+    // Parse a VMF file from a stream, so that we never need the whole file in memory at once
+    fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
         let mut current_branch = VMFBranch::new();
         let mut tree_stack = Vec::<(VMFBranch, String)>::new(); // Holds parents of current branch all the way up the VMF tree
         let leaf_regex = Regex::new("^\"(.*)\" \"(.*)\"$").unwrap();
 
         // We construct our VMF line by line
-        let mut vmf_lines = vmf_string.lines();
+        let mut vmf_lines = reader.lines();
         while let Some(line) = vmf_lines.next() {
+            let line = line?;
             let line = line.trim();
             // Case 1: Line closes the current branch, so traverse back up the tree and add our
             // finalized branch to its parent.
@@ -429,8 +1021,8 @@ impl VMF {
             else if line != "" {
                 // The opening brace lies on the next line, so grab it to check if we actually
                 // satisfy the new branch syntax. This is the last case, so OK to error
-                let next_line = vmf_lines.next().unwrap_or("").trim();
-                if next_line != "{" {
+                let next_line = vmf_lines.next().transpose()?.unwrap_or_default();
+                if next_line.trim() != "{" {
                     bail!("malformed VMF syntax");
                 }
 
@@ -449,3 +1041,85 @@ impl VMF {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A prefab with a func_detail cube and a spawn point, but no world block
+    const ENTITIES_ONLY_VMF: &str = r#"
+versioninfo
+{
+	"editorversion" "400"
+}
+entity
+{
+	"id" "2"
+	"classname" "func_detail"
+	solid
+	{
+		"id" "3"
+		side
+		{
+			"id" "1"
+			"plane" "(-64 64 64) (64 64 64) (64 -64 64)"
+			"material" "DEV/DEV_MEASUREGENERIC01B"
+		}
+		side
+		{
+			"id" "2"
+			"plane" "(-64 -64 -64) (64 -64 -64) (64 64 -64)"
+			"material" "DEV/DEV_MEASUREGENERIC01B"
+		}
+		side
+		{
+			"id" "3"
+			"plane" "(-64 64 64) (-64 -64 64) (-64 -64 -64)"
+			"material" "DEV/DEV_MEASUREGENERIC01B"
+		}
+		side
+		{
+			"id" "4"
+			"plane" "(64 64 -64) (64 -64 -64) (64 -64 64)"
+			"material" "DEV/DEV_MEASUREGENERIC01B"
+		}
+		side
+		{
+			"id" "5"
+			"plane" "(64 64 64) (-64 64 64) (-64 64 -64)"
+			"material" "DEV/DEV_MEASUREGENERIC01B"
+		}
+		side
+		{
+			"id" "6"
+			"plane" "(64 -64 -64) (-64 -64 -64) (-64 -64 64)"
+			"material" "DEV/DEV_MEASUREGENERIC01B"
+		}
+	}
+}
+entity
+{
+	"id" "4"
+	"classname" "info_player_teamspawn"
+	"origin" "0 128 0"
+}
+"#;
+
+    #[test]
+    fn vmf_without_world_loads_entities() {
+        let geometry = Map::geometry_from_string(ENTITIES_ONLY_VMF).unwrap();
+        // Each face of the cube is a quad
+        assert_eq!(geometry.vertices.len(), 6 * 4);
+        assert_eq!(geometry.indices.len(), 6 * 2 * 3);
+        assert_eq!(geometry.entities.len(), 1);
+        assert_eq!(geometry.entities[0].classname, "info_player_teamspawn");
+        assert_eq!(geometry.entities[0].origin, Point3::new(0.0, 128.0, 0.0));
+    }
+
+    #[test]
+    fn reading_a_vmf_matches_parsing_a_string() {
+        // A tiny buffer splits lines across reads
+        let reader = std::io::BufReader::with_capacity(7, ENTITIES_ONLY_VMF.as_bytes());
+        assert_eq!(VMF::from_reader(reader).unwrap(), VMF::from_string(ENTITIES_ONLY_VMF).unwrap());
+    }
+}
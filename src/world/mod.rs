@@ -1,48 +1,301 @@
-mod camera;
+pub mod camera;
 pub mod spline;
 pub mod map;
+pub mod grid;
 
+use crate::keybinds::{Action, Keybinds};
 use crate::texture;
 use crate::RenderState;
 
+use anyhow::*;
+use cgmath::{EuclideanSpace, InnerSpace, MetricSpace, Point3, Rad, Vector3, Zero};
+use serde::{Serialize, Deserialize};
 use std::cell::{RefCell, Ref};
+use std::collections::BTreeSet;
 use web_time::Duration;
 use winit::event::*;
+use winit::keyboard::{Key, ModifiersState, NamedKey};
 use wgpu::util::DeviceExt;
 
+// Range of grid sizes the grid hotkeys step through, matching Hammer's grid
+const MIN_GRID_SIZE: f32 = 1.0;
+const MAX_GRID_SIZE: f32 = 512.0;
+
+// Size in pixels of the longer side of overview images
+#[cfg(not(target_arch = "wasm32"))]
+const OVERVIEW_IMAGE_SIZE: u32 = 2048;
+// Empty space left around the scene in overview images, as a fraction of the scene's size
+#[cfg(not(target_arch = "wasm32"))]
+const OVERVIEW_MARGIN: f32 = 0.05;
+
+// Size of entity markers relative to their distance from the camera (or the view height of
+// orthographic views), which keeps them the same size on screen
+const ENTITY_MARKER_SIZE_PER_DISTANCE: f32 = 0.015;
+
+// Samples per pixel when MSAA is enabled
+pub const MSAA_SAMPLES: u32 = 4;
+
+// Default viewport background, a dark blue in linear RGB
+pub const DEFAULT_CLEAR_COLOR: [f32; 3] = [0.1, 0.2, 0.3];
+
+// Binary saves start with one of these, which can never start a JSON save. Saves from before the
+// format was versioned use the first, and only hold the splines.
+const LEGACY_BINARY_SAVE_MAGIC: &[u8] = b"SPLB";
+const BINARY_SAVE_MAGIC: &[u8] = b"SPLV";
+
+// Bump whenever the save format changes in a way that needs detecting when loading. Binary saves
+// can't skip over missing fields, so every bump needs a struct to read the older binary saves with.
+const SAVE_VERSION: u32 = 3;
+
+// Everything that goes in a save file. Fields added after the first version need a serde default,
+// so that older JSON saves still load.
+#[derive(Serialize, Deserialize)]
+pub struct SaveState {
+    pub version: u32,
+    pub splines: Vec<spline::SplineData>,
+    #[serde(default)]
+    pub camera: Option<SavedCamera>,
+    #[serde(default)]
+    pub selected_spline: u32,
+    #[serde(default)]
+    pub selected_point: u32, // Within the selected spline
+    #[serde(default)]
+    pub clear_color: Option<[f32; 3]>, // Added in version 2
+    #[serde(default)]
+    pub skybox_background: Option<bool>, // Added in version 2
+}
+
+// Binary saves from before the background was saved
+#[derive(Deserialize)]
+struct SaveStateV1 {
+    version: u32,
+    splines: Vec<spline::SplineDataV2>,
+    camera: Option<SavedCamera>,
+    selected_spline: u32,
+    selected_point: u32,
+}
+
+impl From<SaveStateV1> for SaveState {
+    fn from(state: SaveStateV1) -> Self {
+        Self {
+            version: state.version,
+            splines: state.splines.into_iter().map(spline::SplineData::from).collect(),
+            camera: state.camera,
+            selected_spline: state.selected_spline,
+            selected_point: state.selected_point,
+            clear_color: None,
+            skybox_background: None,
+        }
+    }
+}
+
+// Binary saves from before points had their own subdivisions
+#[derive(Deserialize)]
+struct SaveStateV2 {
+    version: u32,
+    splines: Vec<spline::SplineDataV2>,
+    camera: Option<SavedCamera>,
+    selected_spline: u32,
+    selected_point: u32,
+    clear_color: Option<[f32; 3]>,
+    skybox_background: Option<bool>,
+}
+
+impl From<SaveStateV2> for SaveState {
+    fn from(state: SaveStateV2) -> Self {
+        Self {
+            version: state.version,
+            splines: state.splines.into_iter().map(spline::SplineData::from).collect(),
+            camera: state.camera,
+            selected_spline: state.selected_spline,
+            selected_point: state.selected_point,
+            clear_color: state.clear_color,
+            skybox_background: state.skybox_background,
+        }
+    }
+}
+
+impl SaveState {
+    // A save with just splines in it, like the saves from before versioning
+    fn from_splines(splines: Vec<spline::SplineData>) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            splines,
+            camera: None,
+            selected_spline: 0,
+            selected_point: 0,
+            clear_color: None,
+            skybox_background: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SavedCamera {
+    pub position: Point3<f32>,
+    pub pitch: Rad<f32>,
+    pub yaw: Rad<f32>,
+}
+
+// Serialize a save as either JSON, which can be edited by hand, or a compact binary format that is
+// much faster to load and save for big projects
+pub fn encode_state(state: &SaveState, binary: bool) -> Vec<u8> {
+    if binary {
+        let mut save = BINARY_SAVE_MAGIC.to_vec();
+        save.extend(bincode::serialize(state).unwrap());
+        save
+    }
+    else {
+        serde_json::to_vec(state).unwrap()
+    }
+}
+
+// Deserialize a save from encode_state(), detecting which format it is in. Older saves that are
+// just a list of splines are accepted, as are exported SMD and QC files, giving back the spline
+// they were exported from.
+pub(crate) fn decode_state(save: &[u8]) -> Result<SaveState> {
+    let state: SaveState;
+    if let Some(binary_save) = save.strip_prefix(BINARY_SAVE_MAGIC) {
+        // The version comes first, telling us which fields follow
+        let version = binary_save.get(..4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        if let Some(version) = version.filter(|&version| version > SAVE_VERSION) {
+            // Unlike JSON, we can't skip over fields we don't know, so the rest would be garbage
+            bail!("save is from a newer version (format {version} > {SAVE_VERSION}) and can't be loaded");
+        }
+        else if version == Some(1) {
+            state = bincode::deserialize::<SaveStateV1>(binary_save)?.into();
+        }
+        else if version == Some(2) {
+            state = bincode::deserialize::<SaveStateV2>(binary_save)?.into();
+        }
+        else {
+            state = bincode::deserialize(binary_save)?;
+        }
+    }
+    else if let Some(binary_save) = save.strip_prefix(LEGACY_BINARY_SAVE_MAGIC) {
+        let splines: Vec<spline::SplineDataV2> = bincode::deserialize(binary_save)?;
+        return Ok(SaveState::from_splines(splines.into_iter().map(spline::SplineData::from).collect()));
+    }
+    else if spline::import::is_model(save) {
+        return Ok(SaveState::from_splines(spline::import::splines_from_model(save)?));
+    }
+    else if save.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'[') {
+        return Ok(SaveState::from_splines(serde_json::from_slice(save)?));
+    }
+    else {
+        state = serde_json::from_slice(save)?;
+    }
+
+    // Only JSON saves can get here from a newer version, and they just lose the fields we don't know
+    if state.version > SAVE_VERSION {
+        log::warn!("save is from a newer version (format {} > {SAVE_VERSION}), so some of it may not load", state.version);
+    }
+    Ok(state)
+}
+
+// Textures a frame of the scene gets drawn into. With MSAA, the scene is drawn into msaa_view and
+// then resolved into view.
+#[derive(Clone, Copy)]
+struct SceneTargets<'a> {
+    view: &'a wgpu::TextureView,
+    msaa_view: Option<&'a wgpu::TextureView>,
+    depth_view: &'a wgpu::TextureView,
+}
+
 // We make some fields pub so that the GUI can inspect/modify them
 pub struct World {
     depth_texture: texture::Texture,
-    camera: camera::Camera,
+    msaa_target: Option<wgpu::TextureView>, // Multisampled color target, if MSAA is on
+    sample_count: u32, // Samples per pixel our pipelines and targets are currently built for
+    pub camera: camera::Camera,
     camera_uniform: camera::CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
-    camera_controller: camera::CameraController,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    pub camera_controller: camera::CameraController,
     map_renderer: map::MapRenderer,
+    grid_renderer: grid::GridRenderer,
     pub map: map::Map,
+    entity_marker_buffer: wgpu::Buffer,
+    entity_marker_count: u32,
     spline_renderer: spline::SplineRenderer,
     pub splines: Vec<RefCell<spline::Spline>>,
 
     new_spline_requested: bool,
     pub selected_spline: u32,
+    pub snap_value: f32, // Step used when snapping positions and adjusting tangents
+    pub snap_placement: bool, // Whether new points snap to the grid instead of whole units
+    pub place_on_surface: bool, // Whether new points go on the map surface the camera looks at instead of at the camera
+
+    // Cursor state for picking control points
+    picking_enabled: bool,
+    cursor_position: Option<(f32, f32)>,
+    cursor_moved: bool,
+    modifiers: ModifiersState,
+
+    // Measurement tool state. Clicking places the start and then the end of the measurement.
+    pub measuring: bool,
+    pub measure_start: Option<Point3<f32>>,
+    pub measure_end: Option<Point3<f32>>,
+    measure_click_requested: bool,
+
+    // Camera playback along the selected spline. Holds the seconds since playback started while
+    // playing.
+    playback_elapsed: Option<f32>,
+
+    // Preferences
+    pub lod_enabled: bool,
+    pub lod_distance: f32, // Splines further than this from the camera get drawn with a coarser mesh
+    pub show_control_polygon: bool, // Whether to draw straight lines between each spline's control points
+    pub show_point_gizmos: bool, // Whether to draw a cube at each control point of the selected spline
+    pub xray_selected: bool, // Whether to faintly draw the selected spline through whatever is in front of it
+    pub focus_distance: f32, // How far back along the tangent the camera ends up when focusing on a point
+    pub show_grid: bool, // Whether to draw the ground grid and world axes
+    pub grid_spacing: f32, // Distance between ground grid lines
+    pub rebase_origin: bool, // Whether to render relative to the map's center for better precision far from the world origin
+    pub clear_color: [f32; 3], // Viewport background in linear RGB
+    pub skybox_background: bool, // Whether to use the map's skybox color as the background instead, if it has a skybox
+    pub keybinds: Keybinds,
+    pub present_mode: wgpu::PresentMode,
+    pub msaa_enabled: bool, // Only takes effect if the adapter supports MSAA
+    pub frame_cap_enabled: bool,
+    pub frame_cap: f32, // Maximum frames per second
+    pub map_view_mode: map::MapViewMode,
+    pub map_render_mode: map::MapRenderMode,
+    pub autosave_enabled: bool,
+    pub autosave_interval: f32, // In seconds
+    pub playback_duration: f32, // Seconds for camera playback to travel the whole spline
+    pub show_entities: bool, // Whether to draw a marker at each of the map's entities
+    pub show_entity_labels: bool, // Whether to label each entity marker with its classname
+    pub hidden_entity_classes: BTreeSet<String>, // Classnames whose markers aren't drawn
 }
 
 impl World {
     pub fn new(render_state: &RenderState) -> Self {
-        let depth_texture = texture::Texture::create_depth_texture(&render_state.device, &render_state.config, "depth_texture");
+        let sample_count;
+        if render_state.msaa_supported {
+            sample_count = MSAA_SAMPLES;
+        }
+        else {
+            sample_count = 1;
+        }
+        let depth_texture = texture::Texture::create_depth_texture(&render_state.device, render_state.config.width, render_state.config.height, sample_count, "depth_texture");
+        let msaa_target = create_msaa_target(render_state, sample_count);
 
         let camera = camera::Camera {
             position: (0.0, 0.0, 0.0).into(),
             pitch: cgmath::Rad(0.0),
             yaw: cgmath::Rad(0.0),
+            roll: cgmath::Rad(0.0),
             aspect: render_state.config.width as f32 / render_state.config.height as f32,
             fovy: 60.0,
             znear: 1.0,
             zfar: 10000.0,
+            projection: camera::Projection::Perspective,
         };
 
         let mut camera_uniform = camera::CameraUniform::new();
-        camera_uniform.update_view_proj(&camera);
+        camera_uniform.update_view_proj(&camera, Vector3::zero());
 
         let camera_buffer = render_state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -50,21 +303,7 @@ impl World {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let camera_bind_group_layout = render_state.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            ],
-            label: Some("camera_bind_group_layout"),
-        });
+        let camera_bind_group_layout = camera::create_bind_group_layout(&render_state.device);
 
         let camera_bind_group = render_state.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
@@ -77,43 +316,222 @@ impl World {
             label: Some("camera_bind_group"),
         });
 
-        let camera_controller = camera::CameraController::new(500.0, 4.0, std::f32::consts::PI / 1000.0);
+        let camera_controller = camera::CameraController::new(camera::CameraSettings::load());
 
-        let map_renderer = map::MapRenderer::new(render_state, &camera_bind_group_layout);
+        let map_renderer = map::MapRenderer::new(render_state, &camera_bind_group_layout, sample_count);
         let map = map::Map::empty(&render_state.device);
-        let spline_renderer = spline::SplineRenderer::new(render_state, &camera_bind_group_layout);
+        let entity_marker_buffer = create_entity_marker_buffer(&render_state.device, 0);
+        let grid_renderer = grid::GridRenderer::new(render_state, &camera_bind_group_layout, sample_count);
+        let spline_renderer = spline::SplineRenderer::new(&render_state.device, render_state.config.format, &camera_bind_group_layout, sample_count);
 
         Self {
             depth_texture,
+            msaa_target,
+            sample_count,
             camera,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
+            camera_bind_group_layout,
             camera_controller,
             map_renderer,
+            grid_renderer,
             map,
+            entity_marker_buffer,
+            entity_marker_count: 0,
             spline_renderer,
             splines: Vec::new(),
 
             new_spline_requested: false,
             selected_spline: 0,
+            snap_value: 64.0,
+            snap_placement: false,
+            place_on_surface: false,
+
+            picking_enabled: true,
+            cursor_position: None,
+            cursor_moved: false,
+            modifiers: ModifiersState::empty(),
+
+            measuring: false,
+            measure_start: None,
+            measure_end: None,
+            measure_click_requested: false,
+
+            playback_elapsed: None,
+
+            lod_enabled: true,
+            lod_distance: 4096.0,
+            show_control_polygon: false,
+            show_point_gizmos: true,
+            xray_selected: false,
+            focus_distance: 256.0,
+            show_grid: true,
+            grid_spacing: 128.0,
+            rebase_origin: false,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            skybox_background: false,
+            keybinds: Keybinds::load(),
+            present_mode: render_state.config.present_mode,
+            msaa_enabled: true,
+            frame_cap_enabled: false,
+            frame_cap: 60.0,
+            map_view_mode: map::MapViewMode::NormalColor,
+            map_render_mode: map::MapRenderMode::Solid,
+            autosave_enabled: true,
+            autosave_interval: 60.0,
+            playback_duration: 10.0,
+            show_entities: true,
+            show_entity_labels: false,
+            hidden_entity_classes: BTreeSet::new(),
         }
     }
 
     pub fn resize(&mut self, render_state: &RenderState) {
         self.camera.aspect = render_state.size.width as f32 / render_state.size.height as f32;
-        self.depth_texture = texture::Texture::create_depth_texture(&render_state.device, &render_state.config, "depth_texture");
+        self.depth_texture = texture::Texture::create_depth_texture(&render_state.device, render_state.config.width, render_state.config.height, self.sample_count, "depth_texture");
+        self.msaa_target = create_msaa_target(render_state, self.sample_count);
+    }
+
+    // Rebuild the pipelines and render targets if MSAA got toggled
+    fn update_sample_count(&mut self, render_state: &RenderState) {
+        let sample_count;
+        if self.msaa_enabled && render_state.msaa_supported {
+            sample_count = MSAA_SAMPLES;
+        }
+        else {
+            sample_count = 1;
+        }
+        if sample_count == self.sample_count {
+            return;
+        }
+
+        self.sample_count = sample_count;
+        self.map_renderer = map::MapRenderer::new(render_state, &self.camera_bind_group_layout, sample_count);
+        self.grid_renderer = grid::GridRenderer::new(render_state, &self.camera_bind_group_layout, sample_count);
+        self.spline_renderer.set_sample_count(&render_state.device, sample_count);
+        self.resize(render_state);
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
+        // Scrolling zooms orthographic views. Otherwise the camera controller uses it for speed.
+        if let WindowEvent::MouseWheel { delta, .. } = event {
+            if matches!(self.camera.projection, camera::Projection::Orthographic { .. }) {
+                self.camera.zoom(camera::scroll_steps(delta));
+                return true;
+            }
+        }
+
+        // Moving the camera by hand takes it back from playback
+        if let WindowEvent::KeyboardInput { event: KeyEvent { state: ElementState::Pressed, physical_key, .. }, .. } = event {
+            if matches!(self.keybinds.action(physical_key), Some(Action::MoveForward | Action::MoveLeft | Action::MoveBackward | Action::MoveRight)) {
+                self.stop_playback();
+            }
+        }
+
         // Camera controller events
-        if self.camera_controller.process_events(event) {
+        if self.camera_controller.process_events(event, &self.keybinds) {
             return true;
         }
 
+        // Picking events
+        match event {
+            WindowEvent::CursorMoved { position, .. } if self.picking_enabled => {
+                self.cursor_position = Some((position.x as f32, position.y as f32));
+                self.cursor_moved = true;
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.clear_cursor();
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.picking_enabled && self.measuring => {
+                // We need the window size to cast our ray, so place the point in update()
+                self.measure_click_requested = true;
+                return true;
+            },
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    state: ElementState::Pressed,
+                    logical_key: Key::Named(NamedKey::Escape),
+                    ..
+                },
+                ..
+            } if self.is_playing() => {
+                self.stop_playback();
+                return true;
+            },
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    state: ElementState::Pressed,
+                    logical_key: Key::Named(NamedKey::Escape),
+                    ..
+                },
+                ..
+            } if self.measure_start.is_some() => {
+                self.measure_start = None;
+                self.measure_end = None;
+                return true;
+            },
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    state: ElementState::Pressed,
+                    physical_key,
+                    ..
+                },
+                ..
+            } => {
+                match self.keybinds.action(physical_key) {
+                    Some(Action::GridSmaller) => {
+                        self.step_grid_size(false);
+                        return true;
+                    },
+                    Some(Action::GridLarger) => {
+                        self.step_grid_size(true);
+                        return true;
+                    },
+                    Some(Action::FocusPoint) => {
+                        if self.focus_selected_point() {
+                            return true;
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.picking_enabled => {
+                // Select the point under the cursor. Holding shift adds to or removes from the
+                // selection instead.
+                if let Some(spline) = self.splines.get(self.selected_spline as usize) {
+                    let mut spline = spline.borrow_mut();
+                    if let Some(hovered_point) = spline.hovered_point {
+                        if self.modifiers.shift_key() {
+                            if hovered_point != spline.selected_point && !spline.multi_selection.remove(&hovered_point) {
+                                spline.multi_selection.insert(hovered_point);
+                            }
+                        }
+                        else {
+                            spline.selected_point = hovered_point;
+                            spline.multi_selection.clear();
+                        }
+                        return true;
+                    }
+                }
+            },
+            _ => {}
+        }
+
         // Spline control events
         if self.splines.len() > 0 {
-            if self.splines[self.selected_spline as usize].borrow_mut().process_events(event, &self.camera) {
+            if self.splines[self.selected_spline as usize].borrow_mut().process_events(event, &self.camera, self.snap_value, || self.placement_position(), &self.keybinds, self.modifiers.shift_key()) {
                 return true;
             }
         }
@@ -121,19 +539,120 @@ impl World {
         return false;
     }
 
+    // Picking is disabled while the camera is locked, since the cursor is hidden
+    pub fn set_picking_enabled(&mut self, enabled: bool) {
+        self.picking_enabled = enabled;
+        if !enabled {
+            self.clear_cursor();
+        }
+    }
+
+    // Called when the cursor is no longer over the viewport (such as when it is over the GUI)
+    pub fn clear_cursor(&mut self) {
+        if self.cursor_position.is_some() {
+            self.cursor_position = None;
+            self.cursor_moved = true;
+        }
+    }
+
+    // Project a world position to window pixel coordinates using the current camera
+    pub fn project_to_screen(&self, position: cgmath::Point3<f32>, render_state: &RenderState) -> Option<(f32, f32)> {
+        let window_size = (render_state.size.width as f32, render_state.size.height as f32);
+        self.camera.project_to_screen(position, window_size)
+    }
+
+    fn update_measurement(&mut self, render_state: &RenderState) {
+        if !self.measure_click_requested {
+            return;
+        }
+        self.measure_click_requested = false;
+
+        let Some(cursor) = self.cursor_position else {
+            return;
+        };
+        let window_size = (render_state.size.width as f32, render_state.size.height as f32);
+        let Some((origin, direction)) = self.camera.cursor_ray(cursor, window_size) else {
+            return;
+        };
+
+        // Measure against the map, falling back to the Z = 0 plane if we don't hit anything
+        let mut distance = self.map.raycast(origin, direction);
+        if distance.is_none() && direction.z.abs() > f32::EPSILON {
+            let plane_distance = -origin.z / direction.z;
+            if plane_distance > 0.0 {
+                distance = Some(plane_distance);
+            }
+        }
+        let Some(distance) = distance else {
+            return;
+        };
+
+        let hit = origin + direction.normalize() * distance;
+        if self.measure_start.is_none() || self.measure_end.is_some() {
+            self.measure_start = Some(hit);
+            self.measure_end = None;
+        }
+        else {
+            self.measure_end = Some(hit);
+        }
+    }
+
+    fn update_hover(&mut self, render_state: &RenderState) {
+        // Picking is relatively expensive, so only redo it when the cursor actually moves
+        if !self.cursor_moved {
+            return;
+        }
+        self.cursor_moved = false;
+
+        for spline in self.splines.iter() {
+            spline.borrow_mut().hovered_point = None;
+        }
+
+        let Some(cursor) = self.cursor_position else {
+            return;
+        };
+        let Some(spline) = self.splines.get(self.selected_spline as usize).filter(|s| s.borrow().visible) else {
+            return;
+        };
+        let window_size = (render_state.size.width as f32, render_state.size.height as f32);
+        if let Some((origin, direction)) = self.camera.cursor_ray(cursor, window_size) {
+            let mut spline = spline.borrow_mut();
+            spline.hovered_point = spline.pick_point(origin, direction);
+        }
+    }
+
     pub fn process_mouse(&mut self, delta: (f64, f64)) {
         self.camera_controller.process_mouse(delta);
     }
 
     pub fn update(&mut self, render_state: &RenderState, dt: Duration) {
+        self.update_sample_count(render_state);
         if self.new_spline_requested {
-            self.splines.push(spline::Spline::new(&render_state.device, &self.spline_renderer).into());
+            let mut new_spline = spline::Spline::new(&render_state.device, &self.spline_renderer);
+            new_spline.data.name = self.unique_spline_name();
+            self.splines.push(new_spline.into());
             self.selected_spline = self.splines.len() as u32 - 1;
             self.new_spline_requested = false;
         }
 
-        self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera_uniform.update_view_proj(&self.camera);
+        if self.is_playing() {
+            self.update_playback(dt);
+        }
+        else {
+            self.camera_controller.update_camera(&mut self.camera, dt);
+        }
+        let render_origin = self.render_origin();
+        self.map.set_render_origin(&render_state.device, render_origin);
+        for spline in self.splines.iter() {
+            spline.borrow_mut().set_render_origin(render_origin);
+        }
+        self.camera_uniform.update_view_proj(&self.camera, render_origin);
+        self.map_renderer.set_view_mode(&render_state.queue, self.map_view_mode);
+        self.map_renderer.set_render_mode(self.map_render_mode);
+        self.grid_renderer.update(render_state, self.grid_spacing, self.camera.position, render_origin);
+        self.update_entity_markers(render_state, render_origin);
+        self.update_hover(render_state);
+        self.update_measurement(render_state);
         render_state.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
 
         // Loop twice, first to update bundles and then second to update the rest
@@ -142,38 +661,59 @@ impl World {
             if !spline.data.bundle {
                 continue;
             }
-            spline.update(&self.splines, render_state);
+            spline.update(&self.splines, &render_state.device, &render_state.queue);
         }
         for i in 0..self.splines.len() {
             let mut spline = self.splines[i].borrow_mut();
             if spline.data.bundle {
                 continue;
             }
-            spline.update(&self.splines, render_state);
+            spline.update(&self.splines, &render_state.device, &render_state.queue);
         }
     }
 
     pub fn render(&self, _render_state: &RenderState, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let targets = SceneTargets { view, msaa_view: self.msaa_target.as_ref(), depth_view: &self.depth_texture.view };
+        self.draw_scene(encoder, &targets, &self.camera_bind_group, self.lod_enabled, false, true);
+    }
+
+    // Draw the map and splines as seen through camera_bind_group. If splines_on_top is set, the
+    // depth buffer is cleared between the map and the splines so nothing hides them. Editing
+    // overlays (grid, point gizmos) are only drawn when overlays is set.
+    fn draw_scene(&self, encoder: &mut wgpu::CommandEncoder, targets: &SceneTargets, camera_bind_group: &wgpu::BindGroup, lod_enabled: bool, splines_on_top: bool, overlays: bool) {
+        let SceneTargets { view, msaa_view, depth_view } = *targets;
         // Need to borrow up here so that the variables get dropped in the right order
         let borrowed_splines: Vec<Ref<spline::Spline>> = self.splines.iter().map(|s| s.borrow()).collect();
+        let background = self.background_color();
+
+        let color_view;
+        let resolve_target;
+        if let Some(msaa_view) = msaa_view {
+            color_view = msaa_view;
+            resolve_target = Some(view);
+        }
+        else {
+            color_view = view;
+            resolve_target = None;
+        }
 
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("3D Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
+                view: color_view,
+                resolve_target,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
+                        r: background[0] as f64,
+                        g: background[1] as f64,
+                        b: background[2] as f64,
                         a: 1.0,
                     }),
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
+                view: depth_view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
                     store: wgpu::StoreOp::Store,
@@ -184,34 +724,526 @@ impl World {
             timestamp_writes: None,
         });
 
-        self.map_renderer.draw(&mut render_pass, &self.camera_bind_group, &self.map);
-        for spline in borrowed_splines.iter() {
-            self.spline_renderer.draw(&mut render_pass, &self.camera_bind_group, &spline);
+        self.map_renderer.draw(&mut render_pass, camera_bind_group, &self.map);
+        if overlays && self.show_grid {
+            self.grid_renderer.draw(&mut render_pass, camera_bind_group);
+        }
+        if splines_on_top {
+            drop(render_pass);
+            render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Spline Overlay Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        }
+        for spline in borrowed_splines.iter().filter(|s| s.visible) {
+            let use_lod = lod_enabled && spline.distance_to(self.camera.position) > self.lod_distance;
+            self.spline_renderer.draw(&mut render_pass, camera_bind_group, &spline, use_lod, false);
+            if self.show_control_polygon {
+                self.spline_renderer.draw_control_polygon(&mut render_pass, camera_bind_group, &spline);
+            }
+        }
+        // Drawn again after everything else it could be hidden behind
+        if overlays && self.xray_selected {
+            if let Some(spline) = borrowed_splines.get(self.selected_spline as usize).filter(|s| s.visible && !s.data.bundle) {
+                let use_lod = lod_enabled && spline.distance_to(self.camera.position) > self.lod_distance;
+                self.spline_renderer.draw(&mut render_pass, camera_bind_group, spline, use_lod, true);
+            }
+        }
+        if overlays {
+            self.spline_renderer.draw_gizmo_instances(&mut render_pass, camera_bind_group, &self.entity_marker_buffer, self.entity_marker_count);
+        }
+        // Splines with a single point have no mesh, so they get a gizmo as a marker instead
+        for (i, spline) in borrowed_splines.iter().enumerate().filter(|(_, s)| s.visible) {
+            let selected_gizmos = overlays && self.show_point_gizmos && i == self.selected_spline as usize;
+            if selected_gizmos || spline.data.points.len() == 1 {
+                self.spline_renderer.draw_point_gizmos(&mut render_pass, camera_bind_group, spline);
+            }
+        }
+    }
+
+    // Start rendering a top-down orthographic image framing the map and splines, giving back its
+    // read back to poll for the image. With splines_on_top, the splines are drawn over the map even
+    // where it covers them. Reading textures back hasn't been tried on WebGL, so this is only
+    // available natively.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_overview(&self, render_state: &RenderState, splines_on_top: bool) -> Result<texture::RenderTargetReadback> {
+        // Frame everything we have
+        let mut bounds = self.map.bounds();
+        for spline in self.splines.iter() {
+            if let Some((spline_min, spline_max)) = spline.borrow().bounds() {
+                bounds = match bounds {
+                    Some((min, max)) => Some((
+                        Point3::new(min.x.min(spline_min.x), min.y.min(spline_min.y), min.z.min(spline_min.z)),
+                        Point3::new(max.x.max(spline_max.x), max.y.max(spline_max.y), max.z.max(spline_max.z)),
+                    )),
+                    None => Some((spline_min, spline_max)),
+                };
+            }
+        }
+        let Some((min, max)) = bounds else {
+            bail!("nothing to render, load a map or add some splines first");
+        };
+
+        let extent = max - min;
+        let margin = extent.x.max(extent.y).max(1.0) * OVERVIEW_MARGIN;
+        let view_width = extent.x + margin * 2.0;
+        let view_height = extent.y + margin * 2.0;
+        let aspect = view_width / view_height;
+        let width;
+        let height;
+        if aspect >= 1.0 {
+            width = OVERVIEW_IMAGE_SIZE;
+            height = ((OVERVIEW_IMAGE_SIZE as f32 / aspect) as u32).max(1);
+        }
+        else {
+            width = ((OVERVIEW_IMAGE_SIZE as f32 * aspect) as u32).max(1);
+            height = OVERVIEW_IMAGE_SIZE;
         }
+
+        // Look straight down from above the scene with +Y as up, like Hammer's top view
+        let camera = camera::Camera {
+            position: Point3::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0, max.z + 16.0),
+            pitch: -cgmath::Rad(camera::SAFE_FRAC_PI_2),
+            yaw: cgmath::Rad(std::f32::consts::FRAC_PI_2),
+            roll: cgmath::Rad(0.0),
+            aspect,
+            fovy: self.camera.fovy,
+            znear: 1.0,
+            zfar: extent.z + 32.0,
+            projection: camera::Projection::Orthographic { height: view_height },
+        };
+        // The overview gets its own camera buffer, so the live camera is left alone
+        let mut camera_uniform = camera::CameraUniform::new();
+        camera_uniform.update_view_proj(&camera, self.render_origin());
+        let camera_buffer = render_state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overview Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = render_state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("overview_camera_bind_group"),
+        });
+
+        // Our pipelines target the surface format, so render to a texture of the same format
+        let texture = texture::create_render_target(&render_state.device, width, height, render_state.config.format, "Overview Texture");
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = texture::Texture::create_depth_texture(&render_state.device, width, height, self.sample_count, "overview_depth_texture");
+        let msaa_view = (self.sample_count > 1).then(|| texture::create_msaa_target(&render_state.device, width, height, render_state.config.format, self.sample_count, "Overview MSAA Target"));
+
+        let mut encoder = render_state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Overview Encoder"),
+        });
+        let targets = SceneTargets { view: &view, msaa_view: msaa_view.as_ref(), depth_view: &depth_texture.view };
+        self.draw_scene(&mut encoder, &targets, &camera_bind_group, false, splines_on_top, false);
+        Ok(texture::RenderTargetReadback::start(&render_state.device, &render_state.queue, encoder, &texture))
     }
 
-    pub fn restore_state(&mut self, serialized_state: &str, render_state: &RenderState) {
-        let spline_data: Vec<spline::SplineData> = serde_json::from_str(serialized_state).unwrap();
+    pub fn restore_state(&mut self, serialized_state: &[u8], render_state: &RenderState) {
+        let state = decode_state(serialized_state);
+        let Some(state) = state.map_err(|e| log::warn!("failed to load splines: {e}")).ok() else {
+            return;
+        };
         self.splines = Vec::new();
-        for data in spline_data.into_iter() {
+        self.selected_spline = 0;
+        self.add_splines(state.splines, render_state);
+
+        // Pick up where the save left off
+        if let Some(camera) = state.camera {
+            self.camera.position = camera.position;
+            self.camera.pitch = camera.pitch;
+            self.camera.yaw = camera.yaw;
+        }
+        if let Some(spline) = self.splines.get(state.selected_spline as usize) {
+            self.selected_spline = state.selected_spline;
+            let mut spline = spline.borrow_mut();
+            spline.selected_point = state.selected_point.min(spline.data.points.len() as u32);
+        }
+        if let Some(clear_color) = state.clear_color {
+            self.clear_color = clear_color;
+        }
+        if let Some(skybox_background) = state.skybox_background {
+            self.skybox_background = skybox_background;
+        }
+    }
+
+    // Add the splines from a save to the current ones
+    pub fn append_state(&mut self, serialized_state: &[u8], render_state: &RenderState) {
+        let state = decode_state(serialized_state);
+        let Some(state) = state.map_err(|e| log::warn!("failed to append splines: {e}")).ok() else {
+            return;
+        };
+        self.add_splines(state.splines, render_state);
+    }
+
+    // Create splines for the given data, selecting the first one added
+    fn add_splines(&mut self, spline_data: Vec<spline::SplineData>, render_state: &RenderState) {
+        let first_imported = self.splines.len();
+        for mut data in spline_data.into_iter() {
+            data.sanitize();
             let mut spline = spline::Spline::new(&render_state.device, &self.spline_renderer);
             spline.data = data;
             spline.selected_point = spline.data.points.len() as u32;
             spline.request_rebuild();
             self.splines.push(spline.into());
         }
+        if first_imported < self.splines.len() {
+            self.selected_spline = first_imported as u32;
+        }
     }
 
-    pub fn save_state(&self) -> String {
-        let mut spline_data = Vec::new();
-        let borrowed_splines: Vec<Ref<spline::Spline>> = self.splines.iter().map(|s| s.borrow()).collect();
-        for spline in borrowed_splines.iter() {
-            spline_data.push(&spline.data);
+    // Snapshot of everything that gets saved, for saving once we know which format to save in
+    pub fn save_data(&self) -> SaveState {
+        let selected_point = self.splines.get(self.selected_spline as usize).map_or(0, |s| s.borrow().selected_point);
+        SaveState {
+            version: SAVE_VERSION,
+            splines: self.splines.iter().map(|s| s.borrow().data.clone()).collect(),
+            camera: Some(SavedCamera {
+                position: self.camera.position,
+                pitch: self.camera.pitch,
+                yaw: self.camera.yaw,
+            }),
+            selected_spline: self.selected_spline,
+            selected_point,
+            clear_color: Some(self.clear_color),
+            skybox_background: Some(self.skybox_background),
+        }
+    }
+
+    // Move the camera focus_distance back along the selected point's tangent, looking at the point.
+    // Returns false if there is no selected point to focus on.
+    fn focus_selected_point(&mut self) -> bool {
+        let Some(spline) = self.splines.get(self.selected_spline as usize) else {
+            return false;
+        };
+        let Some((position, direction)) = spline.borrow().selected_point_frame() else {
+            return false;
+        };
+
+        self.camera.position = position - direction * self.focus_distance;
+        self.camera.look_along(direction);
+
+        // The controller would otherwise keep gliding or turning from before the jump
+        self.camera_controller.stop();
+        true
+    }
+
+    // The color the viewport gets cleared to, in linear RGB
+    pub fn background_color(&self) -> [f32; 3] {
+        if self.skybox_background {
+            if let Some(skybox_color) = self.map.skybox_color() {
+                return skybox_color;
+            }
+        }
+        self.clear_color
+    }
+
+    // Entities that get a marker, skipping the hidden classnames
+    pub fn visible_entities(&self) -> impl Iterator<Item = &map::MapEntity> {
+        self.map.entities().iter().filter(|e| self.show_entities && !self.hidden_entity_classes.contains(&e.classname))
+    }
+
+    // Rewrite the entity markers for the current camera, since their size depends on it
+    fn update_entity_markers(&mut self, render_state: &RenderState, render_origin: Vector3<f32>) {
+        let markers: Vec<spline::GizmoInstance> = self.visible_entities().map(|entity| {
+            let scale = match self.camera.projection {
+                camera::Projection::Perspective => entity.origin.distance(self.camera.position),
+                camera::Projection::Orthographic { height } => height,
+            };
+            spline::GizmoInstance {
+                center: (entity.origin.to_vec() - render_origin).into(),
+                size: scale * ENTITY_MARKER_SIZE_PER_DISTANCE,
+                color: egui::Rgba::from(entity.color()).to_array(),
+            }
+        }).collect();
+
+        self.entity_marker_count = markers.len() as u32;
+        if markers.is_empty() {
+            return;
+        }
+        let size = std::mem::size_of_val(markers.as_slice()) as wgpu::BufferAddress;
+        if self.entity_marker_buffer.size() < size {
+            self.entity_marker_buffer = create_entity_marker_buffer(&render_state.device, size);
+        }
+        render_state.queue.write_buffer(&self.entity_marker_buffer, 0, bytemuck::cast_slice(&markers));
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback_elapsed.is_some()
+    }
+
+    // Start moving the camera along the selected spline, taking playback_duration to reach the end
+    pub fn start_playback(&mut self) {
+        if self.splines.get(self.selected_spline as usize).is_some_and(|s| !s.borrow().data.points.is_empty()) {
+            self.playback_elapsed = Some(0.0);
+        }
+    }
+
+    pub fn stop_playback(&mut self) {
+        if self.playback_elapsed.take().is_some() {
+            // Drop any mouse movement that built up during playback
+            self.camera_controller.stop();
+        }
+    }
+
+    // Place the camera along the selected spline at a constant speed, looking along the curve.
+    // Closed splines loop until playback is stopped, while open ones stop at the end.
+    fn update_playback(&mut self, dt: Duration) {
+        let Some(elapsed) = self.playback_elapsed else {
+            return;
+        };
+        let Some(spline) = self.splines.get(self.selected_spline as usize) else {
+            self.stop_playback();
+            return;
+        };
+        let spline = spline.borrow();
+
+        let duration = self.playback_duration.max(f32::EPSILON);
+        let mut elapsed = elapsed + dt.as_secs_f32();
+        let mut finished = false;
+        if elapsed >= duration {
+            if spline.data.closed {
+                elapsed %= duration;
+            }
+            else {
+                elapsed = duration;
+                finished = true;
+            }
+        }
+        if let Some((position, direction)) = spline.sample_by_arc_length(spline.length() * elapsed / duration) {
+            self.camera.position = position;
+            self.camera.look_along(direction);
+        }
+        drop(spline);
+
+        self.playback_elapsed = Some(elapsed);
+        if finished {
+            self.stop_playback();
+        }
+    }
+
+    // Move the snap value to the next larger or smaller power of two grid size
+    fn step_grid_size(&mut self, larger: bool) {
+        if self.snap_value.is_nan() || self.snap_value <= 0.0 {
+            self.snap_value = MIN_GRID_SIZE;
+            return;
+        }
+
+        let exponent = self.snap_value.log2();
+        let next_exponent;
+        if larger {
+            next_exponent = exponent.floor() + 1.0;
+        }
+        else {
+            next_exponent = exponent.ceil() - 1.0;
+        }
+        self.snap_value = 2.0f32.powf(next_exponent).clamp(MIN_GRID_SIZE, MAX_GRID_SIZE);
+    }
+
+    // Point everything gets rendered relative to. Far from the world origin, single precision
+    // positions get too coarse and the view jitters, so we can rebase onto the map's center.
+    fn render_origin(&self) -> Vector3<f32> {
+        if !self.rebase_origin {
+            return Vector3::zero();
+        }
+        match self.map.bounds() {
+            Some((min, max)) => ((min.to_vec() + max.to_vec()) / 2.0).map(|c| c.round()),
+            None => Vector3::zero(),
+        }
+    }
+
+    // Where newly placed points go, rounded to the placement grid. That's the camera's position, or
+    // with surface placement the first bit of map the camera looks at. Holding alt switches
+    // between the two, and we fall back to the camera when there's no surface in view.
+    pub fn placement_position(&self) -> Point3<f32> {
+        let mut position = self.camera.position;
+        if self.place_on_surface != self.modifiers.alt_key() {
+            let direction = self.camera.view_direction();
+            if let Some(distance) = self.map.raycast(self.camera.position, direction) {
+                position = self.camera.position + direction * distance;
+            }
+        }
+
+        let grid = self.placement_grid();
+        position.map(|c| (c / grid).round() * grid)
+    }
+
+    pub fn map_supports_wireframe(&self) -> bool {
+        self.map_renderer.supports_wireframe()
+    }
+
+    // Grid that newly placed points get rounded to
+    fn placement_grid(&self) -> f32 {
+        if self.snap_placement && self.snap_value > 0.0 {
+            self.snap_value
+        }
+        else {
+            1.0
         }
-        serde_json::to_string(&spline_data).unwrap()
     }
 
     pub fn add_spline(&mut self) {
         self.new_spline_requested = true;
     }
+
+    // Hide every spline except the selected one. If that's already the case, show them all again.
+    pub fn toggle_solo(&mut self) {
+        let selected = self.selected_spline as usize;
+        let soloed = self.splines.iter().enumerate().all(|(i, s)| s.borrow().visible == (i == selected));
+        for (i, spline) in self.splines.iter().enumerate() {
+            spline.borrow_mut().visible = soloed || i == selected;
+        }
+    }
+
+    // Generate a default model path for a new spline that doesn't collide with any existing ones
+    fn unique_spline_name(&self) -> String {
+        let mut n = self.splines.len() + 1;
+        loop {
+            let name = format!("spline_gen/spline_{n}.mdl");
+            if !self.splines.iter().any(|s| s.borrow().data.name == name) {
+                return name;
+            }
+            n += 1;
+        }
+    }
+}
+
+fn create_entity_marker_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Entity Marker Buffer"),
+        size,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// Multisampled color target matching the surface, or None when we aren't multisampling
+fn create_msaa_target(render_state: &RenderState, sample_count: u32) -> Option<wgpu::TextureView> {
+    if sample_count == 1 {
+        return None;
+    }
+    Some(texture::create_msaa_target(&render_state.device, render_state.config.width, render_state.config.height, render_state.config.format, sample_count, "MSAA Target"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spline::tests::straight_spline;
+
+    fn as_json<T: Serialize>(value: &T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    fn test_state() -> SaveState {
+        let mut curved = straight_spline(3, 6);
+        curved.name = "curved".to_string();
+        curved.points[1].handles = Some((Vector3::new(-10.0, 5.0, 0.0), Vector3::new(10.0, 5.0, 0.0)));
+        curved.points[2].material_override = Some("dev/dev_blendmeasure".to_string());
+        curved.points[2].subdivisions = Some(2);
+        SaveState {
+            version: SAVE_VERSION,
+            splines: vec![straight_spline(2, 3), curved],
+            camera: Some(SavedCamera { position: Point3::new(1.0, 2.0, 3.0), pitch: Rad(0.5), yaw: Rad(-1.0) }),
+            selected_spline: 1,
+            selected_point: 2,
+            clear_color: Some([0.5, 0.25, 0.0]),
+            skybox_background: Some(true),
+        }
+    }
+
+    #[test]
+    fn saves_round_trip_in_both_formats() {
+        let state = test_state();
+        for binary in [false, true] {
+            let decoded = decode_state(&encode_state(&state, binary)).unwrap();
+            assert_eq!(as_json(&decoded), as_json(&state), "binary: {binary}");
+        }
+    }
+
+    // The spline in the checked in binary saves. Those were written by the versions that used
+    // each format, so unlike saves we encode here, they catch changes to the binary layout.
+    fn saved_spline() -> spline::SplineData {
+        serde_json::from_str(include_str!("test_saves/spline.json")).unwrap()
+    }
+
+    #[test]
+    fn version_2_binary_saves_load() {
+        let decoded = decode_state(include_bytes!("test_saves/v2.bin")).unwrap();
+        assert_eq!(decoded.version, 2);
+        assert_eq!(as_json(&decoded.splines), as_json(&[saved_spline()]));
+        assert_eq!(decoded.camera.map(|camera| camera.position), Some(Point3::new(64.0, -128.0, 32.0)));
+        assert_eq!((decoded.selected_spline, decoded.selected_point), (0, 1));
+        assert_eq!(decoded.clear_color, Some([0.5, 0.25, 0.125]));
+        assert_eq!(decoded.skybox_background, Some(true));
+    }
+
+    #[test]
+    fn version_1_binary_saves_load() {
+        let decoded = decode_state(include_bytes!("test_saves/v1.bin")).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert_eq!(as_json(&decoded.splines), as_json(&[saved_spline()]));
+        assert_eq!(decoded.camera.map(|camera| camera.position), Some(Point3::new(64.0, -128.0, 32.0)));
+        assert_eq!((decoded.selected_spline, decoded.selected_point), (0, 1));
+        assert!(decoded.clear_color.is_none() && decoded.skybox_background.is_none());
+    }
+
+    #[test]
+    fn unversioned_saves_load() {
+        let decoded = decode_state(include_bytes!("test_saves/legacy.bin")).unwrap();
+        assert_eq!(as_json(&decoded.splines), as_json(&[saved_spline()]));
+        assert!(decoded.camera.is_none());
+
+        let splines = test_state().splines;
+        let decoded = decode_state(&serde_json::to_vec(&splines).unwrap()).unwrap();
+        assert_eq!(as_json(&decoded.splines), as_json(&splines));
+        assert!(decoded.camera.is_none());
+    }
+
+    #[test]
+    fn newer_binary_saves_are_rejected() {
+        // Laid out like a current save, so it would only fail to load because of the version
+        let mut save = encode_state(&test_state(), true);
+        save[BINARY_SAVE_MAGIC.len()..][..4].copy_from_slice(&(SAVE_VERSION + 1).to_le_bytes());
+        assert!(decode_state(&save).is_err());
+    }
+
+    #[test]
+    fn non_finite_points_get_dropped() {
+        // 1e39 is past the range of f32, so it loads as infinity
+        let mut state = test_state();
+        state.splines[1].points[1].position.x = 1e38;
+        let save = String::from_utf8(encode_state(&state, false)).unwrap().replace("1e38", "1e39");
+
+        let mut decoded = decode_state(save.as_bytes()).unwrap();
+        assert!(decoded.splines[1].points[1].position.x.is_infinite());
+        for data in &mut decoded.splines {
+            data.sanitize();
+        }
+        assert_eq!(decoded.splines[0].points.len(), 2);
+        assert_eq!(decoded.splines[1].points.len(), 2);
+        assert!(decoded.splines.iter().flat_map(|data| &data.points).all(|point| point.is_finite()));
+    }
 }
@@ -8,12 +8,14 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) color: vec3<f32>,
+    @location(3) normal: vec3<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
 };
 
 @group(1) @binding(0)
@@ -27,6 +29,7 @@ fn vs_main(
     out.tex_coords = model.tex_coords;
     out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
     out.color = model.color;
+    out.normal = model.normal;
     return out;
 }
 
@@ -37,7 +40,26 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+// Matches MapViewMode in map.rs
+struct ViewModeUniform {
+    mode: u32,
+};
+
+@group(2) @binding(0)
+var<uniform> view_mode: ViewModeUniform;
+
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.3, 0.5, 0.81);
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * vec4<f32>(in.color, 1.0);
+    let texture_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    // Simple directional light with some ambient, so faces never go completely black
+    let lighting = 0.4 + 0.6 * max(dot(normalize(in.normal), normalize(LIGHT_DIRECTION)), 0.0);
+    if view_mode.mode == 1u {
+        return vec4<f32>(vec3<f32>(0.6 * lighting), 1.0);
+    }
+    else if view_mode.mode == 2u {
+        return vec4<f32>(texture_color.rgb * lighting, 1.0);
+    }
+    return texture_color * vec4<f32>(in.color, 1.0);
 }
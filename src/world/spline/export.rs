@@ -1,60 +1,413 @@
-use super::Spline;
+use super::{Spline, SplineData, SplineMesh, SubdivMode};
 
 use anyhow::*;
-use cgmath::Point3;
+use cgmath::{InnerSpace, Point3, Vector3};
 use egui::{Color32, Rgba};
 use indoc::{formatdoc, indoc};
-use std::cell::{RefCell, Ref};
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::io::{Cursor, Write};
 use zip::ZipWriter;
 use zip::write::SimpleFileOptions;
 
-pub fn construct_zip(splines: &[RefCell<Spline>]) -> Result<Vec<u8>> {
+// Whether to check the SMD triangles for normals that disagree with their winding
+const VALIDATE_SMD_NORMALS: bool = cfg!(debug_assertions);
+
+// Where the origin of each exported model is placed
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportOrigin {
+    FirstPoint,
+    WorldOrigin,
+    BoundingCenter,
+    Custom(Point3<f32>),
+}
+
+impl std::fmt::Display for ExportOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ExportOrigin::FirstPoint => write!(f, "First Point"),
+            ExportOrigin::WorldOrigin => write!(f, "World Origin"),
+            ExportOrigin::BoundingCenter => write!(f, "Bounding Box Center"),
+            ExportOrigin::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl ExportOrigin {
+    // The world position that becomes the model's origin, given the splines going into the model
+    // and their meshes
+    fn position(&self, splines: &[&SplineData], meshes: &[SplineMesh]) -> Point3<f32> {
+        let zero = Point3::new(0.0, 0.0, 0.0);
+        let first_point = splines.iter().find_map(|data| data.points.first()).map_or(zero, |p| p.position);
+        match *self {
+            ExportOrigin::FirstPoint => first_point,
+            ExportOrigin::WorldOrigin => zero,
+            ExportOrigin::BoundingCenter => {
+                let bounds = meshes.iter().filter_map(|mesh| mesh.bounds()).reduce(|(min, max), (mesh_min, mesh_max)| (
+                    Point3::new(min.x.min(mesh_min.x), min.y.min(mesh_min.y), min.z.min(mesh_min.z)),
+                    Point3::new(max.x.max(mesh_max.x), max.y.max(mesh_max.y), max.z.max(mesh_max.z)),
+                ));
+                match bounds {
+                    Some((min, max)) => Point3::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0, (min.z + max.z) / 2.0),
+                    None => first_point,
+                }
+            },
+            ExportOrigin::Custom(position) => position,
+        }
+    }
+}
+
+// Which axis points up in an exported OBJ. Source is Z-up, but most OBJ consumers expect Y-up.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ObjUpAxis {
+    Z,
+    Y,
+}
+
+impl std::fmt::Display for ObjUpAxis {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ObjUpAxis::Z => write!(f, "Z-up (Source)"),
+            ObjUpAxis::Y => write!(f, "Y-up"),
+        }
+    }
+}
+
+impl ObjUpAxis {
+    // Convert a Source position or normal into this axis convention. Turning Z-up into Y-up is a
+    // rotation about the X axis, so triangle winding is preserved.
+    fn convert(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        match *self {
+            ObjUpAxis::Z => [x, y, z],
+            ObjUpAxis::Y => [x, z, -y],
+        }
+    }
+}
+
+// When separately is set, each model gets its own folder with a full set of files, so that they
+// can be compiled independently of each other. Bundle splines are merged into another spline's
+// model, see model_groups. Only the spline data is needed, so this also works without a window or
+// GPU.
+pub fn construct_zip(splines: &[SplineData], separately: bool, origin: ExportOrigin) -> Result<Vec<u8>> {
     // Construct the buffer we will write our Zip file to
     let mut zip_buffer = Vec::new();
     let mut zip = ZipWriter::new(Cursor::new(&mut zip_buffer));
-    let options = SimpleFileOptions::default();
 
-    // Construct the model files for each spline
-    for (i, spline) in splines.iter().enumerate() {
-        let spline = spline.borrow();
-        if spline.data.bundle {
-            continue;
+    let duplicate_names = find_duplicates(splines.iter().filter(|data| !data.bundle).map(|data| sanitize_model_name(&data.name)));
+    ensure!(duplicate_names.is_empty(), "multiple splines use the model paths {:?}", duplicate_names);
+
+    // Construct the model files for each spline, along with the bundles merged into it
+    for group in model_groups(splines.iter().map(|data| data.bundle)) {
+        let i = group[0];
+        let model: Vec<&SplineData> = group.iter().map(|&spline_i| &splines[spline_i]).collect();
+        if separately {
+            let folder = format!("spline-{i}/");
+            write_model_files(&mut zip, &folder, i, &model, origin)?;
+            write_material_files(&mut zip, &folder)?;
+        }
+        else {
+            write_model_files(&mut zip, "", i, &model, origin)?;
         }
+    }
 
-        // Construct the SMD file
-        zip.start_file(format!("spline-{i}.smd"), options)?;
-        smd_from_spline(&spline, &mut zip)?;
+    if !separately {
+        write_material_files(&mut zip, "")?;
+    }
 
-        // Construct the QC file
-        let origin;
-        if spline.data.points.len() == 0 {
-            origin = Point3::new(0.0, 0.0, 0.0);
+    zip.finish().unwrap();
+    return Ok(zip_buffer);
+}
+
+// Split the splines into models given whether each one is a bundle spline, giving the indices of
+// the splines in each model. A bundle spline's tube is merged into the model of the next non-bundle
+// spline after it, so that a bundle compiles into the same prop as the splines following it.
+// Bundles past the last non-bundle spline join the last model instead, and with no non-bundle
+// splines at all there's no model to merge into, so nothing gets exported. The model's own spline
+// comes first, and gives the model its path and name.
+fn model_groups(bundle_flags: impl Iterator<Item = bool>) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut pending_bundles = Vec::new();
+    for (i, bundle) in bundle_flags.enumerate() {
+        if bundle {
+            pending_bundles.push(i);
         }
         else {
-            origin = spline.data.points[0].position;
+            let mut group = vec![i];
+            group.append(&mut pending_bundles);
+            groups.push(group);
         }
-        zip.start_file(format!("spline-{i}.qc"), options)?;
-        // We negate the origin to offset it to the first vertex
-        zip.write_all(&formatdoc! {"
-            $staticprop
-            $modelname \"{}\"
-            $origin {} {} {}
-            $scale \"1.0\"
-            $body \"Body\" \"spline-{i}\"
-            $cdmaterials \"spline-gen\"
-            $sequence idle \"spline-{i}\"
-            $surfaceprop \"default\"
-            $mostlyopaque
-        ", spline.data.name, -origin.x, -origin.y, -origin.z}.into_bytes())?;
-    }
-
-    // Construct the required VTF/VMT files
-    zip.add_directory("materials/spline-gen", options)?;
-    zip.start_file("materials/spline-gen/spline.vtf", options)?;
+    }
+    if let Some(group) = groups.last_mut() {
+        group.append(&mut pending_bundles);
+    }
+    groups
+}
+
+// Zip up a single OBJ holding every exported model as its own object, along with the MTL giving
+// each control point's color. Bundle splines are merged into another spline's object, see
+// model_groups.
+pub fn construct_obj_zip(splines: &[SplineData], up_axis: ObjUpAxis) -> Result<Vec<u8>> {
+    let mut obj = Vec::new();
+    let mut mtl = Vec::new();
+    obj.write_all(b"mtllib splines.mtl\n")?;
+
+    // OBJ indices are global across the file, so track how many vertices came before each spline
+    let mut vertex_offset = 0;
+    for group in model_groups(splines.iter().map(|data| data.bundle)) {
+        writeln!(obj, "o spline-{}", group[0])?;
+        for i in group {
+            vertex_offset += obj_from_spline(&splines[i], i, vertex_offset, up_axis, &mut obj)?;
+            mtl_from_spline(&splines[i], i, &mut mtl)?;
+        }
+    }
+
+    let mut zip_buffer = Vec::new();
+    let mut zip = ZipWriter::new(Cursor::new(&mut zip_buffer));
+    let options = SimpleFileOptions::default();
+    zip.start_file("splines.obj", options)?;
+    zip.write_all(&obj)?;
+    zip.start_file("splines.mtl", options)?;
+    zip.write_all(&mtl)?;
+    zip.finish()?;
+    Ok(zip_buffer)
+}
+
+// Write a spline's mesh into the current OBJ object, with each triangle using the material of the
+// control point its majority t-value starts from. Returns the number of vertices written.
+fn obj_from_spline(data: &SplineData, i: usize, vertex_offset: usize, up_axis: ObjUpAxis, obj: &mut dyn Write) -> Result<usize> {
+    let mesh = SplineMesh::new(data, SubdivMode::Fixed(data.subdivisions));
+    for vertex in mesh.vertices.iter() {
+        let [x, y, z] = up_axis.convert(vertex.position);
+        writeln!(obj, "v {x:.6} {y:.6} {z:.6}")?;
+    }
+    for vertex in mesh.vertices.iter() {
+        let [x, y, z] = up_axis.convert(vertex.normal);
+        writeln!(obj, "vn {x:.6} {y:.6} {z:.6}")?;
+    }
+
+    // Only switch materials when they change, which is once per segment
+    let mut current_point = None;
+    for triangle in mesh.indices.chunks(3) {
+        let v0 = mesh.vertices[triangle[0] as usize];
+        let v1 = mesh.vertices[triangle[1] as usize];
+        let v2 = mesh.vertices[triangle[2] as usize];
+        let point = majority_t_value(&v0, &v1, &v2).floor() as usize;
+        if current_point != Some(point) {
+            writeln!(obj, "usemtl spline-{i}-point-{point}")?;
+            current_point = Some(point);
+        }
+
+        // OBJ indices start at 1
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize + vertex_offset + 1);
+        writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+
+    Ok(mesh.vertices.len())
+}
+
+// Write a material for each of a spline's control points, colored with the point's color
+fn mtl_from_spline(data: &SplineData, i: usize, mtl: &mut dyn Write) -> Result<()> {
+    for (point_i, point) in data.points.iter().enumerate() {
+        let [r, g, b, a] = point.color.to_srgba_unmultiplied();
+        writeln!(mtl, "newmtl spline-{i}-point-{point_i}")?;
+        writeln!(mtl, "Kd {:.6} {:.6} {:.6}", r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)?;
+        writeln!(mtl, "d {:.6}", a as f32 / 255.0)?;
+        writeln!(mtl)?;
+    }
+    Ok(())
+}
+
+// Build a single binary glTF (GLB) holding each exported model as its own mesh and node, for
+// previewing in a browser. Bundle splines are merged into another spline's mesh, see model_groups.
+// glTF is Y-up, so the meshes are converted the same way as a Y-up OBJ, but positions stay in
+// Source units. The point colors are baked into the vertex colors.
+pub fn gltf_from_splines(splines: &[SplineData]) -> Result<Vec<u8>> {
+    // glTF accessor component types and buffer view targets
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+    let mut binary: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+    let mut translucent = false;
+    // Every view starts on a 4 byte boundary, which is all our float and u32 data needs
+    let mut push_view = |binary: &mut Vec<u8>, bytes: &[u8], target: u32| {
+        let view = serde_json::json!({
+            "buffer": 0,
+            "byteOffset": binary.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        });
+        binary.extend_from_slice(bytes);
+        buffer_views.push(view);
+        buffer_views.len() - 1
+    };
+
+    // Each model is a mesh, with a primitive for its own spline and each bundle merged into it
+    for group in model_groups(splines.iter().map(|data| data.bundle)) {
+        let mut primitives = Vec::new();
+        for data in group.iter().map(|&i| &splines[i]) {
+            let mesh = SplineMesh::new(data, SubdivMode::Fixed(data.subdivisions));
+            if mesh.vertices.is_empty() {
+                continue;
+            }
+
+            let positions: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| ObjUpAxis::Y.convert(v.position)).collect();
+            let normals: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| ObjUpAxis::Y.convert(Vector3::from(v.normal).normalize().into())).collect();
+            // Blend between the colors of the points on either side, like the viewport does. The
+            // segment closing a loop blends back towards the first point.
+            let colors: Vec<[f32; 4]> = mesh.vertices.iter().map(|v| {
+                let t = v.t_value - v.t_value.floor();
+                let [r0, g0, b0, a0] = data.points[v.t_value.floor() as usize].color.to_srgba_unmultiplied();
+                let [r1, g1, b1, a1] = data.points[v.t_value.ceil() as usize % data.points.len()].color.to_srgba_unmultiplied();
+                let color0 = Rgba::from_srgba_unmultiplied(r0, g0, b0, a0);
+                let color1 = Rgba::from_srgba_unmultiplied(r1, g1, b1, a1);
+                (color0 * (1.0 - t) + color1 * t).to_rgba_unmultiplied()
+            }).collect();
+            translucent |= colors.iter().any(|color| color[3] < 1.0);
+
+            let mut min = positions[0];
+            let mut max = positions[0];
+            for position in positions.iter() {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+            }
+
+            let position_view = push_view(&mut binary, bytemuck::cast_slice(&positions), ARRAY_BUFFER);
+            let normal_view = push_view(&mut binary, bytemuck::cast_slice(&normals), ARRAY_BUFFER);
+            let color_view = push_view(&mut binary, bytemuck::cast_slice(&colors), ARRAY_BUFFER);
+            let index_view = push_view(&mut binary, bytemuck::cast_slice(&mesh.indices), ELEMENT_ARRAY_BUFFER);
+            let first_accessor = accessors.len();
+            accessors.push(serde_json::json!({ "bufferView": position_view, "componentType": FLOAT, "count": positions.len(), "type": "VEC3", "min": min, "max": max }));
+            accessors.push(serde_json::json!({ "bufferView": normal_view, "componentType": FLOAT, "count": normals.len(), "type": "VEC3" }));
+            accessors.push(serde_json::json!({ "bufferView": color_view, "componentType": FLOAT, "count": colors.len(), "type": "VEC4" }));
+            accessors.push(serde_json::json!({ "bufferView": index_view, "componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR" }));
+
+            primitives.push(serde_json::json!({
+                "attributes": {
+                    "POSITION": first_accessor,
+                    "NORMAL": first_accessor + 1,
+                    "COLOR_0": first_accessor + 2,
+                },
+                "indices": first_accessor + 3,
+                "material": 0,
+            }));
+        }
+        if primitives.is_empty() {
+            continue;
+        }
+
+        let name = &splines[group[0]].name;
+        meshes.push(serde_json::json!({ "name": name, "primitives": primitives }));
+        nodes.push(serde_json::json!({ "name": name, "mesh": meshes.len() - 1 }));
+    }
+    ensure!(!meshes.is_empty(), "there are no splines to export");
+
+    // A single white material, so the vertex colors come through unchanged
+    let gltf = serde_json::json!({
+        "asset": { "version": "2.0", "generator": format!("Spline Generator {}", env!("CARGO_PKG_VERSION")) },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": [{
+            "name": "spline",
+            "pbrMetallicRoughness": { "baseColorFactor": [1.0, 1.0, 1.0, 1.0], "metallicFactor": 0.0, "roughnessFactor": 1.0 },
+            "alphaMode": if translucent { "BLEND" } else { "OPAQUE" },
+            "doubleSided": translucent,
+        }],
+        "accessors": accessors,
+        "bufferViews": buffer_views,
+        "buffers": [{ "byteLength": binary.len() }],
+    });
+
+    // Chunks have to be padded to 4 bytes, with spaces for the JSON and zeros for the binary
+    let mut json = serde_json::to_vec(&gltf)?;
+    json.resize(json.len().next_multiple_of(4), b' ');
+    binary.resize(binary.len().next_multiple_of(4), 0);
+    let total_length = 12 + 8 + json.len() + 8 + binary.len();
+
+    let mut glb = Vec::with_capacity(total_length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    glb.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"BIN\0");
+    glb.extend_from_slice(&binary);
+    Ok(glb)
+}
+
+// Write the SMD and QC files for a model made of one or more splines, with every path prefixed by
+// folder. The model takes the first spline's model path.
+fn write_model_files<W: Write + std::io::Seek>(zip: &mut ZipWriter<W>, folder: &str, i: usize, splines: &[&SplineData], origin: ExportOrigin) -> Result<()> {
+    let options = SimpleFileOptions::default();
+
+    // The render mesh may be coarser than what we want to export, so build the meshes at full detail
+    let meshes: Vec<SplineMesh> = splines.iter().map(|data| SplineMesh::new(data, SubdivMode::Fixed(data.subdivisions))).collect();
+    let comment: String = splines.iter().map(|data| generator_comment(data)).collect();
+
+    // Construct the SMD file
+    zip.start_file(format!("{folder}spline-{i}.smd"), options)?;
+    zip.write_all(comment.as_bytes())?;
+    zip.write_all(indoc! {b"
+        version 1
+        nodes
+        0 \"static_prop\" -1
+        end
+        skeleton
+        time 0
+        0 0.000000 0.000000 0.000000 0.000000 0.000000 0.000000
+        end
+        triangles
+    "})?;
+    for (data, mesh) in splines.iter().zip(meshes.iter()) {
+        smd_triangles(data, mesh, zip)?;
+    }
+    zip.write_all(b"end")?;
+
+    // Construct the QC file
+    let origin = origin.position(splines, &meshes);
+    // Overridden materials are given relative to materials/, so they need the root as a material
+    // directory too. They come from the game's own content, so we don't package them.
+    let mut cdmaterials = "$cdmaterials \"spline-gen\"".to_string();
+    if splines.iter().flat_map(|data| data.points.iter()).any(|p| segment_material(p).is_some()) {
+        cdmaterials.push_str("\n$cdmaterials \"\"");
+    }
+    zip.start_file(format!("{folder}spline-{i}.qc"), options)?;
+    zip.write_all(comment.as_bytes())?;
+    // The SMD is in world coordinates, so we negate the origin to offset the model onto it
+    zip.write_all(&formatdoc! {"
+        $staticprop
+        $modelname \"{}\"
+        $origin {} {} {}
+        $scale \"1.0\"
+        $body \"Body\" \"spline-{i}\"
+        {cdmaterials}
+        $sequence idle \"spline-{i}\"
+        $surfaceprop \"default\"
+        $mostlyopaque
+    ", sanitize_model_name(&splines[0].name), -origin.x, -origin.y, -origin.z}.into_bytes())?;
+
+    Ok(())
+}
+
+// Write the VTF/VMT files the models use, with every path prefixed by folder
+fn write_material_files<W: Write + std::io::Seek>(zip: &mut ZipWriter<W>, folder: &str) -> Result<()> {
+    let options = SimpleFileOptions::default();
+    zip.add_directory(format!("{folder}materials/spline-gen"), options)?;
+    zip.start_file(format!("{folder}materials/spline-gen/spline.vtf"), options)?;
     zip.write_all(include_bytes!("spline.vtf"))?;
 
-    zip.start_file("materials/spline-gen/spline.vmt", options)?;
+    zip.start_file(format!("{folder}materials/spline-gen/spline.vmt"), options)?;
     zip.write_all(indoc! {b"
         \"UnlitGeneric\"
         {
@@ -63,7 +416,7 @@ pub fn construct_zip(splines: &[RefCell<Spline>]) -> Result<Vec<u8>> {
         }
     "})?;
 
-    zip.start_file("materials/spline-gen/spline-transparent.vmt", options)?;
+    zip.start_file(format!("{folder}materials/spline-gen/spline-transparent.vmt"), options)?;
     zip.write_all(indoc! {b"
         \"UnlitGeneric\"
         {
@@ -73,27 +426,142 @@ pub fn construct_zip(splines: &[RefCell<Spline>]) -> Result<Vec<u8>> {
         }
     "})?;
 
-    zip.finish().unwrap();
-    return Ok(zip_buffer);
+    Ok(())
 }
 
-fn smd_from_spline(spline: &Ref<Spline>, zip: &mut dyn Write) -> Result<()> {
-    zip.write_all(indoc! {b"
-        version 1
-        nodes
-        0 \"static_prop\" -1
-        end
-        skeleton
-        time 0
-        0 0.000000 0.000000 0.000000 0.000000 0.000000 0.000000
-        end
-        triangles
-    "})?;
+// Studiomdl refuses to compile a model with more vertices than this
+pub const MAX_MODEL_VERTICES: usize = 65536;
+
+// The (vertex, triangle) counts of each exported model, at the export subdivisions. Bundle
+// splines count towards the model they get merged into, see model_groups.
+pub fn export_counts(splines: &[RefCell<Spline>]) -> Vec<(String, usize, usize)> {
+    let mut counts = Vec::new();
+    for group in model_groups(splines.iter().map(|s| s.borrow().data.bundle)) {
+        let mut model_vertices = 0;
+        let mut model_triangles = 0;
+        for &spline_i in group.iter() {
+            let spline = splines[spline_i].borrow();
+            let (vertices, triangles) = SplineMesh::counts(&spline.data, spline.data.subdivisions);
+            model_vertices += vertices;
+            model_triangles += triangles;
+        }
+        counts.push((sanitize_model_name(&splines[group[0]].borrow().data.name), model_vertices, model_triangles));
+    }
+    counts
+}
+
+// Find every model path used by more than one exported spline. The SMD files are named by index,
+// but the $modelname of each QC would collide and overwrite each other in game.
+pub fn duplicate_model_names(splines: &[RefCell<Spline>]) -> Vec<String> {
+    find_duplicates(splines.iter().map(|s| s.borrow()).filter(|s| !s.data.bundle).map(|s| sanitize_model_name(&s.data.name)))
+}
+
+fn find_duplicates(names: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen_names = HashSet::new();
+    let mut duplicate_names = Vec::new();
+    for name in names {
+        if !seen_names.insert(name.clone()) && !duplicate_names.contains(&name) {
+            duplicate_names.push(name);
+        }
+    }
+    duplicate_names
+}
+
+// Check a model name for problems that would break compiling the model, returning a description
+// of each problem found
+pub fn model_name_problems(name: &str) -> Vec<&'static str> {
+    let mut problems = Vec::new();
+    if name.is_empty() {
+        problems.push("Model path is empty");
+        return problems;
+    }
+    if name.chars().any(|c| c.is_whitespace()) {
+        problems.push("Model path contains whitespace");
+    }
+    if name.contains('\\') {
+        problems.push("Model path contains backslashes");
+    }
+    if !name.to_lowercase().ends_with(".mdl") {
+        problems.push("Model path doesn't end in .mdl");
+    }
+    problems
+}
 
-    for triangles in spline.indices.chunks(3) {
-        let v0 = spline.vertices[triangles[0] as usize];
-        let v1 = spline.vertices[triangles[1] as usize];
-        let v2 = spline.vertices[triangles[2] as usize];
+// Turn a model name into one that is safe to use as the $modelname of a QC. We lowercase the name,
+// use forward slashes, replace any other illegal characters with underscores, and make sure the
+// name ends in .mdl.
+pub fn sanitize_model_name(name: &str) -> String {
+    let mut sanitized: String = name.trim().to_lowercase().chars().map(|c| {
+        if c == '\\' {
+            '/'
+        }
+        else if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '/' {
+            c
+        }
+        else {
+            '_'
+        }
+    }).collect();
+
+    // Leading slashes would make the path absolute
+    sanitized = sanitized.trim_start_matches('/').to_string();
+    if sanitized.is_empty() || sanitized == ".mdl" {
+        sanitized = "spline".to_string();
+    }
+    if !sanitized.ends_with(".mdl") {
+        sanitized.push_str(".mdl");
+    }
+    sanitized
+}
+
+// The material a point's following segment should use instead of the color atlas, normalized to a
+// path relative to materials/ without the extension
+fn segment_material(point: &super::SplineControlPoint) -> Option<String> {
+    let material = point.material_override.as_ref()?.trim().to_lowercase().replace('\\', "/");
+    let material = material.trim_start_matches('/');
+    let material = material.strip_prefix("materials/").unwrap_or(material);
+    let material = material.strip_suffix(".vmt").unwrap_or(material);
+    if material.is_empty() {
+        None
+    }
+    else {
+        Some(material.to_string())
+    }
+}
+
+// Comment block recording the settings that generated a spline's model, so they can be found
+// again from the exported files. Both QC and SMD files accept // comments. The full spline data is
+// included on one line so that the model can be loaded back as a spline.
+fn generator_comment(data: &SplineData) -> String {
+    formatdoc! {"
+        // Generated by Spline Generator {}
+        // Model path: {}
+        // Radius: {}
+        // Sides: {}
+        // Subdivisions: {}
+        // Points: {}
+        {}{}
+    ", env!("CARGO_PKG_VERSION"), data.name, data.radius, data.sides, data.subdivisions, data.points.len(),
+    super::import::SPLINE_DATA_COMMENT, serde_json::to_string(data).unwrap()}
+}
+
+// The t-value shared by two of a triangle's vertices. Every triangle spans two rings, so two of
+// its vertices always lie on the same ring.
+fn majority_t_value(v0: &super::SplineVertex, v1: &super::SplineVertex, v2: &super::SplineVertex) -> f32 {
+    if v0.t_value == v1.t_value || v0.t_value == v2.t_value {
+        v0.t_value
+    }
+    else {
+        v1.t_value
+    }
+}
+
+// Write a spline's triangles into the triangles block of an SMD
+fn smd_triangles(data: &SplineData, mesh: &SplineMesh, zip: &mut dyn Write) -> Result<()> {
+    for (triangle_i, triangles) in mesh.indices.chunks(3).enumerate() {
+        let v0 = mesh.vertices[triangles[0] as usize];
+        let v1 = mesh.vertices[triangles[1] as usize];
+        let v2 = mesh.vertices[triangles[2] as usize];
         let [v0x, v0y, v0z] = v0.position;
         let [v1x, v1y, v1z] = v1.position;
         let [v2x, v2y, v2z] = v2.position;
@@ -103,17 +571,12 @@ fn smd_from_spline(spline: &Ref<Spline>, zip: &mut dyn Write) -> Result<()> {
 
         // Determine the color of the triangle
         // We use the color of the majority t-value
-        let full_t;
-        if v0.t_value == v1.t_value || v0.t_value == v2.t_value {
-            full_t = v0.t_value;
-        }
-        else {
-            full_t = v1.t_value;
-        }
+        let full_t = majority_t_value(&v0, &v1, &v2);
         let t = full_t - full_t.floor();
 
-        let color0 = Rgba::from(spline.data.points[full_t.floor() as usize].color);
-        let color1 = Rgba::from(spline.data.points[std::cmp::min(full_t.ceil() as usize, spline.data.points.len() - 1)].color);
+        // The segment closing a loop blends back towards the first point
+        let color0 = Rgba::from(data.points[full_t.floor() as usize].color);
+        let color1 = Rgba::from(data.points[full_t.ceil() as usize % data.points.len()].color);
         let (r0, g0, b0, a0) = color0.to_tuple();
         let (r1, g1, b1, a1) = color1.to_tuple();
         let (rt, gt, bt, at) = (r0 * (1.0 - t) + r1 * t, g0 * (1.0 - t) + g1 * t, b0 * (1.0 - t) + b1 * t, a0 * (1.0 - t) + a1 * t);
@@ -133,24 +596,123 @@ fn smd_from_spline(spline: &Ref<Spline>, zip: &mut dyn Write) -> Result<()> {
         // NOTE: X/Y is usually east/north, but is north/west in SMD
         // Additionally, SMD has normals point inwards instead of outwards
         let vmt_name;
-        if qa == 31 {
-            vmt_name = "spline.vmt";
+        let uvs;
+        if let Some(material) = segment_material(&data.points[full_t.floor() as usize]) {
+            // Overridden materials are real textures, so stretch them along the spline with one
+            // repeat per segment instead of picking a color out of the atlas
+            vmt_name = format!("{material}.vmt");
+            uvs = [(v0.t_value, 0.5), (v1.t_value, 0.5), (v2.t_value, 0.5)];
         }
         else {
-            vmt_name = "spline-transparent.vmt";
+            if qa == 31 {
+                vmt_name = "spline.vmt".to_string();
+            }
+            else {
+                vmt_name = "spline-transparent.vmt".to_string();
+            }
+            uvs = [(u, v); 3];
         }
+        let [(u0, uv0), (u1, uv1), (u2, uv2)] = uvs;
         zip.write_all(&formatdoc! {"
             {vmt_name}
-            0 {} {} {} {} {} {} {u} {v}
-            0 {} {} {} {} {} {} {u} {v}
-            0 {} {} {} {} {} {} {u} {v}
+            0 {} {} {} {} {} {} {u0} {uv0}
+            0 {} {} {} {} {} {} {u1} {uv1}
+            0 {} {} {} {} {} {} {u2} {uv2}
         ",
         v0y, -v0x, v0z, -n0y, n0x, -n0z,
         v1y, -v1x, v1z, -n1y, n1x, -n1z,
         v2y, -v2x, v2z, -n2y, n2x, -n2z}.into_bytes())?;
-    }
 
-    zip.write_all(b"end")?;
+        if VALIDATE_SMD_NORMALS {
+            // Check that the winding of the converted triangle still agrees with its converted
+            // normals, since getting the coordinate conversion wrong turns the model inside-out.
+            let p0 = Vector3::new(v0y, -v0x, v0z);
+            let p1 = Vector3::new(v1y, -v1x, v1z);
+            let p2 = Vector3::new(v2y, -v2x, v2z);
+            let geometric_normal = (p1 - p0).cross(p2 - p0);
+            let vertex_normal = Vector3::new(-n0y, n0x, -n0z) + Vector3::new(-n1y, n1x, -n1z) + Vector3::new(-n2y, n2x, -n2z);
+            // SMD normals point inwards, so they should face away from the geometric normal.
+            // Degenerate triangles have no meaningful winding, so skip those.
+            if geometric_normal.magnitude2() > f32::EPSILON && geometric_normal.dot(vertex_normal) >= 0.0 {
+                log::warn!("triangle {triangle_i} of spline \"{}\" is wound against its normals in the SMD", data.name);
+            }
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::straight_spline;
+    use std::io::Read;
+
+    #[test]
+    fn bundles_join_the_next_model() {
+        assert_eq!(model_groups([true, false, true, true, false].into_iter()), vec![vec![1, 0], vec![4, 2, 3]]);
+        assert_eq!(model_groups([false, true].into_iter()), vec![vec![0, 1]]);
+        assert!(model_groups([true, true].into_iter()).is_empty());
+    }
+
+    #[test]
+    fn bundles_are_merged_into_the_model() {
+        let mut splines = vec![straight_spline(2, 3), straight_spline(3, 3), straight_spline(2, 4)];
+        splines[0].bundle = true;
+        splines[0].name = "bundle".to_string();
+        splines[1].name = "model".to_string();
+        let zip_bytes = construct_zip(&splines, false, ExportOrigin::FirstPoint).unwrap();
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(zip_bytes)).unwrap();
+        assert!(zip.by_name("spline-0.smd").is_err());
+        let mut smd = Vec::new();
+        zip.by_name("spline-1.smd").unwrap().read_to_end(&mut smd).unwrap();
+        let embedded: Vec<String> = super::super::import::splines_from_model(&smd).unwrap().into_iter().map(|data| data.name).collect();
+        assert_eq!(embedded, ["model", "bundle"]);
+
+        // Every triangle of both meshes ends up in the SMD, as a material line and three vertex lines
+        let triangles = SplineMesh::counts(&splines[0], splines[0].subdivisions).1 + SplineMesh::counts(&splines[1], splines[1].subdivisions).1;
+        let smd = String::from_utf8(smd).unwrap();
+        let triangle_lines = smd.split("triangles\n").nth(1).unwrap().lines().filter(|line| *line != "end").count();
+        assert_eq!(triangle_lines, triangles * 4);
+    }
+
+    #[test]
+    fn bundles_are_merged_into_the_obj_object() {
+        let mut splines = vec![straight_spline(2, 3), straight_spline(3, 3), straight_spline(2, 4)];
+        splines[0].bundle = true;
+        let zip_bytes = construct_obj_zip(&splines, ObjUpAxis::Z).unwrap();
+
+        let mut obj = String::new();
+        zip::ZipArchive::new(Cursor::new(zip_bytes)).unwrap().by_name("splines.obj").unwrap().read_to_string(&mut obj).unwrap();
+        let objects: Vec<&str> = obj.lines().filter_map(|line| line.strip_prefix("o ")).collect();
+        assert_eq!(objects, ["spline-1", "spline-2"]);
+
+        // The first object has the faces of both its own spline and the bundle
+        let first_object = obj.split("o spline-2").next().unwrap();
+        let triangles = SplineMesh::counts(&splines[0], splines[0].subdivisions).1 + SplineMesh::counts(&splines[1], splines[1].subdivisions).1;
+        assert_eq!(first_object.lines().filter(|line| line.starts_with("f ")).count(), triangles);
+        assert!(first_object.contains("usemtl spline-0-point-0"));
+    }
+
+    #[test]
+    fn bundles_are_merged_into_the_gltf_mesh() {
+        let mut splines = vec![straight_spline(2, 3), straight_spline(3, 3), straight_spline(2, 4)];
+        splines[0].bundle = true;
+        splines[1].name = "model".to_string();
+        let glb = gltf_from_splines(&splines).unwrap();
+
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let gltf: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        let meshes = gltf["meshes"].as_array().unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0]["name"], "model");
+
+        // The model's mesh has the triangles of both its own spline and the bundle
+        let index_counts: Vec<u64> = meshes[0]["primitives"].as_array().unwrap().iter()
+            .map(|primitive| gltf["accessors"][primitive["indices"].as_u64().unwrap() as usize]["count"].as_u64().unwrap())
+            .collect();
+        let triangles = [&splines[1], &splines[0]].map(|data| SplineMesh::counts(data, data.subdivisions).1 as u64 * 3);
+        assert_eq!(index_counts, triangles);
+    }
+}
@@ -0,0 +1,47 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(1) center: vec3<f32>,
+    @location(2) size: f32,
+    @location(3) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // The w of the clip position is the distance along the view direction, so scaling by it keeps
+    // far away gizmos from shrinking below 1% of the view
+    let center_w = (camera.view_proj * vec4<f32>(instance.center, 1.0)).w;
+    let size = max(instance.size, center_w * 0.01);
+    out.clip_position = camera.view_proj * vec4<f32>(instance.center + model.position * size, 1.0);
+    // Light the top of the cube more than the bottom, so that it reads as a solid
+    let shade = 0.8 + 0.4 * model.position.z;
+    out.color = vec4<f32>(instance.color.rgb * shade, instance.color.a);
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
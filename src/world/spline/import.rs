@@ -0,0 +1,32 @@
+// Loading splines back from models we exported. We don't try to reconstruct the splines from the
+// mesh itself. Instead, the export embeds each spline's data in a comment that we read back here.
+use super::SplineData;
+
+use anyhow::*;
+
+// Prefix of the comment line holding the spline's data as JSON
+pub const SPLINE_DATA_COMMENT: &str = "// Spline data: ";
+
+// Whether a save looks like an exported SMD or QC rather than a spline save
+pub fn is_model(save: &[u8]) -> bool {
+    save.trim_ascii_start().starts_with(b"//")
+}
+
+// Read the splines embedded in an exported SMD or QC file. Models with bundle splines merged in
+// hold several.
+pub fn splines_from_model(model: &[u8]) -> Result<Vec<SplineData>> {
+    let model = std::str::from_utf8(model)?;
+    let mut splines = Vec::new();
+    for json in model.lines().filter_map(|line| line.trim().strip_prefix(SPLINE_DATA_COMMENT)) {
+        let mut spline_data: SplineData = serde_json::from_str(json)?;
+
+        // The bundle the spline was attached to is in another save, so we keep the points where
+        // they ended up instead
+        for point in spline_data.points.iter_mut() {
+            point.bundle_ref = None;
+        }
+        splines.push(spline_data);
+    }
+    ensure!(!splines.is_empty(), "model has no embedded spline data, it may have been exported by an older version");
+    Ok(splines)
+}
@@ -1,29 +1,66 @@
 pub mod export;
+pub mod import;
 
 use crate::texture;
-use crate::RenderState;
 use crate::Vertex;
 use crate::world::camera::Camera;
+use crate::keybinds::{Action, Keybinds};
 
 use cgmath::prelude::*;
 use cgmath::{Deg, Rad, Point3, Vector3};
 use egui::{Color32, Rgba};
-use serde::{Serialize, Deserialize};
+use egui::ecolor::Hsva;
+use serde::{Serialize, Deserialize, Deserializer};
+use std::borrow::Cow;
 use std::cell::{RefCell, Ref};
+use std::collections::BTreeSet;
 use winit::event::*;
-use winit::keyboard::{Key, NamedKey};
 use wgpu::util::DeviceExt;
 
+// Fewest sides a tube can have while still enclosing any volume
+pub const MIN_SIDES: u32 = 3;
+
 // Because WebGL has a limit of 16KB per uniform, and we store 16 bytes per control point color,
 // the max number of control points per spline we can have is 1024
 const MAX_POINTS_PER_SPLINE: usize = 1024;
 
+// Color blended into the control point under the cursor
+const HOVER_COLOR: Color32 = Color32::YELLOW;
+
+// Minimum size of a control point when picking, relative to its distance from the camera
+const PICK_RADIUS_PER_DISTANCE: f32 = 0.02;
+
+// How much coarser our far away LOD mesh is compared to the render mesh
+const LOD_SUBDIVISION_DIVISOR: u32 = 4;
+
+// Limit on how many times adaptive subdivision halves a segment, so at most 256 subdivisions
+const ADAPTIVE_MAX_DEPTH: u32 = 8;
+
+// Edge length of a control point gizmo relative to the tube radius, so that it pokes out of the tube
+const GIZMO_SIZE_PER_RADIUS: f32 = 2.5;
+// How much bigger the gizmos of selected points are
+const SELECTED_GIZMO_SCALE: f32 = 1.5;
+
+// Unit cube drawn at every control point, wound CCW from outside
+const GIZMO_VERTICES: [[f32; 3]; 8] = [
+    [-0.5, -0.5, -0.5], [0.5, -0.5, -0.5], [0.5, 0.5, -0.5], [-0.5, 0.5, -0.5],
+    [-0.5, -0.5, 0.5], [0.5, -0.5, 0.5], [0.5, 0.5, 0.5], [-0.5, 0.5, 0.5],
+];
+const GIZMO_INDICES: [u16; 36] = [
+    0, 2, 1, 0, 3, 2, // Bottom
+    4, 5, 6, 4, 6, 7, // Top
+    0, 1, 5, 0, 5, 4, // Front
+    2, 3, 7, 2, 7, 6, // Back
+    1, 2, 6, 1, 6, 5, // Right
+    3, 0, 4, 3, 4, 7, // Left
+];
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SplineVertex {
-    position: [f32; 3],
-    normal: [f32; 3],
-    t_value: f32,
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub t_value: f32, // Index of the segment's starting point plus how far along the segment we are
 }
 
 impl SplineVertex {
@@ -40,16 +77,71 @@ impl crate::Vertex for SplineVertex {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GizmoVertex {
+    position: [f32; 3],
+}
+
+impl GizmoVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+}
+
+impl crate::Vertex for GizmoVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &GizmoVertex::ATTRIBS,
+        }
+    }
+}
+
+// Per-instance data of the gizmo drawn at each control point. Entity markers reuse the gizmos.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GizmoInstance {
+    pub center: [f32; 3], // Relative to the render origin
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl GizmoInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![1 => Float32x3, 2 => Float32, 3 => Float32x4];
+}
+
+impl crate::Vertex for GizmoInstance {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &GizmoInstance::ATTRIBS,
+        }
+    }
+}
+
 // Container so that we can serialize/deserialize for saving splines
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SplineData {
     pub points: Vec<SplineControlPoint>,
-    pub radius: f32,
+    pub radius: f32, // Multiplied by each point's own radius
     pub sides: u32,
-    pub subdivisions: u32,
+    pub subdivisions: u32, // Subdivisions used for the exported model
+    #[serde(default = "render_subdivisions_default", deserialize_with = "deserialize_subdiv_mode")]
+    pub render_subdivisions: SubdivMode, // Subdivisions used for the viewport, so editing stays responsive
     pub name: String,
     #[serde(default = "bundle_default")]
     pub bundle: bool,
+    #[serde(default)]
+    pub color_seed: u64, // Seed used by the last color randomization, so it can be reproduced
+    #[serde(default)]
+    pub interpolation: InterpolationMode,
+    #[serde(default = "tension_default")]
+    pub tension: f32, // Scale of the derived tangents in Catmull-Rom mode
+    #[serde(default)]
+    pub closed: bool, // Whether the last point connects back to the first
+    #[serde(default)]
+    pub end_caps: CapStyle, // How the ends of the tube are closed off. Loops have no ends.
 }
 
 // Default functions for SplineData to support older spline JSON versions
@@ -57,39 +149,352 @@ const fn bundle_default() -> bool {
     false
 }
 
+const fn render_subdivisions_default() -> SubdivMode {
+    SubdivMode::Fixed(8)
+}
+
+const fn tension_default() -> f32 {
+    1.0
+}
+
+// Older spline JSON versions stored the render subdivisions as a plain count
+fn deserialize_subdiv_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SubdivMode, D::Error> {
+    // Binary saves were only added after the mode, and can't tell the two variants apart anyway
+    if !deserializer.is_human_readable() {
+        return SubdivMode::deserialize(deserializer);
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SavedSubdivMode {
+        Count(u32),
+        Mode(SubdivMode),
+    }
+
+    match SavedSubdivMode::deserialize(deserializer)? {
+        SavedSubdivMode::Count(subdivisions) => Ok(SubdivMode::Fixed(subdivisions)),
+        SavedSubdivMode::Mode(mode) => Ok(mode),
+    }
+}
+
+// Spline data as binary saves stored it up to save version 2, before points had their own
+// subdivisions. Binary saves can't skip over missing fields, so this must never change.
+#[derive(Deserialize)]
+pub(crate) struct SplineDataV2 {
+    points: Vec<SplineControlPointV2>,
+    radius: f32,
+    sides: u32,
+    subdivisions: u32,
+    render_subdivisions: SubdivMode,
+    name: String,
+    bundle: bool,
+    color_seed: u64,
+    interpolation: InterpolationMode,
+    tension: f32,
+    closed: bool,
+    end_caps: CapStyle,
+}
+
+impl From<SplineDataV2> for SplineData {
+    fn from(data: SplineDataV2) -> Self {
+        Self {
+            points: data.points.into_iter().map(SplineControlPoint::from).collect(),
+            radius: data.radius,
+            sides: data.sides,
+            subdivisions: data.subdivisions,
+            render_subdivisions: data.render_subdivisions,
+            name: data.name,
+            bundle: data.bundle,
+            color_seed: data.color_seed,
+            interpolation: data.interpolation,
+            tension: data.tension,
+            closed: data.closed,
+            end_caps: data.end_caps,
+        }
+    }
+}
+
+// Where the tangents at each control point come from
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum InterpolationMode {
+    // Each point's own pitch, yaw, and tangent magnitude
+    #[default]
+    Hermite,
+    // Derived from the neighboring points, so the curve passes smoothly through them
+    CatmullRom,
+}
+
+impl std::fmt::Display for InterpolationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            InterpolationMode::Hermite => write!(f, "Hermite"),
+            InterpolationMode::CatmullRom => write!(f, "Catmull-Rom"),
+        }
+    }
+}
+
+// How the ends of an open spline's tube are closed off
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CapStyle {
+    // Left open, for tubes that are meant to be seen into
+    None,
+    // A flat disc across the end ring
+    #[default]
+    Flat,
+    // A hemisphere bulging out past the end ring
+    Rounded,
+}
+
+impl std::fmt::Display for CapStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CapStyle::None => write!(f, "None"),
+            CapStyle::Flat => write!(f, "Flat"),
+            CapStyle::Rounded => write!(f, "Rounded"),
+        }
+    }
+}
+
+impl CapStyle {
+    // Steps from the end ring to the tip of a rounded cap. Matching the tube's angle between
+    // sides keeps the cap about as smooth as the tube.
+    fn rounded_steps(sides: u32) -> u32 {
+        (sides / 4).max(1)
+    }
+
+    // The (vertex, triangle) counts that one cap adds on top of the tube's rings
+    fn counts(self, sides: u32) -> (usize, usize) {
+        let sides_usize = sides as usize;
+        match self {
+            CapStyle::None => (0, 0),
+            CapStyle::Flat => (0, sides_usize.saturating_sub(2)),
+            CapStyle::Rounded => {
+                let steps = CapStyle::rounded_steps(sides) as usize;
+                ((steps - 1) * sides_usize + 1, 2 * sides_usize * (steps - 1) + sides_usize)
+            },
+        }
+    }
+}
+
+impl SplineData {
+    // Hand-edited or corrupted saves can contain points we can't build a mesh from, so fix up
+    // anything like that after loading
+    pub fn sanitize(&mut self) {
+        if self.sides < MIN_SIDES {
+            log::warn!("spline \"{}\" had {} sides, raised to {}", self.name, self.sides, MIN_SIDES);
+            self.sides = MIN_SIDES;
+        }
+        let point_count = self.points.len();
+        self.points.retain(|p| p.is_finite());
+        if self.points.len() != point_count {
+            log::warn!("dropped {} points with non-finite values from spline \"{}\"", point_count - self.points.len(), self.name);
+        }
+        if self.points.len() > MAX_POINTS_PER_SPLINE {
+            log::warn!("spline \"{}\" has {} points, so the points past {MAX_POINTS_PER_SPLINE} share a color", self.name, self.points.len());
+        }
+    }
+
+    // Whether there's a tube to build a mesh for, which takes at least one segment and enough
+    // sides to enclose something
+    pub fn has_tube(&self) -> bool {
+        self.points.len() >= 2 && self.sides >= MIN_SIDES
+    }
+
+    // Whether the spline forms a loop. A single point has nothing to loop back to.
+    fn is_loop(&self) -> bool {
+        self.closed && self.points.len() > 1
+    }
+
+    // Number of segments between control points, including the one back to the start of a loop
+    pub fn segment_count(&self) -> usize {
+        if self.is_loop() {
+            self.points.len()
+        }
+        else {
+            self.points.len().saturating_sub(1)
+        }
+    }
+
+    // Position and tangent direction at t, where [0, 1] covers the whole spline with each segment
+    // taking an equal share regardless of its length. A spline without points sits at the origin
+    // facing +X.
+    pub fn sample(&self, t: f32) -> (Point3<f32>, Vector3<f32>) {
+        let points = self.resolved_points();
+        if points.len() < 2 {
+            return points.first()
+                .map(|point| (point.position, point.tangent_direction()))
+                .unwrap_or((Point3::origin(), Vector3::unit_x()));
+        }
+
+        let segments = self.segment_count();
+        let scaled_t = t.clamp(0.0, 1.0) * segments as f32;
+        let segment = (scaled_t.floor() as usize).min(segments - 1);
+        let local_t = scaled_t - segment as f32;
+        let start = &points[segment];
+        let end = &points[(segment + 1) % points.len()];
+        (start.interpolate(end, local_t), start.interp_tangent_dir(end, local_t))
+    }
+
+    // The first segment whose shape may differ from previous, so that the frames and mesh before it
+    // can be kept. Changing a point reshapes the segments on either side of it. Gives None when
+    // the whole spline needs rebuilding.
+    fn first_changed_segment(&self, previous: &SplineData) -> Option<usize> {
+        // Loops spread their twist correction over every frame, so any change touches all of them
+        if self.is_loop() || previous.is_loop() || !previous.has_tube() {
+            return None;
+        }
+        // Per-segment subdivision overrides are scaled by the export subdivisions, see SubdivMode::for_segment
+        if self.radius != previous.radius || self.sides != previous.sides || self.subdivisions != previous.subdivisions || self.render_subdivisions != previous.render_subdivisions || self.end_caps != previous.end_caps {
+            return None;
+        }
+
+        // Compare the resolved points, so that Catmull-Rom tangents moved by a neighbor count too
+        let points = self.resolved_points();
+        let previous_points = previous.resolved_points();
+        let first_changed = points.iter().zip(previous_points.iter())
+            .position(|(point, previous_point)| !point.same_shape(previous_point))
+            .unwrap_or(points.len().min(previous_points.len()));
+        Some(first_changed.saturating_sub(1))
+    }
+
+    // The control points with the tangents that the curve actually uses. In Catmull-Rom mode, the
+    // tangent at point i is (P[i+1] - P[i-1]) * 0.5 * tension, with one-sided differences at the
+    // endpoints. Loops have no endpoints, so their neighbors wrap around instead. The derived tangents are stored back as pitch/yaw/magnitude so that everything
+    // downstream (subdivision, RMF frames) works the same in either mode.
+    fn resolved_points(&self) -> Cow<'_, [SplineControlPoint]> {
+        if self.interpolation == InterpolationMode::Hermite || self.points.len() < 2 {
+            return Cow::Borrowed(&self.points);
+        }
+
+        let last = self.points.len() - 1;
+        let mut points = self.points.clone();
+        for (i, point) in points.iter_mut().enumerate() {
+            let tangent;
+            if self.is_loop() {
+                let previous = &self.points[(i + last) % self.points.len()];
+                let next = &self.points[(i + 1) % self.points.len()];
+                tangent = (next.position - previous.position) * 0.5 * self.tension;
+            }
+            else if i == 0 {
+                tangent = (self.points[1].position - self.points[0].position) * self.tension;
+            }
+            else if i == last {
+                tangent = (self.points[last].position - self.points[last - 1].position) * self.tension;
+            }
+            else {
+                tangent = (self.points[i + 1].position - self.points[i - 1].position) * 0.5 * self.tension;
+            }
+            point.handles = None;
+            point.aim_along(tangent);
+            point.tangent_magnitude = tangent.magnitude();
+        }
+        Cow::Owned(points)
+    }
+}
+
+// How each segment between two control points gets subdivided
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SubdivMode {
+    // The same number of subdivisions for every segment
+    Fixed(u32),
+    // Keep subdividing until the mesh deviates from the curve by at most this many units
+    Adaptive(f32),
+}
+
+impl SubdivMode {
+    // A mode producing roughly factor times fewer subdivisions
+    fn coarser(self, factor: u32) -> Self {
+        match self {
+            SubdivMode::Fixed(subdivisions) => SubdivMode::Fixed((subdivisions / factor).max(1)),
+            SubdivMode::Adaptive(tolerance) => SubdivMode::Adaptive(tolerance * factor as f32),
+        }
+    }
+
+    // The mode to use for a segment whose starting point overrides the spline's export
+    // subdivisions. Fixed modes get scaled by the same ratio as the override, so exporting uses
+    // the override exactly while the viewport and LOD keep their relative density. Adaptive
+    // subdivision already follows the curve, so it ignores overrides.
+    fn for_segment(self, subdivisions_override: Option<u32>, export_subdivisions: u32) -> Self {
+        match (self, subdivisions_override) {
+            (SubdivMode::Fixed(subdivisions), Some(segment_subdivisions)) => {
+                let scaled = (segment_subdivisions as f32 * subdivisions as f32 / export_subdivisions.max(1) as f32).round() as u32;
+                SubdivMode::Fixed(scaled.max(1))
+            },
+            _ => self,
+        }
+    }
+
+    // The t values in [0, 1) to place subdivisions at between start and end
+    fn segment_samples(self, start: &SplineControlPoint, end: &SplineControlPoint) -> Vec<f32> {
+        match self {
+            SubdivMode::Fixed(subdivisions) => {
+                (0..subdivisions).map(|s| s as f32 / subdivisions as f32).collect()
+            },
+            SubdivMode::Adaptive(tolerance) => {
+                let mut samples = Vec::new();
+                adaptive_samples(start, end, tolerance, 0.0, 1.0, 0, &mut samples);
+                samples
+            },
+        }
+    }
+}
+
+// Recursively halve [t0, t1] until the chord between its ends is within tolerance of the curve,
+// pushing the start of every final interval. The curve is checked at several points since an
+// S-shaped interval can cross its chord exactly at the midpoint.
+fn adaptive_samples(start: &SplineControlPoint, end: &SplineControlPoint, tolerance: f32, t0: f32, t1: f32, depth: u32, samples: &mut Vec<f32>) {
+    let chord_start = start.interpolate(end, t0);
+    let chord_end = start.interpolate(end, t1);
+    let chord = chord_end - chord_start;
+    let chord_length2 = chord.magnitude2();
+    let mut error: f32 = 0.0;
+    for fraction in [0.25, 0.5, 0.75] {
+        let offset = start.interpolate(end, t0 + (t1 - t0) * fraction) - chord_start;
+        let along = if chord_length2 > 0.0 { (offset.dot(chord) / chord_length2).clamp(0.0, 1.0) } else { 0.0 };
+        error = error.max((offset - chord * along).magnitude());
+    }
+
+    if error > tolerance && depth < ADAPTIVE_MAX_DEPTH {
+        let t_mid = (t0 + t1) * 0.5;
+        adaptive_samples(start, end, tolerance, t0, t_mid, depth + 1, samples);
+        adaptive_samples(start, end, tolerance, t_mid, t1, depth + 1, samples);
+    }
+    else {
+        samples.push(t0);
+    }
+}
+
 pub struct Spline {
     // Spline data
     pub data: SplineData,
     pub selected_point: u32,
+    pub multi_selection: BTreeSet<u32>, // Points shift+clicked to select alongside selected_point
+    pub hovered_point: Option<u32>, // Point under the cursor, which gets highlighted
+    pub visible: bool, // Only affects rendering, hidden splines are still exported
 
     // Representative mesh
     reconstruct_mesh: bool, // So that we only rebuild our mesh after we update the underlying points
-    vertices: Vec<SplineVertex>, // We keep a copy of the mesh on CPU for exporting
+    built: Option<(SplineData, SplineFrames)>, // What the render mesh was last built from, so edits only rebuild what changed
+    vertices: Vec<SplineVertex>, // We keep a copy of the render mesh on CPU
     indices: Vec<u32>,
+    length: f32, // Arc length of the render mesh's centerline
+    segment_lengths: Vec<f32>, // Arc length of each segment between control points
 
     // Wgpu data
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    index_count: u32,
+    buffers: SplineBuffers,
+    lod_buffers: SplineBuffers, // Reduced detail mesh for when the spline is far away
+    control_polygon_buffers: SplineBuffers, // Straight lines connecting the control points
+    render_origin: Vector3<f32>, // Subtracted from every vertex position on the GPU
+    bounds_center: Point3<f32>, // Center of the mesh's bounding box, used to pick our LOD
+    bounds: Option<(Point3<f32>, Point3<f32>)>, // Min and max corners of the mesh's bounding box
     point_colors_buffer: wgpu::Buffer,
     point_colors_bind_group: wgpu::BindGroup,
+    gizmo_instance_buffer: wgpu::Buffer, // One gizmo per control point
+    opaque: bool, // Whether every point is fully opaque, letting us cull back faces
 }
 
 impl Spline {
     pub fn new(device: &wgpu::Device, renderer: &SplineRenderer) -> Self {
-        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Spline Vertex Buffer"),
-            size: 0,
-            usage: wgpu::BufferUsages::VERTEX,
-            mapped_at_creation: false,
-        });
-        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Spline Index Buffer"),
-            size: 0,
-            usage: wgpu::BufferUsages::INDEX,
-            mapped_at_creation: false,
-        });
-
         let point_colors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Point Colors Buffer"),
             contents: bytemuck::cast_slice(&[0.0 as f32; MAX_POINTS_PER_SPLINE * 4]),
@@ -107,6 +512,12 @@ impl Spline {
             label: Some("point_colors_bind_group"),
         });
 
+        let gizmo_instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gizmo Instance Buffer"),
+            size: (std::mem::size_of::<GizmoInstance>() * MAX_POINTS_PER_SPLINE) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         Spline {
             data: SplineData {
@@ -114,87 +525,177 @@ impl Spline {
                 radius: 4.0,
                 sides: 3,
                 subdivisions: 16,
+                render_subdivisions: render_subdivisions_default(),
                 name: "".to_string(),
                 bundle: false,
+                color_seed: 0,
+                interpolation: InterpolationMode::Hermite,
+                tension: tension_default(),
+                closed: false,
+                end_caps: CapStyle::default(),
             },
             selected_point: 0,
+            multi_selection: BTreeSet::new(),
+            hovered_point: None,
+            visible: true,
 
             reconstruct_mesh: false,
+            built: None,
             vertices: Vec::new(),
             indices: Vec::new(),
 
-            vertex_buffer,
-            index_buffer,
-            index_count: 0,
+            buffers: SplineBuffers::empty(device),
+            lod_buffers: SplineBuffers::empty(device),
+            control_polygon_buffers: SplineBuffers::empty(device),
+            render_origin: Vector3::zero(),
+            bounds_center: Point3::new(0.0, 0.0, 0.0),
+            bounds: None,
             point_colors_buffer,
             point_colors_bind_group,
+            gizmo_instance_buffer,
+            opaque: true,
+            length: 0.0,
+            segment_lengths: Vec::new(),
+        }
+    }
+
+    pub fn distance_to(&self, position: Point3<f32>) -> f32 {
+        self.bounds_center.distance(position)
+    }
+
+    // Rebuild our buffers relative to origin if it has changed
+    pub fn set_render_origin(&mut self, origin: Vector3<f32>) {
+        if self.render_origin != origin {
+            self.render_origin = origin;
+            self.request_rebuild();
+        }
+    }
+
+    // Bounding box of the spline's mesh as of the last rebuild, or None if it has no mesh
+    pub fn bounds(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        self.bounds
+    }
+
+    // Total arc length as of the last rebuild, measured along the render subdivisions
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    // Arc length of each segment as of the last rebuild. Segment i starts at point i.
+    pub fn segment_lengths(&self) -> &[f32] {
+        &self.segment_lengths
+    }
+
+    // (vertex, triangle) counts of the render mesh as of the last rebuild
+    pub fn mesh_counts(&self) -> (usize, usize) {
+        (self.vertices.len(), self.indices.len() / 3)
+    }
+
+    // Position and tangent direction at t, where [0, 1] covers the whole spline, see
+    // SplineData::sample
+    pub fn sample(&self, t: f32) -> (Point3<f32>, Vector3<f32>) {
+        self.data.sample(t)
+    }
+
+    // Evaluate the position and tangent direction at global_t, like sample, but returns None if
+    // the spline has no points.
+    pub fn evaluate(&self, global_t: f32) -> Option<(Point3<f32>, Vector3<f32>)> {
+        if self.data.points.is_empty() {
+            return None;
         }
+        Some(self.sample(global_t))
+    }
+
+    // Like evaluate, but at distance units along the spline so that evenly spaced distances give
+    // evenly spaced positions. Distances are measured along the frames of the last rebuild, the
+    // same as length(), so playback doesn't rebuild them every frame. Distances past either end
+    // are clamped to it.
+    pub fn sample_by_arc_length(&self, distance: f32) -> Option<(Point3<f32>, Vector3<f32>)> {
+        let Some((built_data, frames)) = &self.built else {
+            // Without a tube there's no length to go along
+            return self.evaluate(0.0);
+        };
+        self.evaluate(arc_length_t(built_data, frames, distance))
+    }
+
+    // Position and tangent direction of the selected point, or None if the append cursor is
+    // selected instead of a point
+    pub fn selected_point_frame(&self) -> Option<(Point3<f32>, Vector3<f32>)> {
+        let points = self.data.resolved_points();
+        let point = points.get(self.selected_point as usize)?;
+        Some((point.position, point.tangent_direction()))
+    }
+
+    // Every selected point that exists, in order. Points can get removed while selected, so
+    // multi_selection may hold stale indices that we skip.
+    pub fn selected_points(&self) -> Vec<usize> {
+        let mut selected: BTreeSet<u32> = self.multi_selection.iter().copied().collect();
+        selected.insert(self.selected_point);
+        selected.into_iter().map(|i| i as usize).filter(|i| *i < self.data.points.len()).collect()
     }
 
     pub fn request_rebuild(&mut self) {
-        // Update will perform the actual mesh rebuilding
-        // For now, we'll just reconstruct the entire mesh on request. We could make this more
-        // efficient by only reconstructing the mesh at the updated location. Maybe in the future.
+        // Update will perform the actual mesh rebuilding, redoing only the part of the mesh from
+        // the first changed point onward
         self.reconstruct_mesh = true;
     }
 
-    pub fn process_events(&mut self, event: &WindowEvent, camera: &Camera) -> bool {
+    // With extend_selection, stepping through points adds them to the selection instead of
+    // replacing it, so holding shift selects a range. placement gives where placed points go, and
+    // is only called when a point actually gets placed.
+    pub fn process_events(&mut self, event: &WindowEvent, camera: &Camera, tangent_step: f32, placement: impl Fn() -> Point3<f32>, keybinds: &Keybinds, extend_selection: bool) -> bool {
         match event {
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
                     state,
-                    logical_key,
+                    physical_key,
                     repeat,
                     ..
                 },
                 ..
-            } if *state == ElementState::Pressed && *repeat == false => {
-                match logical_key.as_ref() {
-                    Key::Named(NamedKey::Space) => {
-                        let mut new_point = SplineControlPoint {
-                            position: camera.position.map(|c| c.round()),
-                            pitch: camera.pitch.into(),
-                            yaw: camera.yaw.into(),
-                            roll: Deg(0.0),
-                            tangent_magnitude: 512.0,
-                            color: Color32::WHITE,
-                            bundle_ref: None,
-                            bundle_positions: Vec::new(),
-                        };
-
+            } if *state == ElementState::Pressed => {
+                match keybinds.action(physical_key) {
+                    // Tangent adjustments repeat when held so that you can feel out the curve
+                    Some(Action::IncreaseTangent) => {
+                        self.adjust_tangent_magnitude(tangent_step);
+                        true
+                    },
+                    Some(Action::DecreaseTangent) => {
+                        self.adjust_tangent_magnitude(-tangent_step);
+                        true
+                    },
+                    _ if *repeat => false,
+                    Some(Action::PlacePoint) => {
                         if self.selected_point == self.data.points.len() as u32 {
-                            // Set the roll, tangent magnitude, and color to be that of the previous point (if there is one)
-                            if self.selected_point != 0 {
-                                new_point.roll = self.data.points[self.selected_point as usize - 1].roll;
-                                new_point.tangent_magnitude = self.data.points[self.selected_point as usize - 1].tangent_magnitude;
-                                new_point.color = self.data.points[self.selected_point as usize - 1].color;
-                            }
-
-                            // Append a new control point to the end of the spline at the camera
-                            self.data.points.push(new_point);
+                            // Append a new control point to the end of the spline
+                            let new_point = self.append_preview(camera, placement());
+                            self.push_point(new_point);
                         }
                         else {
-                            // Set the roll, tangent magnitude, and color to be the same as the point we are replacing
+                            let mut new_point = SplineControlPoint::placed(placement(), camera);
+
+                            // Set the roll, tangent magnitude, color, and radius to be the same as the point we are replacing
                             new_point.roll = self.data.points[self.selected_point as usize - 1].roll;
                             new_point.tangent_magnitude = self.data.points[self.selected_point as usize].tangent_magnitude;
                             new_point.color = self.data.points[self.selected_point as usize].color;
+                            new_point.radius = self.data.points[self.selected_point as usize].radius;
 
                             // Replace the point currently selected with our new point
                             self.data.points[self.selected_point as usize] = new_point;
+                            self.selected_point += 1;
+                            self.request_rebuild();
                         }
-                        self.selected_point += 1;
-                        self.request_rebuild();
                         true
                     },
-                    Key::Named(NamedKey::ArrowLeft) => {
+                    Some(Action::SelectPrevious) => {
                         if self.selected_point != 0 {
-                            self.selected_point -= 1;
+                            self.step_selection(self.selected_point - 1, extend_selection);
                         }
                         true
                     },
-                    Key::Named(NamedKey::ArrowRight) => {
+                    Some(Action::SelectNext) => {
                         if self.selected_point < self.data.points.len() as u32 {
-                            self.selected_point += 1;
+                            self.step_selection(self.selected_point + 1, extend_selection);
                         }
                         true
                     },
@@ -205,7 +706,20 @@ impl Spline {
         }
     }
 
-    pub fn update(&mut self, splines: &[RefCell<Spline>], render_state: &RenderState) {
+    // The point that placing a point would append at the given position. It takes its roll,
+    // tangent magnitude, and color from the previous point, if there is one.
+    pub fn append_preview(&self, camera: &Camera, position: Point3<f32>) -> SplineControlPoint {
+        let mut new_point = SplineControlPoint::placed(position, camera);
+        if let Some(previous_point) = self.data.points.last() {
+            new_point.roll = previous_point.roll;
+            new_point.tangent_magnitude = previous_point.tangent_magnitude;
+            new_point.color = previous_point.color;
+            new_point.radius = previous_point.radius;
+        }
+        new_point
+    }
+
+    pub fn update(&mut self, splines: &[RefCell<Spline>], device: &wgpu::Device, queue: &wgpu::Queue) {
         // Update any bundled points
         let mut rebuild = false;
         for point in self.data.points.iter_mut() {
@@ -235,6 +749,10 @@ impl Spline {
                     point.tangent_magnitude = bundle_point.tangent_magnitude;
                     rebuild = true;
                 }
+                if point.handles != bundle_point.handles {
+                    point.handles = bundle_point.handles;
+                    rebuild = true;
+                }
             }
         }
         if rebuild {
@@ -242,214 +760,457 @@ impl Spline {
         }
 
         if self.reconstruct_mesh {
-            self.vertices = Vec::new();
-            self.indices = Vec::new();
-            // Processing relies on at least one point, so skip if we have none
-            if self.data.points.len() > 0 {
-                let subdiv_t = 1.0 / self.data.subdivisions as f32;
-                // Start by calculating the positions and tangents of our subdivisions on the spline.
-                let mut subdiv_points = Vec::new();
-                let mut subdiv_tangents = Vec::new();
-                for i in 0..(self.data.points.len() - 1) {
-                    for s in 0..self.data.subdivisions {
-                        subdiv_points.push(self.data.points[i].interpolate(&self.data.points[i + 1], subdiv_t * s as f32));
-
-                        let tangent = self.data.points[i].interp_tangent_dir(&self.data.points[i + 1], subdiv_t * s as f32);
-                        subdiv_tangents.push(tangent);
-                    }
+            // Update our bundle offsets if we are a bundling spline
+            if self.data.bundle {
+                self.update_bundle_positions();
+            }
+
+            // Measure the spline along the same subdivisions that the render mesh uses. Without a
+            // segment there's no tube to build, so we leave the mesh empty.
+            let mesh;
+            if !self.data.has_tube() {
+                mesh = SplineMesh::new(&self.data, self.data.render_subdivisions);
+                self.segment_lengths = Vec::new();
+                self.built = None;
+            }
+            else {
+                // Each frame is transported from the one before it, so everything from the first
+                // changed segment to the end has to be redone, but the rings before it can stay
+                let previous = self.built.take();
+                let first_segment = previous.as_ref().and_then(|(previous_data, _)| self.data.first_changed_segment(previous_data));
+                let frames;
+                let first_ring;
+                if let (Some((_, mut previous_frames)), Some(first_segment)) = (previous, first_segment) {
+                    first_ring = previous_frames.rebuild_from(&self.data, self.data.render_subdivisions, first_segment);
+                    frames = previous_frames;
                 }
-                subdiv_points.push(self.data.points[self.data.points.len() - 1].position);
-                subdiv_tangents.push(self.data.points[self.data.points.len() - 1].calculate_tangent().normalize());
-
-                // Calculate the normals and binormals from the tangents of each subdivision.
-                // We calculate the rotation-minimizing (Bishop) frame using the double reflection method:
-                // https://www.microsoft.com/en-us/research/wp-content/uploads/2016/12/Computation-of-rotation-minimizing-frames.pdf
-                //
-                // NOTE: the RMF is a standard choice of frame, but it might be useful to consider this other
-                // method of generating frames to use additional objectives, such as keeping oriented with the Z-axis:
-                // https://onlinelibrary.wiley.com/doi/10.1111/cgf.14979
-                let mut subdiv_normals = Vec::new();
-                let mut subdiv_binormals = Vec::new();
-                subdiv_normals.push(Vector3::unit_z().cross(subdiv_tangents[0]).normalize());
-                subdiv_binormals.push(subdiv_tangents[0].cross(subdiv_normals[0]));
-                for i in 1..subdiv_points.len() {
-                    let reflection_vector_lh = subdiv_points[i] - subdiv_points[i-1];
-                    let normal_reflection_lh = subdiv_normals[i-1] - (2.0 / reflection_vector_lh.dot(reflection_vector_lh)) * (reflection_vector_lh.dot(subdiv_normals[i-1])) * reflection_vector_lh;
-                    let tangent_reflection_lh = subdiv_tangents[i-1] - (2.0 / reflection_vector_lh.dot(reflection_vector_lh)) * (reflection_vector_lh.dot(subdiv_tangents[i-1])) * reflection_vector_lh;
-
-                    let reflection_vector_rh = subdiv_tangents[i] - tangent_reflection_lh;
-                    let normal = normal_reflection_lh - (2.0 / reflection_vector_rh.dot(reflection_vector_rh)) * (reflection_vector_rh.dot(normal_reflection_lh)) * reflection_vector_rh;
-                    subdiv_normals.push(normal);
-                    subdiv_binormals.push(subdiv_tangents[i].cross(normal));
+                else {
+                    frames = SplineFrames::new(&self.data, self.data.render_subdivisions);
+                    first_ring = 0;
                 }
+                mesh = SplineMesh::from_frames_reusing(&self.data, &frames, first_ring, std::mem::take(&mut self.vertices));
+                self.segment_lengths = frames.segment_lengths(&self.data);
+                self.built = Some((self.data.clone(), frames));
+            }
+            self.length = self.segment_lengths.iter().sum();
+            self.vertices = mesh.vertices;
+            self.indices = mesh.indices;
 
-                // Update our bundle offsets if we are a bundling spline
-                if self.data.bundle {
-                    let mut subdiv_i = 0;
-                    for point in self.data.points.iter_mut() {
-                        point.bundle_positions = Vec::new();
-                        for s in 0..self.data.sides {
-                            // Calculate the position within the normal/binormal plane of our point
-                            // We use sin_cos to form a linear combination of the normal and binormal
-                            let angle = s as f32 / self.data.sides as f32 * std::f32::consts::TAU + Rad::<f32>::from(point.roll).0;
-                            let poly_pos = angle.sin_cos();
-                            point.bundle_positions.push(point.position + (poly_pos.0 * subdiv_normals[subdiv_i] + poly_pos.1 * subdiv_binormals[subdiv_i]) * self.data.radius);
-                        }
-                        subdiv_i += self.data.subdivisions as usize;
-                    }
-                }
+            // Upload our meshes to the GPU
+            self.buffers.update(device, queue, &self.vertices, &self.indices, self.render_origin);
+            let lod_mesh = SplineMesh::new(&self.data, self.data.render_subdivisions.coarser(LOD_SUBDIVISION_DIVISOR));
+            self.lod_buffers.update(device, queue, &lod_mesh.vertices, &lod_mesh.indices, self.render_origin);
 
-                // Construct the vertices and normals for our mesh
-                for i in 0..subdiv_points.len() {
-                    for s in 0..self.data.sides {
-                        // Calculate our linearly interpolated roll value from the nearest control points
-                        let lower_i = i as u32 / self.data.subdivisions;
-                        let t_value = i as f32 * subdiv_t;
-                        let inbetween_t = t_value - lower_i as f32;
-                        let roll;
-                        if lower_i == self.data.points.len() as u32 - 1 {
-                            // On the last point of our chain, so we can't interpolate with the
-                            // next point over. Thankfully, we don't need to interpolate at all.
-                            roll = self.data.points[lower_i as usize].roll;
-                        }
-                        else {
-                            roll = self.data.points[lower_i as usize].roll * (1.0 - inbetween_t) + self.data.points[lower_i as usize + 1].roll * inbetween_t;
-                        }
+            let control_polygon = SplineMesh::control_polygon(&self.data);
+            self.control_polygon_buffers.update(device, queue, &control_polygon.vertices, &control_polygon.indices, self.render_origin);
 
-                        // Calculate the position within the normal/binormal plane of our point
-                        // We use sin_cos to form a linear combination of the normal and binormal
-                        let angle = s as f32 / self.data.sides as f32 * std::f32::consts::TAU + Rad::<f32>::from(roll).0;
-                        let poly_pos = angle.sin_cos();
-                        let offset_dir = poly_pos.0 * subdiv_normals[i] + poly_pos.1 * subdiv_binormals[i];
-                        let position = subdiv_points[i] + offset_dir * self.data.radius;
-
-                        // Calculate the angle-weighted normal of our vertex
-                        let angle_weighted_normal;
-                        if i == 0 || i == subdiv_points.len() - 1 {
-                           // Special case: the first/last subdivision have endcaps, so we need to
-                           // include those in the angle-weighted normal
-                           let endcap_angle = std::f32::consts::PI - (std::f32::consts::TAU / self.data.sides as f32);
-                           let endcap_normal;
-                           if i == 0 {
-                              // Normal of starting endcap points opposite the tangent direction
-                              endcap_normal = subdiv_tangents[i];
-                           }
-                           else {
-                              // Normal of starting endcap points in the tangent direction
-                              endcap_normal = -subdiv_tangents[i];
-                           }
-
-                           angle_weighted_normal = (offset_dir * std::f32::consts::PI + endcap_angle * endcap_normal).normalize();
-                        }
-                        else {
-                           // Without endcaps, the angle-weighted normal is exactly the offset direction
-                           angle_weighted_normal = offset_dir;
-                        }
+            // Find our bounding box, whose center is used for LOD selection
+            self.bounds = vertex_bounds(&self.vertices);
+            if let Some((min, max)) = self.bounds {
+                self.bounds_center = Point3::from_vec((min.to_vec() + max.to_vec()) / 2.0);
+            }
+            else if let Some(point) = self.data.points.first() {
+                // A lone point has no mesh, but still shows up as a marker
+                self.bounds_center = point.position;
+                self.bounds = Some((point.position, point.position));
+            }
 
-                        self.vertices.push(SplineVertex {
-                            position: position.into(),
-                            normal: angle_weighted_normal.into(),
-                            t_value,
-                        });
-                    }
-                }
+            self.reconstruct_mesh = false;
+        }
 
-                // Construct our indices to form the mesh
-                // End-cap for our first subdivision
-                for i in 1..(self.data.sides - 1) {
-                    self.indices.push(0);
-                    self.indices.push(i);
-                    self.indices.push(i + 1);
-                }
-                // Triangles between subdivisions
-                for subdiv in 0..(subdiv_points.len() - 1) {
-                    let base_i = subdiv as u32 * self.data.sides;
-                    let next_base_i = (subdiv as u32 + 1) * self.data.sides;
-                    for i in 0..self.data.sides {
-                        let next_i = (i + 1) % self.data.sides;
-                        self.indices.push(base_i + next_i);
-                        self.indices.push(base_i + i);
-                        self.indices.push(next_base_i + next_i);
-
-                        self.indices.push(base_i + i);
-                        self.indices.push(next_base_i + i);
-                        self.indices.push(next_base_i + next_i);
-                    }
-                }
-                // End-cap for our last subdivision
-                let end_base_i = (subdiv_points.len() as u32 - 1) * self.data.sides;
-                for i in 1..(self.data.sides - 1) {
-                    self.indices.push(end_base_i);
-                    self.indices.push(end_base_i + i + 1);
-                    self.indices.push(end_base_i + i);
-                }
+        // Write to the point color buffer. The gizmos are drawn at the same time.
+        self.opaque = self.data.points.iter().all(|p| p.color.a() == 255);
+        let selected_point = self.selected_point as usize;
+        let multi_selection = &self.multi_selection;
+        let (color_vec, gizmo_instances) = point_colors(
+            &self.data,
+            |i| i == selected_point || multi_selection.contains(&(i as u32)),
+            self.hovered_point,
+            self.render_origin,
+        );
+
+        // Write our colors to the GPU
+        queue.write_buffer(&self.point_colors_buffer, 0, bytemuck::cast_slice(&color_vec));
+        queue.write_buffer(&self.gizmo_instance_buffer, 0, bytemuck::cast_slice(&gizmo_instances));
+    }
+
+    // Find the closest control point hit by the given ray. Points are treated as spheres slightly
+    // larger than the tube, with a minimum size so that far away points are still easy to hit.
+    pub fn pick_point(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<u32> {
+        let mut closest = None;
+        let mut closest_distance = f32::MAX;
+        for (i, point) in self.data.points.iter().enumerate() {
+            let to_point = point.position - origin;
+            let distance = to_point.dot(direction);
+            if distance < 0.0 {
+                // Point is behind the camera
+                continue;
             }
 
-            // Build our mesh buffers for the GPU
-            let vertex_buffer = render_state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Spline Vertex Buffer"),
-                contents: bytemuck::cast_slice(&self.vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = render_state.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Spline Index Buffer"),
-                contents: bytemuck::cast_slice(&self.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
+            let pick_radius = (self.data.radius * point.radius.abs() * 1.5).max(distance * PICK_RADIUS_PER_DISTANCE);
+            let ray_offset = to_point - direction * distance;
+            if ray_offset.magnitude2() <= pick_radius * pick_radius && distance < closest_distance {
+                closest = Some(i as u32);
+                closest_distance = distance;
+            }
+        }
 
-            self.vertex_buffer = vertex_buffer;
-            self.index_buffer = index_buffer;
-            self.index_count = self.indices.len() as u32;
+        closest
+    }
 
-            self.reconstruct_mesh = false;
+    // Point every tangent at the next point, with a magnitude equal to the length of that segment.
+    // The last point has no next point, so it continues in the direction of the previous segment,
+    // unless the spline loops back to the first point.
+    pub fn aim_tangents_at_next(&mut self) {
+        if self.data.points.len() < 2 {
+            return;
         }
 
-        // Write to the point color buffer
-        // First, construct a slice of f32s representing each color at each control point
-        let mut color_vec = vec![0.0; MAX_POINTS_PER_SPLINE * 4];
-        for (i, point) in self.data.points.iter().enumerate() {
-            let mut color_rgba;
-            if i == self.selected_point as usize {
-                // Current point is selected, so set to the inverse color
-                color_rgba = Rgba::from(point.color.to_opaque());
-                color_rgba = Rgba::from_rgb(1.0 - color_rgba.r(), 1.0 - color_rgba.g(), 1.0 - color_rgba.b());
+        for i in 0..self.data.points.len() {
+            let segment;
+            if self.data.is_loop() {
+                segment = self.data.points[(i + 1) % self.data.points.len()].position - self.data.points[i].position;
+            }
+            else if i == self.data.points.len() - 1 {
+                segment = self.data.points[i].position - self.data.points[i - 1].position;
             }
             else {
-                color_rgba = Rgba::from(point.color);
+                segment = self.data.points[i + 1].position - self.data.points[i].position;
             }
-            let (r, g, b, a) = color_rgba.to_tuple();
-            color_vec[i * 4] = r;
-            color_vec[i * 4 + 1] = g;
-            color_vec[i * 4 + 2] = b;
-            color_vec[i * 4 + 3] = a;
-        }
 
-        // Write our colors to the GPU
-        render_state.queue.write_buffer(&self.point_colors_buffer, 0, bytemuck::cast_slice(&color_vec));
+            self.data.points[i].set_tangent(segment);
+        }
+        self.request_rebuild();
     }
 
-    pub fn add_before_selected(&mut self) {
-        let selected_point = &self.data.points[self.selected_point as usize];
-        let new_point = SplineControlPoint {
-            position: selected_point.position - selected_point.calculate_tangent(),
-            pitch: selected_point.pitch,
-            yaw: selected_point.yaw,
-            roll: selected_point.roll,
-            tangent_magnitude: selected_point.tangent_magnitude,
-            color: selected_point.color,
-            bundle_ref: None,
-            bundle_positions: Vec::new(),
-        };
-        self.data.points.insert(self.selected_point as usize, new_point);
+    // Turn each interior tangent towards the average direction of its incoming and outgoing
+    // chords, like a Catmull-Rom spline, so the curve has no creases. Strength blends between the
+    // current tangents at 0 and the fully smoothed ones at 1. With scale_magnitudes, the magnitudes
+    // also blend towards the average length of the two chords. Handles get unified.
+    pub fn smooth_tangents(&mut self, strength: f32, scale_magnitudes: bool) {
+        let strength = strength.clamp(0.0, 1.0);
+        for i in 0..self.data.points.len() {
+            let (Some(previous), Some(next)) = self.adjacent_positions(i) else {
+                continue;
+            };
+            let point = &mut self.data.points[i];
+            let incoming = point.position - previous;
+            let outgoing = next - point.position;
+            if incoming.magnitude2() == 0.0 || outgoing.magnitude2() == 0.0 {
+                continue;
+            }
+            let smooth_direction = incoming.normalize() + outgoing.normalize();
+            if smooth_direction.magnitude2() < 1e-6 {
+                // The spline doubles back on itself here, so there's no sensible average
+                continue;
+            }
+
+            let current_magnitude = point.calculate_tangent().magnitude();
+            let mut direction = point.tangent_direction().lerp(smooth_direction.normalize(), strength);
+            if direction.magnitude2() < 1e-6 {
+                direction = smooth_direction;
+            }
+            let mut magnitude = current_magnitude;
+            if scale_magnitudes {
+                let average_length = (incoming.magnitude() + outgoing.magnitude()) / 2.0;
+                magnitude += (average_length - current_magnitude) * strength;
+            }
 
+            if magnitude > 0.0 {
+                point.set_tangent(direction.normalize() * magnitude);
+            }
+            else {
+                point.aim_along(direction);
+            }
+        }
         self.request_rebuild();
     }
-}
 
-#[derive(Serialize, Deserialize)]
-pub struct SplineControlPoint {
-    pub position: Point3<f32>,
-    pub pitch: Deg<f32>,
+    // Positions of the points before and after index, wrapping around loops
+    pub fn adjacent_positions(&self, index: usize) -> (Option<Point3<f32>>, Option<Point3<f32>>) {
+        let num_points = self.data.points.len();
+        if index >= num_points || num_points < 2 {
+            return (None, None);
+        }
+
+        let previous;
+        let next;
+        if self.data.is_loop() {
+            previous = Some(self.data.points[(index + num_points - 1) % num_points].position);
+            next = Some(self.data.points[(index + 1) % num_points].position);
+        }
+        else {
+            previous = index.checked_sub(1).map(|i| self.data.points[i].position);
+            next = self.data.points.get(index + 1).map(|p| p.position);
+        }
+        (previous, next)
+    }
+
+    // Scale every tangent magnitude to a fraction of the average length of the point's adjacent
+    // segments, so that curves look balanced regardless of how far apart the points are.
+    pub fn scale_tangents_to_segments(&mut self, fraction: f32) {
+        if self.data.points.len() < 2 {
+            return;
+        }
+
+        let last_i = self.data.points.len() - 1;
+        for i in 0..=last_i {
+            let average_length;
+            if self.data.is_loop() {
+                let incoming = self.data.points[i].position.distance(self.data.points[(i + last_i) % (last_i + 1)].position);
+                let outgoing = self.data.points[i].position.distance(self.data.points[(i + 1) % (last_i + 1)].position);
+                average_length = (incoming + outgoing) / 2.0;
+            }
+            else if i == 0 {
+                average_length = self.data.points[0].position.distance(self.data.points[1].position);
+            }
+            else if i == last_i {
+                average_length = self.data.points[last_i].position.distance(self.data.points[last_i - 1].position);
+            }
+            else {
+                let incoming = self.data.points[i].position.distance(self.data.points[i - 1].position);
+                let outgoing = self.data.points[i].position.distance(self.data.points[i + 1].position);
+                average_length = (incoming + outgoing) / 2.0;
+            }
+
+            self.data.points[i].set_tangent_magnitude(average_length * fraction);
+        }
+        self.request_rebuild();
+    }
+
+    // Give every point a random hue, keeping its alpha. The colors are generated from the seed, so
+    // randomizing with the same seed always gives the same palette.
+    pub fn randomize_colors(&mut self, seed: u64) {
+        self.data.color_seed = seed;
+        let mut rng_state = seed;
+        for point in self.data.points.iter_mut() {
+            let hue = (splitmix64(&mut rng_state) >> 40) as f32 / (1u64 << 24) as f32;
+            let color: Color32 = Hsva::new(hue, 0.8, 1.0, 1.0).into();
+            point.color = Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), point.color.a());
+        }
+    }
+
+    fn step_selection(&mut self, new_point: u32, extend_selection: bool) {
+        if extend_selection {
+            // The end of the spline isn't a point, so it can't be part of the selection
+            if self.selected_point < self.data.points.len() as u32 {
+                self.multi_selection.insert(self.selected_point);
+            }
+            self.multi_selection.remove(&new_point);
+        }
+        else {
+            self.multi_selection.clear();
+        }
+        self.selected_point = new_point;
+    }
+
+    // Move every selected point by delta. Bundled points follow their bundle, so they are skipped.
+    pub fn translate_selection(&mut self, delta: Vector3<f32>) {
+        for i in self.selected_points() {
+            let point = &mut self.data.points[i];
+            if point.bundle_ref.is_none() {
+                point.position += delta;
+            }
+        }
+        self.request_rebuild();
+    }
+
+    // Round the position of every selected point to the grid, skipping bundled points
+    pub fn snap_selection(&mut self, grid: f32) {
+        for i in self.selected_points() {
+            let point = &mut self.data.points[i];
+            if point.bundle_ref.is_none() {
+                point.position = point.position.map(|c| (c / grid).round() * grid);
+            }
+        }
+        self.request_rebuild();
+    }
+
+    fn adjust_tangent_magnitude(&mut self, delta: f32) {
+        if let Some(point) = self.data.points.get_mut(self.selected_point as usize) {
+            let magnitude = point.handles.map_or(point.tangent_magnitude, |(_, out_handle)| out_handle.magnitude() * 3.0);
+            point.set_tangent_magnitude(magnitude + delta);
+            self.request_rebuild();
+        }
+    }
+
+    // Most copies of the run of points from first to last that fit within the points a spline
+    // can have, see duplicate_run
+    pub fn max_run_copies(&self, first: usize, last: usize) -> u32 {
+        if first >= last {
+            return 0;
+        }
+        (MAX_POINTS_PER_SPLINE.saturating_sub(self.data.points.len()) / (last - first)) as u32
+    }
+
+    // Repeat the run of points from first to last (inclusive) count times, inserting the copies
+    // right after the run. Each copy is offset by the distance from the first to the last point of
+    // the run (plus extra_offset), and skips the first point of the run since it would land on
+    // the last point of the previous copy. The count is clamped so that the spline stays within
+    // MAX_POINTS_PER_SPLINE.
+    pub fn duplicate_run(&mut self, first: usize, last: usize, count: u32, extra_offset: Vector3<f32>) {
+        if first >= last || last >= self.data.points.len() {
+            return;
+        }
+        let count = count.min(self.max_run_copies(first, last));
+
+        let offset = self.data.points[last].position - self.data.points[first].position + extra_offset;
+        let mut copies = Vec::new();
+        for copy_i in 1..=count {
+            for point in self.data.points[(first + 1)..=last].iter() {
+                let mut new_point = point.clone();
+                new_point.position += offset * copy_i as f32;
+                new_point.bundle_ref = None;
+                copies.push(new_point);
+            }
+        }
+
+        // Keep the same points selected
+        let num_copies = copies.len() as u32;
+        if self.selected_point > last as u32 {
+            self.selected_point += num_copies;
+        }
+        self.multi_selection = self.multi_selection.iter().map(|i| {
+            if *i > last as u32 {
+                i + num_copies
+            }
+            else {
+                *i
+            }
+        }).collect();
+        self.data.points.splice((last + 1)..(last + 1), copies);
+        self.request_rebuild();
+    }
+
+    fn update_bundle_positions(&mut self) {
+        // Processing relies on at least one point, so skip if we have none
+        if self.data.points.is_empty() {
+            return;
+        }
+
+        // Bundles get exported alongside this spline, so their frames need to match the exported
+        // mesh rather than whatever resolution we happen to render at
+        let frames = SplineFrames::new(&self.data, SubdivMode::Fixed(self.data.subdivisions));
+        for (point, &subdiv_i) in self.data.points.iter_mut().zip(frames.point_indices.iter()) {
+            point.bundle_positions = Vec::new();
+            for s in 0..self.data.sides {
+                // Calculate the position within the normal/binormal plane of our point
+                // We use sin_cos to form a linear combination of the normal and binormal
+                let angle = s as f32 / self.data.sides as f32 * std::f32::consts::TAU + Rad::<f32>::from(point.roll).0;
+                let poly_pos = angle.sin_cos();
+                point.bundle_positions.push(point.position + (poly_pos.0 * frames.normals[subdiv_i] + poly_pos.1 * frames.binormals[subdiv_i]) * self.data.radius * frames.radii[subdiv_i]);
+            }
+        }
+    }
+
+    // Add a point to the end of the spline. If the append cursor was selected, it stays selected
+    // so that further points keep getting appended.
+    pub fn push_point(&mut self, point: SplineControlPoint) {
+        if self.selected_point == self.data.points.len() as u32 {
+            self.selected_point += 1;
+        }
+        self.data.points.push(point);
+        self.request_rebuild();
+    }
+
+    // Insert a point before index, clamped to the end of the spline. The selection is shifted so
+    // that the same points stay selected.
+    pub fn insert_point(&mut self, index: usize, point: SplineControlPoint) {
+        let index = index.min(self.data.points.len());
+        if index as u32 <= self.selected_point {
+            self.selected_point += 1;
+        }
+        self.multi_selection = self.multi_selection.iter().map(|i| {
+            if *i >= index as u32 {
+                i + 1
+            }
+            else {
+                *i
+            }
+        }).collect();
+        self.data.points.insert(index, point);
+        self.request_rebuild();
+    }
+
+    // Remove the point at index, shifting the selection so that the same points stay selected. If
+    // the selected point is removed, the one after it gets selected instead.
+    pub fn remove_point(&mut self, index: usize) {
+        if index >= self.data.points.len() {
+            return;
+        }
+        if (index as u32) < self.selected_point {
+            self.selected_point -= 1;
+        }
+        self.multi_selection = self.multi_selection.iter().filter(|i| **i != index as u32).map(|i| {
+            if *i > index as u32 {
+                i - 1
+            }
+            else {
+                *i
+            }
+        }).collect();
+        self.data.points.remove(index);
+        self.request_rebuild();
+    }
+
+    // Move the point at from so that it ends up at index to, keeping the same points selected
+    pub fn move_point(&mut self, from: usize, to: usize) {
+        let num_points = self.data.points.len();
+        if from >= num_points || to >= num_points || from == to {
+            return;
+        }
+        let moved = |i: u32| {
+            let i = i as usize;
+            let new_i;
+            if i == from {
+                new_i = to;
+            }
+            else if from < i && i <= to {
+                new_i = i - 1;
+            }
+            else if to <= i && i < from {
+                new_i = i + 1;
+            }
+            else {
+                new_i = i;
+            }
+            new_i as u32
+        };
+        self.selected_point = moved(self.selected_point);
+        self.multi_selection = self.multi_selection.iter().map(|i| moved(*i)).collect();
+        let point = self.data.points.remove(from);
+        self.data.points.insert(to, point);
+        self.request_rebuild();
+    }
+
+    pub fn add_before_selected(&mut self) {
+        let selected_point = &self.data.points[self.selected_point as usize];
+        let new_point = SplineControlPoint {
+            position: selected_point.position - selected_point.calculate_tangent(),
+            pitch: selected_point.pitch,
+            yaw: selected_point.yaw,
+            roll: selected_point.roll,
+            tangent_magnitude: selected_point.tangent_magnitude,
+            color: selected_point.color,
+            bundle_ref: None,
+            material_override: selected_point.material_override.clone(),
+            subdivisions: selected_point.subdivisions,
+            radius: selected_point.radius,
+            handles: selected_point.handles,
+            bundle_positions: Vec::new(),
+        };
+        // The new point takes over the selection, while the rest of the selection stays on the
+        // same points
+        let index = self.selected_point as usize;
+        self.insert_point(index, new_point);
+        self.selected_point = index as u32;
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SplineControlPoint {
+    pub position: Point3<f32>,
+    pub pitch: Deg<f32>,
     pub yaw: Deg<f32>,
     #[serde(default = "roll_default")]
     pub roll: Deg<f32>,
@@ -457,6 +1218,19 @@ pub struct SplineControlPoint {
     pub color: Color32,
     #[serde(default)]
     pub bundle_ref: Option<(u32, u32, u32)>,
+    // Material the segment following this point gets exported with instead of its color, as a
+    // path relative to materials/
+    #[serde(default)]
+    pub material_override: Option<String>,
+    // Export subdivisions of the segment following this point instead of the spline's
+    #[serde(default)]
+    pub subdivisions: Option<u32>,
+    #[serde(default = "point_radius_default")]
+    pub radius: f32, // Scales the spline's radius at this point, tapering smoothly to its neighbors
+    // Incoming and outgoing Bezier handle offsets. When set, they define the tangent instead of
+    // the pitch, yaw, and tangent magnitude.
+    #[serde(default)]
+    pub handles: Option<(Vector3<f32>, Vector3<f32>)>,
 
     // Bundle helper data
     #[serde(skip)]
@@ -468,17 +1242,192 @@ const fn roll_default() -> Deg<f32> {
    Deg(0.0)
 }
 
+const fn point_radius_default() -> f32 {
+    1.0
+}
+
+// Control points as binary saves stored them up to save version 2. Like SplineDataV2, this must
+// never change.
+#[derive(Deserialize)]
+struct SplineControlPointV2 {
+    position: Point3<f32>,
+    pitch: Deg<f32>,
+    yaw: Deg<f32>,
+    roll: Deg<f32>,
+    tangent_magnitude: f32,
+    color: Color32,
+    bundle_ref: Option<(u32, u32, u32)>,
+    material_override: Option<String>,
+    radius: f32,
+    handles: Option<(Vector3<f32>, Vector3<f32>)>,
+}
+
+impl From<SplineControlPointV2> for SplineControlPoint {
+    fn from(point: SplineControlPointV2) -> Self {
+        Self {
+            position: point.position,
+            pitch: point.pitch,
+            yaw: point.yaw,
+            roll: point.roll,
+            tangent_magnitude: point.tangent_magnitude,
+            color: point.color,
+            bundle_ref: point.bundle_ref,
+            material_override: point.material_override,
+            subdivisions: None,
+            radius: point.radius,
+            handles: point.handles,
+            bundle_positions: Vec::new(),
+        }
+    }
+}
+
 impl SplineControlPoint {
+    // A white point with no roll and no bundle reference
+    pub fn new(position: Point3<f32>, pitch: Deg<f32>, yaw: Deg<f32>, tangent_magnitude: f32) -> Self {
+        SplineControlPoint {
+            position,
+            pitch,
+            yaw,
+            roll: Deg(0.0),
+            tangent_magnitude,
+            color: Color32::WHITE,
+            bundle_ref: None,
+            material_override: None,
+            subdivisions: None,
+            radius: point_radius_default(),
+            handles: None,
+            bundle_positions: Vec::new(),
+        }
+    }
+
+    // Whether every number defining the point is finite. A single NaN would turn the whole mesh
+    // into NaN vertices.
+    pub fn is_finite(&self) -> bool {
+        self.position.x.is_finite() && self.position.y.is_finite() && self.position.z.is_finite()
+            && self.pitch.0.is_finite() && self.yaw.0.is_finite() && self.roll.0.is_finite()
+            && self.tangent_magnitude.is_finite() && self.radius.is_finite()
+            && self.handles.is_none_or(|(in_handle, out_handle)| {
+                in_handle.x.is_finite() && in_handle.y.is_finite() && in_handle.z.is_finite()
+                    && out_handle.x.is_finite() && out_handle.y.is_finite() && out_handle.z.is_finite()
+            })
+    }
+
+    // Whether the point gives the tube the same shape as other, ignoring what only affects color
+    fn same_shape(&self, other: &SplineControlPoint) -> bool {
+        self.position == other.position && self.pitch == other.pitch && self.yaw == other.yaw && self.roll == other.roll
+            && self.tangent_magnitude == other.tangent_magnitude && self.radius == other.radius && self.handles == other.handles
+            && self.subdivisions == other.subdivisions
+    }
+
+    // Tangent of the segment leaving this point. A Bezier handle sits a third of the way along the
+    // equivalent hermite tangent.
     fn calculate_tangent(&self) -> Vector3<f32> {
+        if let Some((_, out_handle)) = self.handles {
+            return out_handle * 3.0;
+        }
+        self.pitch_yaw_direction() * self.tangent_magnitude
+    }
+
+    // Tangent of the segment arriving at this point, which only differs from the outgoing one when
+    // the handles are broken
+    fn incoming_tangent(&self) -> Vector3<f32> {
+        if let Some((in_handle, _)) = self.handles {
+            return in_handle * -3.0;
+        }
+        self.calculate_tangent()
+    }
+
+    // Unit direction of our tangent, which stays valid even with a zero tangent magnitude. Broken
+    // handles get the average of their two directions.
+    fn tangent_direction(&self) -> Vector3<f32> {
+        if let Some((in_handle, out_handle)) = self.handles {
+            let direction = out_handle - in_handle;
+            if direction.magnitude2() > 0.0 {
+                return direction.normalize();
+            }
+        }
+        self.pitch_yaw_direction()
+    }
+
+    fn pitch_yaw_direction(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
-        let tangent_dir = Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch);
-        tangent_dir * self.tangent_magnitude
+        Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch)
+    }
+
+    // Set the pitch and yaw so that our tangent points along direction. When pointing straight up
+    // or down the yaw is undefined, so we leave it unchanged.
+    pub fn aim_along(&mut self, direction: Vector3<f32>) {
+        let horizontal = (direction.x * direction.x + direction.y * direction.y).sqrt();
+        if horizontal == 0.0 && direction.z == 0.0 {
+            return;
+        }
+
+        self.pitch = Rad(direction.z.atan2(horizontal)).into();
+        if horizontal > 1e-6 {
+            self.yaw = Rad(direction.y.atan2(direction.x)).into();
+        }
+    }
+
+    // Turn the tangent to point along direction, keeping its magnitude. Handles get unified.
+    pub fn aim_tangent(&mut self, direction: Vector3<f32>) {
+        let magnitude = self.calculate_tangent().magnitude();
+        if magnitude > 0.0 && direction.magnitude2() > 0.0 {
+            self.set_tangent(direction.normalize() * magnitude);
+        }
+        else {
+            self.aim_along(direction);
+        }
+    }
+
+    // Radius between us and other, using the hermite basis with flat tangents so that the taper
+    // eases in and out of every point
+    fn interpolate_radius(&self, other: &SplineControlPoint, t: f32) -> f32 {
+        let t2 = t*t;
+        let t3 = t*t2;
+        (2.0*t3 - 3.0*t2 + 1.0) * self.radius + (-2.0*t3 + 3.0*t2) * other.radius
+    }
+
+    // Switch between defining the tangent with Bezier handles or with pitch, yaw, and magnitude,
+    // keeping the curve the same
+    pub fn set_handles_enabled(&mut self, enabled: bool) {
+        if enabled && self.handles.is_none() {
+            let handle = self.calculate_tangent() / 3.0;
+            self.handles = Some((-handle, handle));
+        }
+        else if !enabled {
+            if let Some((_, out_handle)) = self.handles.take() {
+                self.aim_along(out_handle);
+                self.tangent_magnitude = out_handle.magnitude() * 3.0;
+            }
+        }
+    }
+
+    // Set the length of the outgoing tangent. Handles keep their directions and relative lengths.
+    fn set_tangent_magnitude(&mut self, magnitude: f32) {
+        self.tangent_magnitude = magnitude;
+        if let Some((in_handle, out_handle)) = &mut self.handles {
+            let current_magnitude = out_handle.magnitude() * 3.0;
+            if current_magnitude > 0.0 {
+                let scale = magnitude / current_magnitude;
+                *in_handle *= scale;
+                *out_handle *= scale;
+            }
+        }
+    }
+
+    // Point the tangent along tangent, with its length as the magnitude. Handles get unified.
+    fn set_tangent(&mut self, tangent: Vector3<f32>) {
+        self.aim_along(tangent);
+        self.tangent_magnitude = tangent.magnitude();
+        if self.handles.is_some() {
+            self.handles = Some((-tangent / 3.0, tangent / 3.0));
+        }
     }
 
     fn interpolate(&self, other: &SplineControlPoint, t: f32) -> Point3<f32> {
         let tangent_s = self.calculate_tangent();
-        let tangent_o = other.calculate_tangent();
+        let tangent_o = other.incoming_tangent();
         let pos_s = self.position.to_vec();
         let pos_o = other.position.to_vec();
         // Interpolate using cubic hermite spline formula for 2 points
@@ -490,7 +1439,7 @@ impl SplineControlPoint {
     // Used to calculate tangent for inbetween points
     fn interp_tangent_dir(&self, other: &SplineControlPoint, t: f32) -> Vector3<f32> {
         let tangent_s = self.calculate_tangent();
-        let tangent_o = other.calculate_tangent();
+        let tangent_o = other.incoming_tangent();
         let pos_s = self.position.to_vec();
         let pos_o = other.position.to_vec();
         // Tangent can be calculated as the derivative of our above formula w.r.t t.
@@ -498,33 +1447,627 @@ impl SplineControlPoint {
         ((6.0*t2 - 6.0*t) * (pos_s - pos_o) + (3.0*t2 - 4.0*t + 1.0) * tangent_s + (3.0*t2 - 2.0*t) * tangent_o).normalize()
     }
 
-    pub fn blank() -> Self {
-        Self{
-            position: cgmath::Point3::new(0.0, 0.0, 0.0),
-            pitch: Deg(0.0),
-            yaw: Deg(0.0),
-            roll: Deg(0.0),
-            tangent_magnitude: 0.0,
-            color: Color32::WHITE,
-            bundle_ref: None,
-            bundle_positions: Vec::new(),
+    // A new point at the given position, aimed the way the camera looks
+    fn placed(position: Point3<f32>, camera: &Camera) -> Self {
+        SplineControlPoint::new(position, camera.pitch.into(), camera.yaw.into(), 512.0)
+    }
+}
+
+fn vertex_bounds(vertices: &[SplineVertex]) -> Option<(Point3<f32>, Point3<f32>)> {
+    let first = vertices.first()?;
+    let mut min = Vector3::from(first.position);
+    let mut max = min;
+    for vertex in vertices.iter() {
+        let position = Vector3::from(vertex.position);
+        min = Vector3::new(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+        max = Vector3::new(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+    }
+    Some((Point3::from_vec(min), Point3::from_vec(max)))
+}
+
+// Small, fast PRNG that is good enough for picking colors: https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Positions and rotation-minimizing frames of every subdivision (ring) along a spline
+struct SplineFrames {
+    points: Vec<Point3<f32>>,
+    tangents: Vec<Vector3<f32>>,
+    radii: Vec<f32>, // Radius scale of each ring, before multiplying by the spline's radius
+    normals: Vec<Vector3<f32>>,
+    binormals: Vec<Vector3<f32>>,
+    t_values: Vec<f32>, // Index of the segment plus the t value within it
+    point_indices: Vec<usize>, // Which subdivision each control point lands on
+}
+
+impl SplineFrames {
+    // A spline without points gets no frames
+    fn new(data: &SplineData, mode: SubdivMode) -> Self {
+        let mut frames = Self {
+            points: Vec::new(),
+            tangents: Vec::new(),
+            radii: Vec::new(),
+            normals: Vec::new(),
+            binormals: Vec::new(),
+            t_values: Vec::new(),
+            point_indices: Vec::new(),
+        };
+        frames.rebuild_from(data, mode, 0);
+
+        // Transporting the frame all the way around a loop generally doesn't bring it back to the
+        // first frame, which would leave a visible twist at the seam. Measure the angle between
+        // the two by transporting one step further onto the first ring, then spread the
+        // correction evenly along the loop.
+        if data.is_loop() {
+            let Self { points, tangents, normals, binormals, .. } = &mut frames;
+            let last = points.len() - 1;
+            let reflection_vector_lh = points[0] - points[last];
+            let normal_reflection_lh = normals[last] - (2.0 / reflection_vector_lh.dot(reflection_vector_lh)) * (reflection_vector_lh.dot(normals[last])) * reflection_vector_lh;
+            let tangent_reflection_lh = tangents[last] - (2.0 / reflection_vector_lh.dot(reflection_vector_lh)) * (reflection_vector_lh.dot(tangents[last])) * reflection_vector_lh;
+            let reflection_vector_rh = tangents[0] - tangent_reflection_lh;
+            let seam_normal = normal_reflection_lh - (2.0 / reflection_vector_rh.dot(reflection_vector_rh)) * (reflection_vector_rh.dot(normal_reflection_lh)) * reflection_vector_rh;
+
+            let defect = tangents[0].dot(seam_normal.cross(normals[0])).atan2(seam_normal.dot(normals[0]));
+            if defect.is_finite() {
+                for i in 1..points.len() {
+                    let (sin, cos) = (defect * i as f32 / points.len() as f32).sin_cos();
+                    normals[i] = normals[i] * cos + tangents[i].cross(normals[i]) * sin;
+                    binormals[i] = tangents[i].cross(normals[i]);
+                }
+            }
+        }
+
+        frames
+    }
+
+    // Recalculate the subdivisions from the start of first_segment onward, keeping the ones before
+    // it, and give back the index of the first recalculated one. Loops need the correction in
+    // new() afterwards, so they can't be partially rebuilt.
+    fn rebuild_from(&mut self, data: &SplineData, mode: SubdivMode, first_segment: usize) -> usize {
+        let first_ring = self.point_indices.get(first_segment).copied().unwrap_or(self.points.len());
+        self.points.truncate(first_ring);
+        self.tangents.truncate(first_ring);
+        self.radii.truncate(first_ring);
+        self.normals.truncate(first_ring);
+        self.binormals.truncate(first_ring);
+        self.t_values.truncate(first_ring);
+        self.point_indices.truncate(first_segment);
+        let Self { points, tangents, radii, normals, binormals, t_values, point_indices } = self;
+
+        // Start by calculating the positions and tangents of our subdivisions on the spline.
+        let control_points = data.resolved_points();
+        if control_points.is_empty() {
+            return first_ring;
+        }
+        for i in first_segment..data.segment_count() {
+            let start = &control_points[i];
+            let end = &control_points[(i + 1) % control_points.len()];
+            point_indices.push(points.len());
+            let segment_mode = mode.for_segment(start.subdivisions, data.subdivisions);
+            for t in segment_mode.segment_samples(start, end) {
+                points.push(start.interpolate(end, t));
+
+                let tangent = start.interp_tangent_dir(end, t);
+                tangents.push(tangent);
+                radii.push(start.interpolate_radius(end, t));
+                t_values.push(i as f32 + t);
+            }
+        }
+        // A loop's last segment ends on the first ring, so it doesn't need a ring of its own
+        if !data.is_loop() {
+            point_indices.push(points.len());
+            points.push(control_points[control_points.len() - 1].position);
+            tangents.push(control_points[control_points.len() - 1].tangent_direction());
+            radii.push(control_points[control_points.len() - 1].radius);
+            t_values.push((control_points.len() - 1) as f32);
+        }
+
+        // Calculate the normals and binormals from the tangents of each subdivision.
+        // We calculate the rotation-minimizing (Bishop) frame using the double reflection method:
+        // https://www.microsoft.com/en-us/research/wp-content/uploads/2016/12/Computation-of-rotation-minimizing-frames.pdf
+        //
+        // NOTE: the RMF is a standard choice of frame, but it might be useful to consider this other
+        // method of generating frames to use additional objectives, such as keeping oriented with the Z-axis:
+        // https://onlinelibrary.wiley.com/doi/10.1111/cgf.14979
+        if first_ring == 0 {
+            // Seed the first frame with a horizontal normal. That's undefined when starting
+            // straight up or down, so fall back to the X axis there instead of producing NaNs.
+            let mut seed_normal = Vector3::unit_z().cross(tangents[0]);
+            if seed_normal.magnitude2() < 1e-6 {
+                seed_normal = Vector3::unit_x().cross(tangents[0]);
+            }
+            normals.push(seed_normal.normalize());
+            binormals.push(tangents[0].cross(normals[0]));
+        }
+        for i in first_ring.max(1)..points.len() {
+            let reflection_vector_lh = points[i] - points[i-1];
+            let normal_reflection_lh = normals[i-1] - (2.0 / reflection_vector_lh.dot(reflection_vector_lh)) * (reflection_vector_lh.dot(normals[i-1])) * reflection_vector_lh;
+            let tangent_reflection_lh = tangents[i-1] - (2.0 / reflection_vector_lh.dot(reflection_vector_lh)) * (reflection_vector_lh.dot(tangents[i-1])) * reflection_vector_lh;
+
+            let reflection_vector_rh = tangents[i] - tangent_reflection_lh;
+            let normal = normal_reflection_lh - (2.0 / reflection_vector_rh.dot(reflection_vector_rh)) * (reflection_vector_rh.dot(normal_reflection_lh)) * reflection_vector_rh;
+            normals.push(normal);
+            binormals.push(tangents[i].cross(normal));
+        }
+
+        first_ring
+    }
+
+    // Arc length of each segment, summing the distances between its subdivisions
+    fn segment_lengths(&self, data: &SplineData) -> Vec<f32> {
+        let mut lengths = vec![0.0; data.segment_count()];
+        for i in 1..self.points.len() {
+            // Clamp in case a t value just short of the next segment rounded up to it
+            let segment = (self.t_values[i - 1] as usize).min(lengths.len() - 1);
+            lengths[segment] += self.points[i].distance(self.points[i - 1]);
+        }
+        // A loop's last segment ends back on the first ring
+        if data.is_loop() {
+            let last = self.points.len() - 1;
+            lengths[data.segment_count() - 1] += self.points[0].distance(self.points[last]);
+        }
+        lengths
+    }
+}
+
+// CPU-side tube mesh of a spline, built without touching the GPU so that it can be exported and
+// inspected on its own. The layout is relied on by the renderer and the SMD export:
+// - Vertices are stored ring by ring, with `sides` vertices per ring, so the tube always starts
+//   with `rings * sides` vertices where a ring is placed at every subdivision plus the last point.
+//   Loops end back on the first ring, so they have no ring for the last point. Rounded end-caps
+//   append their own rings after the tube's, first cap first, each followed by its tip vertex.
+// - Indices start with the first end-cap, followed by two triangles per side between each pair of
+//   rings (wrapping from the last side back to the first), and end with the last end-cap. Flat
+//   caps are a triangle fan, and rounded caps are bands like the tube's closed off by a fan to the
+//   tip. Loops have no end-caps, and instead also join the last ring to the first.
+// - Every triangle is wound CCW when viewed from outside the tube, so its geometric normal points
+//   away from the center of its ring.
+pub struct SplineMesh {
+    pub vertices: Vec<SplineVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl SplineMesh {
+    // Straight lines between the control points, drawn as a line strip with each point taking its
+    // own color. Not a tube, so none of the layout above applies.
+    pub fn control_polygon(data: &SplineData) -> Self {
+        let vertices: Vec<SplineVertex> = data.points.iter().enumerate().map(|(i, point)| SplineVertex {
+            position: point.position.into(),
+            normal: [0.0; 3],
+            t_value: i as f32,
+        }).collect();
+        let mut indices: Vec<u32> = (0..vertices.len() as u32).collect();
+        if data.is_loop() {
+            indices.push(0);
+        }
+        Self { vertices, indices }
+    }
+
+    // Bounding box of the mesh, or None if it's empty
+    pub fn bounds(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        vertex_bounds(&self.vertices)
+    }
+
+    // Subdivisions is passed separately so that we can build both the render and export meshes.
+    // Splines without a tube to build (see SplineData::has_tube) give an empty mesh.
+    pub fn new(data: &SplineData, mode: SubdivMode) -> Self {
+        if !data.has_tube() {
+            return Self {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            };
+        }
+
+        Self::from_frames(data, &SplineFrames::new(data, mode))
+    }
+
+    // The (vertex, triangle) counts that new() would produce with fixed subdivisions, without
+    // building the mesh
+    pub fn counts(data: &SplineData, subdivisions: u32) -> (usize, usize) {
+        if !data.has_tube() {
+            return (0, 0);
+        }
+
+        let sides = data.sides as usize;
+        let segment_rings: usize = data.points.iter().take(data.segment_count())
+            .map(|point| match SubdivMode::Fixed(subdivisions).for_segment(point.subdivisions, data.subdivisions) {
+                SubdivMode::Fixed(segment_subdivisions) => segment_subdivisions as usize,
+                SubdivMode::Adaptive(_) => unreachable!(),
+            })
+            .sum();
+        if data.is_loop() {
+            return (segment_rings * sides, 2 * sides * segment_rings);
+        }
+
+        let rings = segment_rings + 1;
+        let (cap_vertices, cap_triangles) = data.end_caps.counts(data.sides);
+        (rings * sides + 2 * cap_vertices, 2 * cap_triangles + 2 * sides * (rings - 1))
+    }
+
+    fn from_frames(data: &SplineData, frames: &SplineFrames) -> Self {
+        Self::from_frames_reusing(data, frames, 0, Vec::new())
+    }
+
+    // Build the mesh keeping the vertices of the rings before first_ring, which must come from a
+    // mesh built with the same frames up to that ring
+    fn from_frames_reusing(data: &SplineData, frames: &SplineFrames, first_ring: usize, mut vertices: Vec<SplineVertex>) -> Self {
+        let mut indices = Vec::new();
+
+        // Construct the vertices and normals for our mesh
+        vertices.truncate(first_ring * data.sides as usize);
+        for i in first_ring..frames.points.len() {
+            for s in 0..data.sides {
+                // Calculate our linearly interpolated roll value from the nearest control points
+                let t_value = frames.t_values[i];
+                let lower_i = (t_value as u32).min(data.points.len() as u32 - 1);
+                let inbetween_t = t_value - lower_i as f32;
+                let roll;
+                if data.is_loop() {
+                    // The segment after the last point of a loop leads back to the first point
+                    let next_i = (lower_i as usize + 1) % data.points.len();
+                    roll = data.points[lower_i as usize].roll * (1.0 - inbetween_t) + data.points[next_i].roll * inbetween_t;
+                }
+                else if lower_i == data.points.len() as u32 - 1 {
+                    // On the last point of our chain, so we can't interpolate with the
+                    // next point over. Thankfully, we don't need to interpolate at all.
+                    roll = data.points[lower_i as usize].roll;
+                }
+                else {
+                    roll = data.points[lower_i as usize].roll * (1.0 - inbetween_t) + data.points[lower_i as usize + 1].roll * inbetween_t;
+                }
+
+                // Calculate the position within the normal/binormal plane of our point
+                // We use sin_cos to form a linear combination of the normal and binormal
+                let angle = s as f32 / data.sides as f32 * std::f32::consts::TAU + Rad::<f32>::from(roll).0;
+                let poly_pos = angle.sin_cos();
+                let offset_dir = poly_pos.0 * frames.normals[i] + poly_pos.1 * frames.binormals[i];
+                let position = frames.points[i] + offset_dir * data.radius * frames.radii[i];
+
+                // Calculate the angle-weighted normal of our vertex
+                let angle_weighted_normal;
+                if !data.is_loop() && data.end_caps == CapStyle::Flat && (i == 0 || i == frames.points.len() - 1) {
+                   // Special case: the first/last subdivision have endcaps, so we need to
+                   // include those in the angle-weighted normal
+                   let endcap_angle = std::f32::consts::PI - (std::f32::consts::TAU / data.sides as f32);
+                   let endcap_normal;
+                   if i == 0 {
+                      // Normal of starting endcap points opposite the tangent direction
+                      endcap_normal = frames.tangents[i];
+                   }
+                   else {
+                      // Normal of starting endcap points in the tangent direction
+                      endcap_normal = -frames.tangents[i];
+                   }
+
+                   angle_weighted_normal = (offset_dir * std::f32::consts::PI + endcap_angle * endcap_normal).normalize();
+                }
+                else {
+                   // Without flat endcaps, the angle-weighted normal is exactly the offset direction
+                   angle_weighted_normal = offset_dir;
+                }
+
+                vertices.push(SplineVertex {
+                    position: position.into(),
+                    normal: angle_weighted_normal.into(),
+                    t_value,
+                });
+            }
+        }
+
+        // Rounded caps get their own rings, which bulge out along the tangent at each end
+        let is_loop = data.is_loop();
+        let last_ring = frames.points.len() - 1;
+        let mut cap_bases = [0, 0]; // Index of the first vertex of each rounded cap
+        if !is_loop && data.end_caps == CapStyle::Rounded {
+            for (cap_i, (ring_i, outward)) in [(0, -frames.tangents[0]), (last_ring, frames.tangents[last_ring])].into_iter().enumerate() {
+                cap_bases[cap_i] = vertices.len() as u32;
+                let center = frames.points[ring_i];
+                let radius = data.radius * frames.radii[ring_i];
+                let t_value = frames.t_values[ring_i];
+                // Reuse the end ring's directions so the cap's sides line up with the tube's. Without
+                // a flat cap, the normals of the ring are exactly those directions.
+                let directions: Vec<Vector3<f32>> = vertices[(ring_i * data.sides as usize)..((ring_i + 1) * data.sides as usize)].iter()
+                    .map(|v| Vector3::from(v.normal))
+                    .collect();
+                let steps = CapStyle::rounded_steps(data.sides);
+                for step in 1..steps {
+                    let (sin, cos) = (step as f32 / steps as f32 * std::f32::consts::FRAC_PI_2).sin_cos();
+                    for direction in directions.iter() {
+                        let normal = direction * cos + outward * sin;
+                        vertices.push(SplineVertex {
+                            position: (center + normal * radius).into(),
+                            normal: normal.into(),
+                            t_value,
+                        });
+                    }
+                }
+                vertices.push(SplineVertex {
+                    position: (center + outward * radius).into(),
+                    normal: outward.into(),
+                    t_value,
+                });
+            }
+        }
+
+        // Construct our indices to form the mesh
+        // End-cap for our first subdivision
+        if !is_loop {
+            match data.end_caps {
+                CapStyle::None => {},
+                CapStyle::Flat => {
+                    for i in 1..(data.sides - 1) {
+                        indices.push(0);
+                        indices.push(i);
+                        indices.push(i + 1);
+                    }
+                },
+                CapStyle::Rounded => {
+                    // Go from the tip back to the first ring of the tube, so the bands are wound
+                    // the same way as the tube's
+                    let steps = CapStyle::rounded_steps(data.sides);
+                    let ring_base = |step: u32| if step == 0 { 0 } else { cap_bases[0] + (step - 1) * data.sides };
+                    let tip_i = cap_bases[0] + (steps - 1) * data.sides;
+                    let first_base_i = ring_base(steps - 1);
+                    for i in 0..data.sides {
+                        let next_i = (i + 1) % data.sides;
+                        indices.push(tip_i);
+                        indices.push(first_base_i + i);
+                        indices.push(first_base_i + next_i);
+                    }
+                    for step in (1..steps).rev() {
+                        push_band(&mut indices, ring_base(step), ring_base(step - 1), data.sides);
+                    }
+                },
+            }
+        }
+        // Triangles between subdivisions. Loops also join the last ring back to the first.
+        let rings = frames.points.len();
+        let bands = if is_loop { rings } else { rings - 1 };
+        for subdiv in 0..bands {
+            let base_i = subdiv as u32 * data.sides;
+            let next_base_i = ((subdiv + 1) % rings) as u32 * data.sides;
+            push_band(&mut indices, base_i, next_base_i, data.sides);
+        }
+        // End-cap for our last subdivision
+        if !is_loop {
+            let end_base_i = last_ring as u32 * data.sides;
+            match data.end_caps {
+                CapStyle::None => {},
+                CapStyle::Flat => {
+                    for i in 1..(data.sides - 1) {
+                        indices.push(end_base_i);
+                        indices.push(end_base_i + i + 1);
+                        indices.push(end_base_i + i);
+                    }
+                },
+                CapStyle::Rounded => {
+                    let steps = CapStyle::rounded_steps(data.sides);
+                    let ring_base = |step: u32| if step == 0 { end_base_i } else { cap_bases[1] + (step - 1) * data.sides };
+                    for step in 1..steps {
+                        push_band(&mut indices, ring_base(step - 1), ring_base(step), data.sides);
+                    }
+                    let tip_i = cap_bases[1] + (steps - 1) * data.sides;
+                    let last_base_i = ring_base(steps - 1);
+                    for i in 0..data.sides {
+                        let next_i = (i + 1) % data.sides;
+                        indices.push(last_base_i + next_i);
+                        indices.push(last_base_i + i);
+                        indices.push(tip_i);
+                    }
+                },
+            }
+        }
+
+        // Every ring gets a vertex per side, and every index has to land on one of our vertices
+        let cap_vertices = if is_loop { 0 } else { data.end_caps.counts(data.sides).0 };
+        debug_assert_eq!(vertices.len(), frames.points.len() * data.sides as usize + 2 * cap_vertices);
+        debug_assert!(indices.len() % 3 == 0 && indices.iter().all(|&i| (i as usize) < vertices.len()), "spline mesh index out of range");
+
+        Self {
+            vertices,
+            indices,
         }
     }
 }
 
+// The t, as taken by SplineData::sample, at distance units along the rings of frames built from
+// data. We walk the rings until we reach the distance, then interpolate the t value between them.
+// A loop's last stretch leads back to the first ring.
+fn arc_length_t(data: &SplineData, frames: &SplineFrames, distance: f32) -> f32 {
+    let segments = data.segment_count() as f32;
+    let mut rings: Vec<(Point3<f32>, f32)> = frames.points.iter().copied().zip(frames.t_values.iter().copied()).collect();
+    if data.is_loop() {
+        rings.push((frames.points[0], segments));
+    }
+    let mut remaining = distance.max(0.0);
+    for pair in rings.windows(2) {
+        let (start, start_t) = pair[0];
+        let (end, end_t) = pair[1];
+        let step = start.distance(end);
+        if remaining <= step && step > 0.0 {
+            return (start_t + (end_t - start_t) * remaining / step) / segments;
+        }
+        remaining -= step;
+    }
+    1.0
+}
+
+// Construct the slice of f32s uploaded to the point color buffer, holding the color at each
+// control point, along with the gizmo drawn for each point. The whole buffer gets rewritten, so
+// the slots past the last point are zeroed rather than keeping the colors of deleted points.
+// Points past the limit have no slot of their own, and the shader clamps them to the last one.
+fn point_colors(data: &SplineData, is_selected: impl Fn(usize) -> bool, hovered_point: Option<u32>, render_origin: Vector3<f32>) -> (Vec<f32>, Vec<GizmoInstance>) {
+    let mut color_vec = vec![0.0; MAX_POINTS_PER_SPLINE * 4];
+    // The gizmos are always opaque
+    let mut gizmo_instances = Vec::with_capacity(data.points.len().min(MAX_POINTS_PER_SPLINE));
+    for (i, point) in data.points.iter().enumerate().take(MAX_POINTS_PER_SPLINE) {
+        let mut color_rgba;
+        let gizmo_color;
+        let mut gizmo_size = data.radius * point.radius.abs() * GIZMO_SIZE_PER_RADIUS;
+        if is_selected(i) {
+            // Current point is selected, so set to the inverse color
+            color_rgba = Rgba::from(point.color.to_opaque());
+            color_rgba = Rgba::from_rgb(1.0 - color_rgba.r(), 1.0 - color_rgba.g(), 1.0 - color_rgba.b());
+            gizmo_color = color_rgba;
+            gizmo_size *= SELECTED_GIZMO_SCALE;
+        }
+        else if hovered_point == Some(i as u32) {
+            // Current point is hovered, so tint it with our highlight color
+            color_rgba = Rgba::from(point.color.to_opaque()) * 0.5 + Rgba::from(HOVER_COLOR) * 0.5;
+            gizmo_color = color_rgba;
+        }
+        else {
+            // Color32 is premultiplied in gamma space, so converting it straight to linear
+            // darkens translucent colors. Unmultiply first, then premultiply in linear space
+            // to match our premultiplied alpha blending.
+            let [r, g, b, a] = point.color.to_srgba_unmultiplied();
+            color_rgba = Rgba::from_srgba_unmultiplied(r, g, b, a);
+            gizmo_color = Rgba::from(point.color.to_opaque());
+        }
+        gizmo_instances.push(GizmoInstance {
+            center: (point.position.to_vec() - render_origin).into(),
+            size: gizmo_size,
+            color: gizmo_color.to_array(),
+        });
+        let (r, g, b, a) = color_rgba.to_tuple();
+        color_vec[i * 4] = r;
+        color_vec[i * 4 + 1] = g;
+        color_vec[i * 4 + 2] = b;
+        color_vec[i * 4 + 3] = a;
+    }
+    // The segment closing a loop blends towards the color after the last point, so that slot
+    // takes the first point's color
+    let num_points = data.points.len();
+    if data.is_loop() && num_points < MAX_POINTS_PER_SPLINE {
+        color_vec.copy_within(0..4, num_points * 4);
+    }
+
+    (color_vec, gizmo_instances)
+}
+
+// The render mesh of a spline as plain vertices and triangle indices, without touching the GPU.
+// Spline::update builds the same mesh, but reuses the part before the first edited segment.
+pub fn build_spline_mesh(data: &SplineData) -> (Vec<SplineVertex>, Vec<u32>) {
+    let mesh = SplineMesh::new(data, data.render_subdivisions);
+    (mesh.vertices, mesh.indices)
+}
+
+// Two triangles per side joining a ring to the next one along the tangent, wound CCW from outside
+fn push_band(indices: &mut Vec<u32>, base_i: u32, next_base_i: u32, sides: u32) {
+    for i in 0..sides {
+        let next_i = (i + 1) % sides;
+        indices.push(base_i + next_i);
+        indices.push(base_i + i);
+        indices.push(next_base_i + next_i);
+
+        indices.push(base_i + i);
+        indices.push(next_base_i + i);
+        indices.push(next_base_i + next_i);
+    }
+}
+
+// GPU copy of a spline mesh
+struct SplineBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    vertex_count: usize,
+    index_count: u32,
+}
+
+impl SplineBuffers {
+    // Vertex positions get uploaded relative to origin
+    fn new(device: &wgpu::Device, vertices: &[SplineVertex], indices: &[u32], origin: Vector3<f32>) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Spline Vertex Buffer"),
+            contents: bytemuck::cast_slice(&relative_vertices(vertices, origin)),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Spline Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            vertex_count: vertices.len(),
+            index_count: indices.len() as u32,
+        }
+    }
+
+    // Upload a new mesh, writing over the old buffers instead of reallocating them if it's the
+    // same size. Dragging a value around usually keeps the size, so this saves an allocation per frame.
+    fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, vertices: &[SplineVertex], indices: &[u32], origin: Vector3<f32>) {
+        if vertices.is_empty() || vertices.len() != self.vertex_count || indices.len() != self.index_count as usize {
+            *self = Self::new(device, vertices, indices, origin);
+            return;
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&relative_vertices(vertices, origin)));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(indices));
+    }
+
+    fn empty(device: &wgpu::Device) -> Self {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Spline Vertex Buffer"),
+            size: 0,
+            usage: wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Spline Index Buffer"),
+            size: 0,
+            usage: wgpu::BufferUsages::INDEX,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            vertex_count: 0,
+            index_count: 0,
+        }
+    }
+}
+
+fn relative_vertices(vertices: &[SplineVertex], origin: Vector3<f32>) -> Vec<SplineVertex> {
+    vertices.iter().map(|v| SplineVertex {
+        position: (Vector3::from(v.position) - origin).into(),
+        ..*v
+    }).collect()
+}
+
 // Struct that handles the rendering of spline instances. Separate from Spline so that we can
 // freely draw multiple Splines without maintaining separate copies of our rendering state
 pub struct SplineRenderer {
     solid_render_pipeline: wgpu::RenderPipeline,
+    culled_render_pipeline: wgpu::RenderPipeline,
     wireframe_render_pipeline: wgpu::RenderPipeline,
+    xray_render_pipeline: wgpu::RenderPipeline,
+    gizmo_render_pipeline: wgpu::RenderPipeline,
+    gizmo_vertex_buffer: wgpu::Buffer,
+    gizmo_index_buffer: wgpu::Buffer,
     point_colors_bind_group_layout: wgpu::BindGroupLayout,
+    // Kept around so the pipelines can be rebuilt when the sample count changes
+    format: wgpu::TextureFormat,
+    shader: wgpu::ShaderModule,
+    gizmo_shader: wgpu::ShaderModule,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    gizmo_pipeline_layout: wgpu::PipelineLayout,
 }
 
 impl SplineRenderer {
-    pub fn new(render_state: &RenderState, camera_layout: &wgpu::BindGroupLayout) -> Self {
-        let shader = render_state.device.create_shader_module(wgpu::include_wgsl!("spline_shader.wgsl"));
+    // Takes the device and target format rather than a RenderState so that we can also render
+    // without a window
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, camera_layout: &wgpu::BindGroupLayout, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("spline_shader.wgsl"));
 
-        let point_colors_bind_group_layout = render_state.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        let point_colors_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
@@ -540,7 +2083,7 @@ impl SplineRenderer {
             label: Some("point_colors_bind_group_layout"),
         });
 
-        let render_pipeline_layout = render_state.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Spline Render Pipeline Layout"),
             bind_group_layouts: &[
                 camera_layout,
@@ -549,105 +2092,71 @@ impl SplineRenderer {
             push_constant_ranges: &[],
         });
 
-        let solid_render_pipeline = render_state.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Solid Spline Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    SplineVertex::desc(),
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: render_state.config.format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
+        let solid_render_pipeline = create_spline_pipeline(device, format, sample_count, &render_pipeline_layout, &shader, &SOLID_PIPELINE);
+        let culled_render_pipeline = create_spline_pipeline(device, format, sample_count, &render_pipeline_layout, &shader, &CULLED_PIPELINE);
+        let wireframe_render_pipeline = create_spline_pipeline(device, format, sample_count, &render_pipeline_layout, &shader, &WIREFRAME_PIPELINE);
+        let xray_render_pipeline = create_xray_pipeline(device, format, sample_count, &render_pipeline_layout, &shader);
+
+        let gizmo_shader = device.create_shader_module(wgpu::include_wgsl!("gizmo_shader.wgsl"));
+        let gizmo_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gizmo Render Pipeline Layout"),
+            bind_group_layouts: &[
+                camera_layout,
+            ],
+            push_constant_ranges: &[],
         });
+        let gizmo_render_pipeline = create_gizmo_pipeline(device, format, sample_count, &gizmo_pipeline_layout, &gizmo_shader);
 
-        let wireframe_render_pipeline = render_state.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Wireframe Spline Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    SplineVertex::desc(),
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: render_state.config.format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                // A line list with a properly built mesh would be a more accurate wireframe, but
-                // with line strip I can use the existing triangle list mesh for a decent wireframe
-                topology: wgpu::PrimitiveTopology::LineStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
+        let gizmo_vertices: Vec<GizmoVertex> = GIZMO_VERTICES.iter().map(|&position| GizmoVertex { position }).collect();
+        let gizmo_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Vertex Buffer"),
+            contents: bytemuck::cast_slice(&gizmo_vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let gizmo_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Index Buffer"),
+            contents: bytemuck::cast_slice(&GIZMO_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
         });
 
         SplineRenderer {
             solid_render_pipeline,
+            culled_render_pipeline,
             wireframe_render_pipeline,
+            xray_render_pipeline,
+            gizmo_render_pipeline,
+            gizmo_vertex_buffer,
+            gizmo_index_buffer,
             point_colors_bind_group_layout,
+            format,
+            shader,
+            gizmo_shader,
+            render_pipeline_layout,
+            gizmo_pipeline_layout,
         }
     }
 
-    pub fn draw<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup, spline: &'s Ref<Spline>) {
-        if spline.data.bundle {
+    // Rebuild the pipelines to render to targets with a different number of samples. Spline
+    // instances hold bind groups from our layouts, so we keep those and only replace the pipelines.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.solid_render_pipeline = create_spline_pipeline(device, self.format, sample_count, &self.render_pipeline_layout, &self.shader, &SOLID_PIPELINE);
+        self.culled_render_pipeline = create_spline_pipeline(device, self.format, sample_count, &self.render_pipeline_layout, &self.shader, &CULLED_PIPELINE);
+        self.wireframe_render_pipeline = create_spline_pipeline(device, self.format, sample_count, &self.render_pipeline_layout, &self.shader, &WIREFRAME_PIPELINE);
+        self.xray_render_pipeline = create_xray_pipeline(device, self.format, sample_count, &self.render_pipeline_layout, &self.shader);
+        self.gizmo_render_pipeline = create_gizmo_pipeline(device, self.format, sample_count, &self.gizmo_pipeline_layout, &self.gizmo_shader);
+    }
+
+    // With xray, the spline is drawn faintly on top of everything regardless of depth
+    pub fn draw<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup, spline: &'s Ref<Spline>, use_lod: bool, xray: bool) {
+        if xray {
+            render_pass.set_pipeline(&self.xray_render_pipeline);
+        }
+        else if spline.data.bundle {
             render_pass.set_pipeline(&self.wireframe_render_pipeline);
         }
+        else if spline.opaque {
+            render_pass.set_pipeline(&self.culled_render_pipeline);
+        }
         else {
             render_pass.set_pipeline(&self.solid_render_pipeline);
         }
@@ -655,9 +2164,471 @@ impl SplineRenderer {
         render_pass.set_bind_group(0, camera_bind_group, &[]);
         render_pass.set_bind_group(1, &spline.point_colors_bind_group, &[]);
 
-        render_pass.set_vertex_buffer(0, spline.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(spline.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        let buffers;
+        if use_lod {
+            buffers = &spline.lod_buffers;
+        }
+        else {
+            buffers = &spline.buffers;
+        }
+        render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        render_pass.draw_indexed(0..buffers.index_count, 0, 0..1);
+    }
+
+    // Draw the straight lines between the spline's control points
+    pub fn draw_control_polygon<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup, spline: &'s Ref<Spline>) {
+        let buffers = &spline.control_polygon_buffers;
+        if buffers.index_count < 2 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.wireframe_render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &spline.point_colors_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..buffers.index_count, 0, 0..1);
+    }
+
+    // Draw a cube at each of the spline's control points, with the selected points enlarged
+    pub fn draw_point_gizmos<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup, spline: &'s Ref<Spline>) {
+        let instance_count = spline.data.points.len().min(MAX_POINTS_PER_SPLINE) as u32;
+        self.draw_gizmo_instances(render_pass, camera_bind_group, &spline.gizmo_instance_buffer, instance_count);
+    }
+
+    // Draw a gizmo for each of the first instance_count GizmoInstances in instance_buffer
+    pub fn draw_gizmo_instances<'s>(&'s self, render_pass: &mut wgpu::RenderPass<'s>, camera_bind_group: &'s wgpu::BindGroup, instance_buffer: &'s wgpu::Buffer, instance_count: u32) {
+        if instance_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.gizmo_render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.gizmo_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.gizmo_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..GIZMO_INDICES.len() as u32, 0, 0..instance_count);
+    }
+}
+
+// The spline pipelines only differ in how their primitives are assembled, so share the rest
+struct SplinePipelineStyle {
+    label: &'static str,
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+}
+
+const SOLID_PIPELINE: SplinePipelineStyle = SplinePipelineStyle {
+    label: "Solid Spline Render Pipeline",
+    topology: wgpu::PrimitiveTopology::TriangleList,
+    cull_mode: None,
+};
+// Opaque splines can't be seen into, so we can cull the inside of the tube
+const CULLED_PIPELINE: SplinePipelineStyle = SplinePipelineStyle {
+    label: "Culled Spline Render Pipeline",
+    topology: wgpu::PrimitiveTopology::TriangleList,
+    cull_mode: Some(wgpu::Face::Back),
+};
+// A line list with a properly built mesh would be a more accurate wireframe, but with line strip I
+// can use the existing triangle list mesh for a decent wireframe
+const WIREFRAME_PIPELINE: SplinePipelineStyle = SplinePipelineStyle {
+    label: "Wireframe Spline Render Pipeline",
+    topology: wgpu::PrimitiveTopology::LineStrip,
+    cull_mode: None,
+};
+
+fn create_spline_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, style: &SplinePipelineStyle) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(style.label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                SplineVertex::desc(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: style.topology,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: style.cull_mode,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// Pipeline for the faint see-through preview of a spline. It ignores the depth buffer, and leaves it
+// alone so whatever gets drawn after isn't hidden by it.
+fn create_xray_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("X-Ray Spline Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                SplineVertex::desc(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_xray",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // Only the near side of the tube, otherwise the overlapping layers make it less faint
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// Pipeline for the cubes marking control points
+fn create_gizmo_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Gizmo Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                GizmoVertex::desc(),
+                GizmoInstance::desc(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A spline running along +X with a point every 100 units. Tangents match the chords, so the
+    // curve is a straight line traversed at constant speed.
+    pub(crate) fn straight_spline(num_points: usize, sides: u32) -> SplineData {
+        let points = (0..num_points)
+            .map(|i| SplineControlPoint::new(Point3::new(i as f32 * 100.0, 0.0, 0.0), Deg(0.0), Deg(0.0), 100.0))
+            .collect();
+        SplineData {
+            points,
+            radius: 4.0,
+            sides,
+            subdivisions: 4,
+            render_subdivisions: SubdivMode::Fixed(4),
+            name: "test".to_string(),
+            bundle: false,
+            color_seed: 0,
+            interpolation: InterpolationMode::Hermite,
+            tension: tension_default(),
+            closed: false,
+            end_caps: CapStyle::None,
+        }
+    }
+
+    fn position(vertex: &SplineVertex) -> Vector3<f32> {
+        Vector3::from(vertex.position)
+    }
+
+    #[test]
+    fn straight_tube_has_a_ring_per_subdivision() {
+        let data = straight_spline(2, 3);
+        let mesh = SplineMesh::new(&data, SubdivMode::Fixed(4));
+        let rings = 4 + 1;
+        assert_eq!(mesh.vertices.len(), rings * 3);
+        assert_eq!(mesh.indices.len(), (rings - 1) * 3 * 2 * 3);
+    }
+
+    #[test]
+    fn mesh_has_a_ring_per_subdivision_and_valid_indices() {
+        for (num_points, subdivisions, sides) in [(2, 1, 3), (3, 4, 5), (5, 16, 8)] {
+            for end_caps in [CapStyle::None, CapStyle::Flat, CapStyle::Rounded] {
+                let mut data = straight_spline(num_points, sides);
+                data.render_subdivisions = SubdivMode::Fixed(subdivisions);
+                data.subdivisions = subdivisions;
+                data.end_caps = end_caps;
+                let (vertices, indices) = build_spline_mesh(&data);
+
+                let segments = num_points - 1;
+                let (cap_vertices, _) = end_caps.counts(sides);
+                assert_eq!(vertices.len(), (segments * subdivisions as usize + 1) * sides as usize + 2 * cap_vertices);
+                assert_eq!(indices.len() % 3, 0);
+                assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+                assert_eq!(SplineMesh::counts(&data, subdivisions), (vertices.len(), indices.len() / 3));
+            }
+        }
+    }
+
+    fn assert_frames_finite(frames: &SplineFrames) {
+        for vector in frames.tangents.iter().chain(frames.normals.iter()).chain(frames.binormals.iter()) {
+            assert!(vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite(), "non-finite frame vector {vector:?}");
+        }
+    }
+
+    #[test]
+    fn too_few_points_build_no_mesh() {
+        for num_points in [0, 1] {
+            let data = straight_spline(num_points, 3);
+            assert!(!data.has_tube());
+            let frames = SplineFrames::new(&data, data.render_subdivisions);
+            assert_eq!(frames.points.len(), num_points);
+            assert_frames_finite(&frames);
+            let mesh = SplineMesh::new(&data, data.render_subdivisions);
+            assert!(mesh.vertices.is_empty() && mesh.indices.is_empty());
+            assert_eq!(SplineMesh::counts(&data, data.subdivisions), (0, 0));
+        }
+    }
+
+    #[test]
+    fn too_few_sides_build_no_mesh_until_sanitized() {
+        for sides in [1, 2] {
+            let mut data = straight_spline(2, sides);
+            data.end_caps = CapStyle::Flat;
+            assert!(!data.has_tube());
+            assert_frames_finite(&SplineFrames::new(&data, data.render_subdivisions));
+            let mesh = SplineMesh::new(&data, data.render_subdivisions);
+            assert!(mesh.vertices.is_empty() && mesh.indices.is_empty());
+
+            data.sanitize();
+            assert_eq!(data.sides, MIN_SIDES);
+            let mesh = SplineMesh::new(&data, data.render_subdivisions);
+            assert_eq!(mesh.vertices.len(), 5 * MIN_SIDES as usize);
+            assert!(mesh.vertices.iter().all(|v| v.position.iter().chain(v.normal.iter()).all(|c| c.is_finite())));
+        }
+    }
+
+    #[test]
+    fn incremental_rebuild_matches_full_rebuild() {
+        // A wiggly spline, so that the frames twist along it
+        let mut data = straight_spline(6, 5);
+        data.end_caps = CapStyle::Rounded;
+        for (i, point) in data.points.iter_mut().enumerate() {
+            point.position.y = if i % 2 == 0 { 40.0 } else { -40.0 };
+            point.position.z = i as f32 * 25.0;
+            point.yaw = Deg(i as f32 * 30.0);
+            point.pitch = Deg(10.0);
+        }
+        let frames = SplineFrames::new(&data, data.render_subdivisions);
+        let mesh = SplineMesh::from_frames(&data, &frames);
+
+        let mut edited = data.clone();
+        edited.points[3].position += Vector3::new(15.0, -20.0, 30.0);
+        edited.points[3].roll = Deg(45.0);
+        let first_segment = edited.first_changed_segment(&data).unwrap();
+        assert_eq!(first_segment, 2);
+
+        let mut incremental = frames;
+        let first_ring = incremental.rebuild_from(&edited, edited.render_subdivisions, first_segment);
+        assert!(first_ring > 0);
+        let full = SplineFrames::new(&edited, edited.render_subdivisions);
+        assert_eq!(incremental.points, full.points);
+        assert_eq!(incremental.tangents, full.tangents);
+        assert_eq!(incremental.radii, full.radii);
+        assert_eq!(incremental.normals, full.normals);
+        assert_eq!(incremental.binormals, full.binormals);
+        assert_eq!(incremental.t_values, full.t_values);
+        assert_eq!(incremental.point_indices, full.point_indices);
+
+        let incremental_mesh = SplineMesh::from_frames_reusing(&edited, &incremental, first_ring, mesh.vertices);
+        let full_mesh = SplineMesh::from_frames(&edited, &full);
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&incremental_mesh.vertices), bytemuck::cast_slice::<_, u8>(&full_mesh.vertices));
+        assert_eq!(incremental_mesh.indices, full_mesh.indices);
+    }
 
-        render_pass.draw_indexed(0..spline.index_count, 0, 0..1);
+    #[test]
+    fn straight_tube_triangles_face_outward() {
+        // A straight tube is convex, so every triangle faces away from any point inside it
+        for end_caps in [CapStyle::None, CapStyle::Flat, CapStyle::Rounded] {
+            let mut data = straight_spline(2, 3);
+            data.end_caps = end_caps;
+            let mesh = SplineMesh::new(&data, SubdivMode::Fixed(4));
+            let inside = Vector3::new(50.0, 0.0, 0.0);
+            for triangle in mesh.indices.chunks(3) {
+                let [v0, v1, v2] = [0, 1, 2].map(|i| position(&mesh.vertices[triangle[i] as usize]));
+                let normal = (v1 - v0).cross(v2 - v0);
+                let centroid = (v0 + v1 + v2) / 3.0;
+                assert!(normal.dot(centroid - inside) > 0.0, "triangle {triangle:?} faces inward with {end_caps} caps");
+            }
+        }
+    }
+
+    fn assert_frames_orthonormal(frames: &SplineFrames) {
+        assert_frames_finite(frames);
+        for i in 0..frames.points.len() {
+            let (tangent, normal, binormal) = (frames.tangents[i], frames.normals[i], frames.binormals[i]);
+            assert!((normal.magnitude() - 1.0).abs() < 1e-4 && (binormal.magnitude() - 1.0).abs() < 1e-4);
+            assert!(normal.dot(tangent).abs() < 1e-4 && binormal.dot(tangent).abs() < 1e-4 && normal.dot(binormal).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn straight_tube_frames_are_orthonormal() {
+        assert_frames_orthonormal(&SplineFrames::new(&straight_spline(3, 3), SubdivMode::Fixed(4)));
+    }
+
+    #[test]
+    fn vertical_start_gives_orthonormal_frames() {
+        // Exactly vertical handles make the first tangent parallel to the Z axis the frames are
+        // normally seeded from
+        for direction in [1.0, -1.0] {
+            let mut data = straight_spline(2, 3);
+            for (i, point) in data.points.iter_mut().enumerate() {
+                point.position = Point3::new(0.0, 0.0, direction * 100.0 * i as f32);
+                let handle = Vector3::new(0.0, 0.0, direction * 30.0);
+                point.handles = Some((-handle, handle));
+            }
+            assert_eq!(data.points[0].tangent_direction(), Vector3::new(0.0, 0.0, direction));
+            assert_frames_orthonormal(&SplineFrames::new(&data, data.render_subdivisions));
+        }
+    }
+
+    #[test]
+    fn removed_points_leave_no_stale_colors() {
+        let mut data = straight_spline(100, 3);
+        for (i, point) in data.points.iter_mut().enumerate() {
+            point.color = Color32::from_rgb(i as u8 + 1, 0, 0);
+        }
+        data.points.truncate(5);
+        let (colors, gizmos) = point_colors(&data, |_| false, None, Vector3::zero());
+        assert_eq!(colors.len(), MAX_POINTS_PER_SPLINE * 4);
+        assert_eq!(gizmos.len(), 5);
+        for (i, point) in data.points.iter().enumerate() {
+            assert_eq!(colors[i * 4..i * 4 + 4], Rgba::from(point.color).to_array());
+        }
+        assert!(colors[5 * 4..].iter().all(|&c| c == 0.0));
+    }
+
+    #[test]
+    fn translucent_colors_are_premultiplied_in_linear_space() {
+        // Half transparent orange. Color32 only keeps it premultiplied in gamma space, so allow
+        // for the rounding that leaves in the unmultiplied channels.
+        let mut data = straight_spline(2, 3);
+        data.points[0].color = Color32::from_rgba_unmultiplied(255, 128, 0, 128);
+        let (colors, _) = point_colors(&data, |_| false, None, Vector3::zero());
+
+        let alpha = 128.0 / 255.0;
+        let linear = |srgb: f32| ((srgb + 0.055) / 1.055).powf(2.4);
+        let expected = [linear(1.0) * alpha, linear(128.0 / 255.0) * alpha, 0.0, alpha];
+        for (channel, expected) in colors[..4].iter().zip(expected) {
+            assert!((channel - expected).abs() < 2e-3, "{:?} should be {expected}", &colors[..4]);
+        }
+    }
+
+    #[test]
+    fn straight_spline_samples_its_midpoint() {
+        let data = straight_spline(2, 3);
+        let (position, tangent) = data.sample(0.5);
+        assert!((position - Point3::new(50.0, 0.0, 0.0)).magnitude() < 1e-4);
+        assert!((tangent - Vector3::unit_x()).magnitude() < 1e-4);
+
+        // Sampling by arc length the way Spline::sample_by_arc_length does, from frames built once
+        let frames = SplineFrames::new(&data, data.render_subdivisions);
+        let (position, _) = data.sample(arc_length_t(&data, &frames, 50.0));
+        assert!((position - Point3::new(50.0, 0.0, 0.0)).magnitude() < 1e-3);
+        // Distances past either end stay on the spline
+        assert_eq!(data.sample(arc_length_t(&data, &frames, -10.0)).0, data.points[0].position);
+        assert!((data.sample(arc_length_t(&data, &frames, 1000.0)).0 - data.points[1].position).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn empty_spline_samples_the_origin() {
+        let data = straight_spline(0, 3);
+        assert_eq!(data.sample(0.5), (Point3::origin(), Vector3::unit_x()));
+        let frames = SplineFrames::new(&data, data.render_subdivisions);
+        assert_eq!(data.sample(arc_length_t(&data, &frames, 10.0)), (Point3::origin(), Vector3::unit_x()));
+    }
+
+    #[test]
+    fn single_point_has_a_marker_but_no_tube() {
+        for closed in [false, true] {
+            let mut data = straight_spline(1, 3);
+            data.closed = closed;
+            data.end_caps = CapStyle::Rounded;
+            let (vertices, indices) = build_spline_mesh(&data);
+            assert!(vertices.is_empty() && indices.is_empty());
+            let polygon = SplineMesh::control_polygon(&data);
+            assert_eq!(polygon.vertices.len(), 1);
+            assert_eq!(polygon.indices, vec![0]);
+            assert_eq!(data.sample(0.5).0, data.points[0].position);
+        }
     }
 }
@@ -13,6 +13,7 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) normal: vec3<f32>,
 };
 
 @group(0) @binding(0)
@@ -30,13 +31,43 @@ fn vs_main(
     out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
     let lower_t = floor(model.t_value);
     let interp_t = model.t_value - lower_t;
-    out.color = point_colors[u32(lower_t)] * (1.0 - interp_t) + point_colors[u32(lower_t) + 1u] * interp_t;
+    // Points past the limit have no color slot of their own, so clamp them to the last one
+    let lower_index = min(u32(lower_t), 1023u);
+    let upper_index = min(u32(lower_t) + 1u, 1023u);
+    out.color = point_colors[lower_index] * (1.0 - interp_t) + point_colors[upper_index] * interp_t;
+    out.normal = model.normal;
     return out;
 }
 
 // Fragment shader
 
+// Same light as the map's lit view modes
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.3, 0.5, 0.81);
+
+// Entry points can't be called from other functions, so the x-ray entry point shares this instead
+fn shade(in: VertexOutput, front_facing: bool) -> vec4<f32> {
+    // The control polygon has no normals, so it stays unlit
+    if dot(in.normal, in.normal) == 0.0 {
+        return in.color;
+    }
+
+    // See-through tubes show their inside, which faces the other way
+    let normal = select(-normalize(in.normal), normalize(in.normal), front_facing);
+    let lighting = 0.4 + 0.6 * max(dot(normal, normalize(LIGHT_DIRECTION)), 0.0);
+    // Colors are premultiplied, so scaling every channel but alpha keeps them valid
+    return vec4<f32>(in.color.rgb * lighting, in.color.a);
+}
+
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    return shade(in, front_facing);
+}
+
+// How much of the spline shows through in the x-ray preview
+const XRAY_ALPHA: f32 = 0.25;
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color;
+fn fs_xray(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    // Scaling every channel keeps the premultiplied color valid while making it fainter
+    return shade(in, front_facing) * XRAY_ALPHA;
 }